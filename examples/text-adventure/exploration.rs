@@ -0,0 +1,67 @@
+//! this module doesn't play the game, it walks every path through it.
+//!
+//! The game is translated to the Console effect, like for playing it, and every way to pick from the options gets tried.
+//! Since the player can walk between rooms for as long as they like, the paths are cut off after a number of steps. The number
+//! of paths grows quickly with it, so the default only finds the short endings. Try `--explore 48` to find more.
+
+use std::collections::BTreeMap;
+
+use higher_free_macro::effects::console::{Console, ConsoleInput};
+use higher_free_macro::interpret::explore::explore;
+
+use crate::dsl::FreeSausageRoll;
+use crate::side_effects::to_console;
+
+const MAX_PATHS: usize = 1_000_000;
+
+pub fn print_endings<'a, 's: 'a>(game: FreeSausageRoll<'a, 's, ()>, max_depth: usize) {
+    let report = explore(
+        to_console(game),
+        max_depth,
+        MAX_PATHS,
+        |layer| match layer {
+            Console::PrintLine(line, _) => Some(line.clone()),
+            _ => None,
+        },
+    );
+
+    //the ending is the last thing said. For each one, remember the shortest way to get there.
+    let mut endings: BTreeMap<String, (usize, Vec<ConsoleInput>)> = BTreeMap::new();
+    for ((), path) in report.finished {
+        let last_line = path
+            .trace
+            .iter()
+            .rev()
+            .flatten()
+            .find(|line| !line.is_empty());
+        let ending = last_line.cloned().unwrap_or_default();
+        let (count, shortest) = endings.entry(ending).or_insert((0, path.inputs.clone()));
+        *count += 1;
+        if path.inputs.len() < shortest.len() {
+            *shortest = path.inputs;
+        }
+    }
+
+    for (ending, (count, shortest)) in &endings {
+        let choices: Vec<_> = shortest
+            .iter()
+            .map(|input| match input {
+                ConsoleInput::Choice(choice) => (choice + 1).to_string(),
+                ConsoleInput::Line(line) => line.clone(),
+            })
+            .collect();
+        println!("{ending}");
+        println!(
+            "    reached by {count} paths, for instance by choosing {}",
+            choices.join(", ")
+        );
+    }
+    println!();
+    println!(
+        "{} paths were cut off after {max_depth} steps.",
+        report.cut_off.len()
+    );
+    if !report.exhaustive {
+        println!("The exploration stopped after {MAX_PATHS} paths.");
+    }
+}
@@ -1,33 +1,26 @@
 //! This module does nothing. It just creates the game's high level flow encoded as Free Monad.
 
 use std::borrow::Cow;
+use std::ops::ControlFlow;
 
 use super::data::{Inventory, Item, Location, Mood, Speaker};
 use super::dsl::{
     exposition, give_player_options, present_location, say_dialogue_line, FreeSausageRoll,
 };
 use higher::{run, Bind, Functor, Pure};
-
-//Haskell has a when function, and it's nice. Sooo, copy that.
-macro_rules! when {
-    {$a:expr => {$($b:tt)*}} => {
-        if($a){
-            run!{$($b)*}
-        } else {
-            run!{ yield () }
-        }
-    };
-}
+use higher_free_macro::control::when;
+use higher_free_macro::free_do;
 
 pub fn game<'a, 's: 'a>() -> FreeSausageRoll<'a, 's, ()> {
-    run! {
+    //free_do! is the do-notation that comes with higher-free-macro. Unlike run! it can destructure bound values.
+    free_do! {
         c <= intro();
-        when!{c => {
+        if c {
             //handle_rooms is the main game loop: Go from room to room.
-            c <= handle_rooms(Location::Refrigerators, Inventory::default()); //can't destructure in assignment in higher-0.2. Maybe later.
+            (_, inventory) <= handle_rooms(Location::Refrigerators, Inventory::default());
             //if we ended up here, we left the supermarket.
-            ending(c.1)
-        }}
+            ending(inventory)
+        }
     }
 }
 
@@ -66,9 +59,8 @@ fn ending<'a, 's: 'a>(inventory: Inventory) -> FreeSausageRoll<'a, 's, ()> {
     } else {
         run! {
             say_dialogue_line(Speaker::Partner, Cow::from("What did you do in there? I asked you to bring me a sausage roll..."), Mood::Annoyed);
-            when!{!inventory.items.is_empty() => {
-                say_dialogue_line(Speaker::Partner, Cow::from("Also, why did you buy all that other stuff?"), Mood::Annoyed)
-            }};
+            //Haskell has a when function, and it's nice. higher-free-macro has one too.
+            when(!inventory.items.is_empty(), say_dialogue_line(Speaker::Partner, Cow::from("Also, why did you buy all that other stuff?"), Mood::Annoyed));
             say_dialogue_line(Speaker::Partner, Cow::from("Well, let's move on, but don't complain if I get hangry on the way."), Mood::Annoyed)
         }
     }
@@ -78,15 +70,17 @@ fn handle_rooms<'a, 's: 'a>(
     room: Location,
     inventory: Inventory,
 ) -> FreeSausageRoll<'a, 's, (Location, Inventory)> {
-    run! {
-        c <= handle_room(room, inventory);
-        if c.0 == Location::Entrance {
-            FreeSausageRoll::pure(c)
-        } else {
-            //If this were an actual game, we could put a save-point here. At this location the next room to handle is just determined by room and inventory.
-            handle_rooms(c.0, c.1)
-        }
-    }
+    //tail_rec_m only builds the next iteration once the player has made a choice, so the loop can run for as long as the player likes.
+    FreeSausageRoll::tail_rec_m((room, inventory), |(room, inventory)| {
+        handle_room(room, inventory).fmap(|(room, inventory)| {
+            if room == Location::Entrance {
+                ControlFlow::Break((room, inventory))
+            } else {
+                //If this were an actual game, we could put a save-point here. At this location the next room to handle is just determined by room and inventory.
+                ControlFlow::Continue((room, inventory))
+            }
+        })
+    })
 }
 
 fn handle_room<'a, 's: 'a>(
@@ -219,7 +213,7 @@ fn handle_checkout<'a, 's: 'a>(
                 run!{
                     r <= if inventory.items.is_empty() {
                         let inventory = inventory.clone();
-                        exposition("You didn't take anything in the store, so you just walk past the cashier.").bind(move |_| FreeSausageRoll::pure(Ok(inventory.clone()))) as FreeSausageRoll<Result<Inventory, Inventory>>
+                        exposition("You didn't take anything in the store, so you just walk past the cashier.").bind(move |()| FreeSausageRoll::pure(Ok(inventory.clone()))) as FreeSausageRoll<Result<Inventory, Inventory>>
                     } else {
                         try_pay(inventory.clone())
                     };
@@ -259,31 +253,34 @@ fn try_take_item<'a, 's: 'a>(
     options: Vec<Item>,
 ) -> FreeSausageRoll<'a, 's, Inventory> {
     //here we run into an "interesting" issue with Rust's ownership and do-notation.
-    //We would like to capture inventory and use it in bind-notation, but that doesn't work (except in the first 2 lines), because Inventory isn't Copy.
-    //This leaves us with a couple of options: We can either pass it through by repeated cloning (done here), or leave do-notation before capturing it.
-    run! {
-        i <= exposition("You look around and these items nearby catch your attention.").fmap(move |_| (inventory.clone(), options.clone()));
-        o <= give_player_options(i.1.iter().map(|o| o.description()).chain(std::iter::once("Cancel")).collect()).fmap(move |c| (c, i.0.clone(), i.1.clone()));
-        {
-            let aborted = o.2.len() <= o.0;
-            let updated_inventory = o.2.get(o.0).ok_or_else(|| o.1.clone()).and_then(|c| o.1.clone().try_add(*c));
-            match updated_inventory {
-                Ok(i) => {
-                    run! {
-                        exposition("You take the item.");
-                        yield (i.clone())
-                    }
-                },
-                Err(i) => {
-                    run! {
-                        if aborted {
-                            exposition("You changed your mind, and didn't take an item.")
-                        } else {
-                            exposition("You try to pick up the item, but your hands are full.")
-                        };
-                        yield i.clone()
-                    }
-                }
+    //We would like to capture inventory and use it in do-notation, but the continuations need to be Fn, and Inventory isn't Copy.
+    //This leaves us with a couple of options: We can either pass it through by repeated cloning (done here, using free_do!'s clone statement),
+    //or leave do-notation before capturing it (see return_item).
+    let descriptions: Vec<_> = options
+        .iter()
+        .map(|o| o.description())
+        .chain(std::iter::once("Cancel"))
+        .collect();
+    free_do! {
+        exposition("You look around and these items nearby catch your attention.");
+        clone descriptions, inventory, options;
+        choice <= give_player_options(descriptions);
+        let aborted = options.len() <= choice;
+        let updated_inventory = options.get(choice).ok_or_else(|| inventory.clone()).and_then(|c| inventory.clone().try_add(*c));
+        match updated_inventory {
+            Ok(i) => {
+                exposition("You take the item.");
+                clone i;
+                yield i
+            },
+            Err(i) => {
+                if aborted {
+                    exposition("You changed your mind, and didn't take an item.")
+                } else {
+                    exposition("You try to pick up the item, but your hands are full.")
+                };
+                clone i;
+                yield i
             }
         }
     }
@@ -365,7 +362,7 @@ fn talk_to_deli_lady<'a, 's: 'a>(inventory: Inventory) -> FreeSausageRoll<'a, 's
         exposition("You greet the lady at the deli counter.");
         say_dialogue_line(Speaker::DeliLady, Cow::from("Hi! How can I help you, dear?"), Mood::Friendly);
         say_dialogue_line(Speaker::DeliLady, Cow::from("We have the most awesome fish sandwiches today. Would you like one?"), Mood::Friendly)
-    }.bind(move |_| deli_lady_loop(inventory.clone()))
+    }.bind(move |()| deli_lady_loop(inventory.clone()))
 }
 
 fn deli_lady_loop<'a, 's: 'a>(inventory: Inventory) -> FreeSausageRoll<'a, 's, Inventory> {
@@ -405,7 +402,7 @@ fn deli_lady_loop<'a, 's: 'a>(inventory: Inventory) -> FreeSausageRoll<'a, 's, I
                             say_dialogue_line(Speaker::DeliLady, Cow::from("I'll put in extra sausage to make up for it."), Mood::Apologetic);
                             say_dialogue_line(Speaker::DeliLady, Cow::from("Here you go! Is there anything else I can help you with? Maybe a fish sandwich?"), Mood::Happy)
                         };
-                        d.bind(move |_| deli_lady_loop(inventory.clone()))
+                        d.bind(move |()| deli_lady_loop(inventory.clone()))
                     },
                     Err(inventory) => {
                         run!{
@@ -418,12 +415,12 @@ fn deli_lady_loop<'a, 's: 'a>(inventory: Inventory) -> FreeSausageRoll<'a, 's, I
             2 => {
                 let inventory = inventory.clone();
                 say_dialogue_line(Speaker::DeliLady, Cow::from("So, you are just here to steal my time? I've got other customers to serve."), Mood::Annoyed)
-                .bind(move |_| FreeSausageRoll::pure(inventory.clone()))
+                .bind(move |()| FreeSausageRoll::pure(inventory.clone()))
             },
             3 => {
                 let inventory = inventory.clone();
                 say_dialogue_line(Speaker::DeliLady, Cow::from("No, that would be gross. Would you buy a sandwich handed back by some other random customer?"), Mood::Confused)
-                .bind(move |_| FreeSausageRoll::pure(inventory.clone()))
+                .bind(move |()| FreeSausageRoll::pure(inventory.clone()))
             }
             _ => unreachable!()
         }
@@ -455,5 +452,5 @@ fn try_pay<'a, 's: 'a>(
                 yield Err(())
             }
         }
-    }.fmap(move |e| e.map(|_| inventory.clone()).map_err(|_| inventory.clone()))
+    }.fmap(move |e| e.map(|()| inventory.clone()).map_err(|()| inventory.clone()))
 }
@@ -16,17 +16,19 @@
 //! game state in a convenient way (see [higher issue 6](https://github.com/bodil/higher/issues/6)).
 //!
 //! But on to the explanation what is going on:
-//! This project has 4 modules:
+//! This project has 5 modules:
 //! - `data` contains the data. Stuff like item types, item descriptions, rooms, etc.
 //! - `dsl` contains the embedded domain specific language. In other words, a Functor and the corresponding Free Monad type (and some helpers)
 //! - `logic` describes the game's main logic using the language defined in "dsl"
 //! - `side_effects` actually runs the logic.
+//! - `exploration` doesn't run the logic, but walks every path through it, and prints the endings. Run the example with `--explore [depth]` for that.
 //!
 //! The important part here is that all the stuff that isn't in `side_effects` is independent of the concrete implementation of `side_effects`.
 //! The current `side_effects` runs a text-adventure, but it could just as well render as a visual-novel, without the need to touch any of the other modules.
 
 mod data;
 mod dsl;
+mod exploration;
 mod logic;
 mod side_effects;
 
@@ -34,6 +36,16 @@ fn main() -> std::io::Result<()> {
     //Let's build the game logic. As a data structure.
     let game = logic::game();
 
-    //And now let's do something with it.
-    side_effects::run(game)
+    //And now let's do something with it. Either play it, or list all the ways it can end.
+    let mut args = std::env::args().skip_while(|arg| arg != "--explore");
+    if args.next().is_some() {
+        let max_depth = args
+            .next()
+            .and_then(|depth| depth.parse().ok())
+            .unwrap_or(40);
+        exploration::print_endings(game, max_depth);
+        Ok(())
+    } else {
+        side_effects::run(game)
+    }
 }
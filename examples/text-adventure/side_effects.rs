@@ -1,60 +1,72 @@
 //! this module interprets the domain specific language as a text adventure.
+//!
+//! It does so by translating the game into a program of the library's Console effect, which then gets run on stdin and stdout.
+//! The same translated program could also be run by `run_scripted()`, to test whole playthroughs.
 
-use crate::dsl::FreeSausageRoll;
+use std::rc::Rc;
 
-pub fn run<'a, 's: 'a>(mut game: FreeSausageRoll<'a, 's, ()>) -> std::io::Result<()> {
+use higher_free_macro::effects::console::{run_console, Console, FreeConsole};
+
+use crate::dsl::{FreeSausageRoll, SausageRoll};
+
+pub fn run<'a, 's: 'a>(game: FreeSausageRoll<'a, 's, ()>) -> std::io::Result<()> {
+    run_console(
+        to_console(game),
+        std::io::stdin().lock(),
+        std::io::stdout().lock(),
+    )
+}
+
+pub fn to_console<'a, 's: 'a>(mut game: FreeSausageRoll<'a, 's, ()>) -> FreeConsole<'a, ()> {
     //this function doesn't know who it is, or why it is here. It only knows it must deal.
     //Deal with the few commands in the eDSL and nothing more.
 
-    //This would be easier to write recursively. However, in an actual project this might run for quite some time.
-    //Since we operate on the stack, let's rather be safe than sorry, and use a loop instead of recursion therefore.
-    while let FreeSausageRoll::Free(command) = game {
-        game = match *command {
-            crate::dsl::SausageRoll::SayDialogueLine {
-                speaker,
-                text,
-                mood,
-                next,
-            } => {
-                println!(
-                    "{} says: \"{text}\" with {} on their face.",
-                    speaker.text_description(),
-                    mood.text_description()
-                );
-                next
-            }
-            crate::dsl::SausageRoll::GivePlayerOptions { options, next } => {
-                println!("Your options are:");
-                for (id, option) in options.iter().enumerate().map(|(i, o)| (i + 1, o)) {
-                    println!("{id}: {option}");
+    //This would be easier to write recursively. However, in an actual project a game might print quite a lot of text
+    //between two choices. Since we operate on the stack, let's rather be safe than sorry, and collect the text in a loop.
+    //The part after a choice is only translated once the player has made it.
+    let mut lines = Vec::new();
+    let rest = loop {
+        game = match game {
+            FreeSausageRoll::Pure(()) => break FreeConsole::Pure(()),
+            FreeSausageRoll::Free(command) => match *command {
+                SausageRoll::SayDialogueLine {
+                    speaker,
+                    text,
+                    mood,
+                    next,
+                } => {
+                    lines.push(format!(
+                        "{} says: \"{text}\" with {} on their face.",
+                        speaker.text_description(),
+                        mood.text_description()
+                    ));
+                    next
                 }
-
-                let mut input = String::new();
-                let mut chosen;
-                while {
-                    input.clear();
-                    std::io::stdin().read_line(&mut input)?;
-                    chosen = input
-                        .trim()
-                        .parse()
-                        .ok()
-                        .filter(|o: &usize| *o > 0 && *o <= options.len());
-                    chosen.is_none()
-                } {
-                    println!("Invalid choice. Please select one of the options given above.");
+                SausageRoll::GivePlayerOptions { options, next } => {
+                    lines.push(String::from("Your options are:"));
+                    let options = options.into_iter().map(String::from).collect();
+                    break FreeConsole::Free(Box::new(Console::ChooseFrom(
+                        options,
+                        Rc::new(move |choice| {
+                            FreeConsole::Free(Box::new(Console::PrintLine(
+                                String::new(),
+                                to_console(next(choice)),
+                            )))
+                        }),
+                    )));
+                }
+                SausageRoll::PresentLocation { location, next } => {
+                    lines.push(location.get_text_description().to_string());
+                    next
+                }
+                SausageRoll::Exposition { text, next } => {
+                    lines.push(text.to_owned());
+                    next
                 }
-                println!();
-                next(chosen.unwrap() - 1)
-            }
-            crate::dsl::SausageRoll::PresentLocation { location, next } => {
-                println!("{}", location.get_text_description());
-                next
-            }
-            crate::dsl::SausageRoll::Exposition { text, next } => {
-                println!("{text}");
-                next
-            }
+            },
         };
-    }
-    std::io::Result::Ok(())
+    };
+    lines.into_iter().rev().fold(rest, |rest, line| {
+        FreeConsole::Free(Box::new(Console::PrintLine(line, rest)))
+    })
 }
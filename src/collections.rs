@@ -0,0 +1,324 @@
+//! Combinators that run Free Monads over collections, modelled after Haskell's `Data.Traversable` and `Control.Monad`.
+//!
+//! Like the ones in [`control`][crate::control], these functions are generic over all types that implement [`MonadFree`]
+//! and [`Bind`], so they work with every type generated by the [`free!`][crate::free] macro.
+//!
+//! The continuations of a Free Monad may be called any number of times (think of a `Vec` Functor, or an eDSL command that
+//! asks the player for input). Collecting results naively would therefore copy the results gathered so far on every step.
+//! Instead, results are collected in a shared persistent list, and only turned into a [`Vec`] at the very end, so all
+//! combinators are linear in the size of the input. Programs that do not yield a `Free` layer are run in a plain loop.
+//!
+//! Laziness works just like described in the [`control`][crate::control] module: Each element's program is only bound once the previous
+//! element's program has finished.
+
+use std::rc::Rc;
+
+use higher::{Bind, Pure};
+
+use crate::MonadFree;
+
+/// A persistent singly linked list of the results collected so far, newest first.
+/// Cloning it is cheap, which is why it can be captured in continuations that are called several times.
+struct Collected<A>(Option<Rc<(A, Collected<A>)>>);
+
+impl<A> Clone for Collected<A> {
+    fn clone(&self) -> Self {
+        Collected(self.0.clone())
+    }
+}
+
+impl<A> Collected<A> {
+    fn new() -> Self {
+        Collected(None)
+    }
+
+    fn push(&self, value: A) -> Self {
+        Collected(Some(Rc::new((value, self.clone()))))
+    }
+
+    fn into_vec(self) -> Vec<A>
+    where
+        A: Clone,
+    {
+        let mut result = Vec::new();
+        let mut node = &self.0;
+        while let Some(cell) = node {
+            result.push(cell.0.clone());
+            node = &(cell.1).0;
+        }
+        result.reverse();
+        result
+    }
+}
+
+impl<A> Drop for Collected<A> {
+    fn drop(&mut self) {
+        //The default drop would recurse once per element. Unlink the nodes we own one by one instead.
+        let mut node = self.0.take();
+        while let Some(cell) = node {
+            node = match Rc::try_unwrap(cell) {
+                Ok((_, mut rest)) => rest.0.take(),
+                Err(_) => None,
+            };
+        }
+    }
+}
+
+/// Everything the driver needs to run one program per item.
+struct Driver<T, Mk, Up, Fin> {
+    items: Vec<T>,
+    make: Mk,
+    update: Up,
+    finish: Fin,
+}
+
+/// Runs `make(item)` for each item starting at `index`, and folds the results into `state` using `update`.
+fn drive<'a, T, M, S, R, Mk, Up, Fin>(
+    driver: Rc<Driver<T, Mk, Up, Fin>>,
+    mut index: usize,
+    mut state: S,
+) -> M::Target<R>
+where
+    M: MonadFree<'a> + Bind<'a, <M as MonadFree<'a>>::Value>,
+    M::Target<R>: Pure<R>,
+    S: Clone + 'a,
+    T: 'a,
+    Mk: Fn(&T) -> M + 'a,
+    Up: Fn(S, &T, <M as MonadFree<'a>>::Value) -> S + 'a,
+    Fin: Fn(S) -> R + 'a,
+{
+    loop {
+        let Some(item) = driver.items.get(index) else {
+            return Pure::pure((driver.finish)(state));
+        };
+        match (driver.make)(item).resume() {
+            Ok(value) => {
+                state = (driver.update)(state, item, value);
+                index += 1;
+            }
+            Err(layer) => {
+                return M::wrap(layer).bind(move |value| {
+                    let state = (driver.update)(state.clone(), &driver.items[index], value);
+                    drive(driver.clone(), index + 1, state)
+                })
+            }
+        }
+    }
+}
+
+/// Runs the given programs one after the other, and collects their results.
+pub fn sequence<'a, M, A, I>(programs: I) -> M::Target<Vec<A>>
+where
+    I: IntoIterator<Item = M>,
+    M: MonadFree<'a, Value = A> + Bind<'a, A> + Clone + 'a,
+    M::Target<Vec<A>>: Pure<Vec<A>>,
+    A: Clone + 'a,
+{
+    let driver = Driver {
+        items: programs.into_iter().collect(),
+        make: M::clone,
+        update: |collected: Collected<A>, _: &M, value| collected.push(value),
+        finish: Collected::into_vec,
+    };
+    drive(Rc::new(driver), 0, Collected::new())
+}
+
+/// Creates a program for each item using `f`, runs them one after the other, and collects their results.
+pub fn traverse<'a, M, A, T, I, F>(items: I, f: F) -> M::Target<Vec<A>>
+where
+    I: IntoIterator<Item = T>,
+    F: FnMut(T) -> M,
+    M: MonadFree<'a, Value = A> + Bind<'a, A> + Clone + 'a,
+    M::Target<Vec<A>>: Pure<Vec<A>>,
+    A: Clone + 'a,
+{
+    sequence(items.into_iter().map(f))
+}
+
+/// Creates a program for each item using `f`, and runs them one after the other. The results are discarded.
+pub fn for_each_m<'a, M, T, I, F>(items: I, f: F) -> M::Target<()>
+where
+    I: IntoIterator<Item = T>,
+    F: FnMut(T) -> M,
+    M: MonadFree<'a> + Bind<'a, <M as MonadFree<'a>>::Value> + Clone + 'a,
+    M::Target<()>: Pure<()>,
+{
+    let driver = Driver {
+        items: items.into_iter().map(f).collect(),
+        make: M::clone,
+        update: |(), _: &M, _| (),
+        finish: |()| (),
+    };
+    drive(Rc::new(driver), 0, ())
+}
+
+/// Runs `program` `count` times, and collects the results.
+pub fn replicate_m<'a, M, A>(count: usize, program: M) -> M::Target<Vec<A>>
+where
+    M: MonadFree<'a, Value = A> + Bind<'a, A> + Clone + 'a,
+    M::Target<Vec<A>>: Pure<Vec<A>>,
+    A: Clone + 'a,
+{
+    let driver = Driver {
+        items: vec![(); count],
+        make: move |(): &()| program.clone(),
+        update: |collected: Collected<A>, (): &(), value| collected.push(value),
+        finish: Collected::into_vec,
+    };
+    drive(Rc::new(driver), 0, Collected::new())
+}
+
+/// Keeps the items for which the program returned by `predicate` yields `true`.
+pub fn filter_m<'a, M, T, I, P>(items: I, predicate: P) -> M::Target<Vec<T>>
+where
+    I: IntoIterator<Item = T>,
+    P: Fn(&T) -> M + 'a,
+    M: MonadFree<'a, Value = bool> + Bind<'a, bool>,
+    M::Target<Vec<T>>: Pure<Vec<T>>,
+    T: Clone + 'a,
+{
+    let driver = Driver {
+        items: items.into_iter().collect(),
+        make: predicate,
+        update: |collected: Collected<T>, item: &T, keep| {
+            if keep {
+                collected.push(item.clone())
+            } else {
+                collected
+            }
+        },
+        finish: Collected::into_vec,
+    };
+    drive(Rc::new(driver), 0, Collected::new())
+}
+
+/// Folds the items from left to right, where each step is a program that yields the new accumulator.
+pub fn fold_m<'a, M, B, T, I, F>(init: B, items: I, f: F) -> M
+where
+    I: IntoIterator<Item = T>,
+    F: Fn(B, T) -> M + 'a,
+    M: MonadFree<'a, Value = B> + Bind<'a, B, Target<B> = M>,
+    T: Clone + 'a,
+{
+    fn fold_m_impl<'a, M, B, T, F>(items: Rc<Vec<T>>, mut index: usize, mut acc: B, f: Rc<F>) -> M
+    where
+        F: Fn(B, T) -> M + 'a,
+        M: MonadFree<'a, Value = B> + Bind<'a, B, Target<B> = M>,
+        T: Clone + 'a,
+    {
+        loop {
+            let Some(item) = items.get(index) else {
+                return M::pure(acc);
+            };
+            match f(acc, item.clone()).resume() {
+                Ok(b) => {
+                    acc = b;
+                    index += 1;
+                }
+                Err(layer) => {
+                    return M::wrap(layer)
+                        .bind::<B, _>(move |b| fold_m_impl(items.clone(), index + 1, b, f.clone()))
+                }
+            }
+        }
+    }
+    fold_m_impl(Rc::new(items.into_iter().collect()), 0, init, Rc::new(f))
+}
+
+/// Creates a program for each pair of items using `f`, runs them one after the other, and collects their results.
+/// Stops at the end of the shorter input.
+pub fn zip_with_m<'a, M, A, T, U, I, J, F>(left: I, right: J, mut f: F) -> M::Target<Vec<A>>
+where
+    I: IntoIterator<Item = T>,
+    J: IntoIterator<Item = U>,
+    F: FnMut(T, U) -> M,
+    M: MonadFree<'a, Value = A> + Bind<'a, A> + Clone + 'a,
+    M::Target<Vec<A>>: Pure<Vec<A>>,
+    A: Clone + 'a,
+{
+    sequence(left.into_iter().zip(right).map(|(t, u)| f(t, u)))
+}
+
+#[cfg(test)]
+mod collections_tests {
+    use super::{filter_m, fold_m, for_each_m, replicate_m, sequence, traverse, zip_with_m};
+    use crate::test_support::{read, run, write};
+    use higher::Functor;
+
+    free!(FreeVec<A>, Vec<FreeVec<A>>);
+
+    #[test]
+    fn test_sequence_branches() {
+        let program = sequence(vec![
+            FreeVec::lift_f(vec![1, 2]),
+            FreeVec::Pure(3),
+            FreeVec::lift_f(vec![4, 5]),
+        ]);
+        assert_eq!(
+            program.into_leaves().collect::<Vec<_>>(),
+            vec![vec![1, 3, 4], vec![1, 3, 5], vec![2, 3, 4], vec![2, 3, 5]]
+        );
+    }
+
+    #[test]
+    fn test_sequence_large() {
+        //would take ages if this weren't linear, and overflow the stack if it weren't iterative.
+        let count: u32 = 200_000;
+        let (_, result) = run(traverse(0..count, |_| read()), usize::MAX);
+        let result = result.unwrap();
+        assert_eq!(result.len(), 200_000);
+        assert_eq!(result[..3], [1, 2, 3]);
+        assert_eq!(result[199_999], 200_000);
+
+        let program: FreeVec<Vec<u32>> = sequence((0..count).map(FreeVec::Pure));
+        assert_eq!(program.into_pure().map(|v| v.len()), Some(200_000));
+    }
+
+    #[test]
+    fn test_for_each_m() {
+        let (written, result) = run(for_each_m(vec![3, 1, 4], write), 100);
+        assert_eq!(result, Some(()));
+        assert_eq!(written, vec![3, 1, 4]);
+    }
+
+    #[test]
+    fn test_replicate_m() {
+        let (_, result) = run(replicate_m(4, read()), 100);
+        assert_eq!(result, Some(vec![1, 2, 3, 4]));
+        let none: FreeVec<Vec<u32>> = replicate_m(0, FreeVec::lift_f(vec![1]));
+        assert_eq!(none.into_pure(), Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_filter_m() {
+        let program = filter_m(vec![10, 20, 30, 40], |x| {
+            let x = *x;
+            read().fmap(move |input| input % 2 == 1 || x == 40)
+        });
+        assert_eq!(run(program, 100).1, Some(vec![10, 30, 40]));
+        //the powerset, as every item is both kept and dropped.
+        let powerset = filter_m(vec![1, 2], |_| FreeVec::lift_f(vec![true, false]));
+        assert_eq!(
+            powerset.into_leaves().collect::<Vec<_>>(),
+            vec![vec![1, 2], vec![1], vec![2], vec![]]
+        );
+    }
+
+    #[test]
+    fn test_fold_m() {
+        let program = fold_m(0, vec![5, 6, 7], |acc, x| {
+            write(acc).fmap(move |()| acc + x)
+        });
+        assert_eq!(run(program, 100), (vec![0, 5, 11], Some(18)));
+        let pure: FreeVec<u64> = fold_m(0, 0..100_000u64, |acc, x| FreeVec::Pure(acc + x));
+        assert_eq!(pure.into_pure(), Some(4_999_950_000));
+    }
+
+    #[test]
+    fn test_zip_with_m() {
+        let program = zip_with_m(vec![1, 2, 3], vec![10, 20], |a, b| {
+            write(a + b).fmap(move |()| a * b)
+        });
+        assert_eq!(run(program, 100), (vec![11, 22], Some(vec![10, 40])));
+    }
+}
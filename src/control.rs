@@ -0,0 +1,228 @@
+//! Control-flow combinators for Free Monads, modelled after Haskell's `Control.Monad` and the `monad-loops` package.
+//!
+//! The functions in this module are generic over all types that implement [`MonadFree`] and [`Bind`], so they work
+//! with every type generated by the [`free!`][crate::free] macro.
+//!
+//! # Laziness
+//! Rust is a strict language, so a Free Monad only contains as much structure as has actually been built.
+//! The loops in this module never build more than one iteration ahead: The next iteration is only created inside the
+//! continuation that is passed to [`bind()`][Bind::bind]. For the typical eDSL Functor, that stores its continuation in a closure
+//! (like `Rc<dyn Fn(Input) -> A>`), this means that nothing is built until the interpreter feeds the layer an input, and an
+//! infinite loop created by [`forever`] is perfectly fine.
+//!
+//! Functors that store the next step by value (like `Vec<A>` or a `Say(String, A)` command) are mapped eagerly though.
+//! A loop over such layers builds all its iterations up front, so it must terminate, and its depth is limited by the stack.
+//! Iterations that do not yield a `Free` layer at all are run in a plain loop, and don't use any stack.
+
+use std::ops::ControlFlow;
+use std::rc::Rc;
+
+use higher::{Bind, Pure};
+
+use crate::MonadFree;
+
+/// Runs `program` if `condition` is `true`, and does nothing otherwise.
+pub fn when<'a, M>(condition: bool, program: M) -> M
+where
+    M: MonadFree<'a, Value = ()>,
+{
+    if condition {
+        program
+    } else {
+        M::pure(())
+    }
+}
+
+/// Runs `program` if `condition` is `false`, and does nothing otherwise.
+pub fn unless<'a, M>(condition: bool, program: M) -> M
+where
+    M: MonadFree<'a, Value = ()>,
+{
+    when(!condition, program)
+}
+
+/// Runs `program` over and over again. The result is never `Pure`, so its value type can be chosen freely.
+///
+/// See the [module documentation](self) for when this is lazy.
+///
+/// # Panics
+/// Panics if `program` is `Pure`, because repeating it would loop forever without ever yielding a `Free` layer.
+pub fn forever<'a, M, B>(program: M) -> M::Target<B>
+where
+    M: MonadFree<'a> + Bind<'a, <M as MonadFree<'a>>::Value> + Clone + 'a,
+{
+    assert!(
+        program.peel().is_err(),
+        "forever() called with a Pure program, which never yields."
+    );
+    program
+        .clone()
+        .bind(move |_| forever::<M, B>(program.clone()))
+}
+
+/// Runs `step` on `init`, and then on the state returned in [`ControlFlow::Continue`] over and over again,
+/// until `step` returns [`ControlFlow::Break`]. The result is the value carried by `Break`.
+///
+/// Every type generated by the [`free!`][crate::free] macro also has this as an associated function named `tail_rec_m`.
+///
+/// See the [module documentation](self) for when this is lazy.
+pub fn loop_m<'a, M, S, B, F>(init: S, step: F) -> M::Target<B>
+where
+    M: MonadFree<'a, Value = ControlFlow<B, S>> + Bind<'a, ControlFlow<B, S>>,
+    M::Target<B>: Pure<B>,
+    F: Fn(S) -> M + 'a,
+{
+    fn loop_m_impl<'a, M, S, B, F>(mut state: S, step: Rc<F>) -> M::Target<B>
+    where
+        M: MonadFree<'a, Value = ControlFlow<B, S>> + Bind<'a, ControlFlow<B, S>>,
+        M::Target<B>: Pure<B>,
+        F: Fn(S) -> M + 'a,
+    {
+        loop {
+            match step(state).resume() {
+                Ok(ControlFlow::Continue(s)) => state = s,
+                Ok(ControlFlow::Break(b)) => return Pure::pure(b),
+                Err(layer) => {
+                    return M::wrap(layer).bind(move |flow| match flow {
+                        ControlFlow::Continue(s) => loop_m_impl(s, step.clone()),
+                        ControlFlow::Break(b) => Pure::pure(b),
+                    })
+                }
+            }
+        }
+    }
+    loop_m_impl(init, Rc::new(step))
+}
+
+/// Runs `step` on `init`, and then on its own result, until `done` returns `true` for the current value.
+/// `done` is checked before each step, so if it holds for `init`, `step` is never run.
+///
+/// See the [module documentation](self) for when this is lazy.
+pub fn iterate_until<'a, M, S, F, P>(init: S, step: F, done: P) -> M
+where
+    M: MonadFree<'a, Value = S> + Bind<'a, S, Target<S> = M>,
+    F: Fn(S) -> M + 'a,
+    P: Fn(&S) -> bool + 'a,
+{
+    fn iterate_until_impl<'a, M, S, F, P>(mut state: S, step: Rc<F>, done: Rc<P>) -> M
+    where
+        M: MonadFree<'a, Value = S> + Bind<'a, S, Target<S> = M>,
+        F: Fn(S) -> M + 'a,
+        P: Fn(&S) -> bool + 'a,
+    {
+        loop {
+            if done(&state) {
+                return M::pure(state);
+            }
+            match step(state).resume() {
+                Ok(s) => state = s,
+                Err(layer) => {
+                    return M::wrap(layer)
+                        .bind::<S, _>(move |s| iterate_until_impl(s, step.clone(), done.clone()))
+                }
+            }
+        }
+    }
+    iterate_until_impl(init, Rc::new(step), Rc::new(done))
+}
+
+#[cfg(test)]
+mod control_tests {
+    use super::{forever, iterate_until, loop_m, unless, when};
+    use crate::free_do;
+    use crate::test_support::{read, run, write, FreeIo};
+    use std::ops::ControlFlow;
+
+    free!(FreeVec<A>, Vec<FreeVec<A>>);
+
+    #[test]
+    fn test_when_unless() {
+        assert_eq!(run(when(true, write(1)), 10), (vec![1], Some(())));
+        assert_eq!(run(when(false, write(1)), 10), (vec![], Some(())));
+        assert_eq!(run(unless(true, write(1)), 10), (vec![], Some(())));
+        assert_eq!(run(unless(false, write(1)), 10), (vec![1], Some(())));
+    }
+
+    #[test]
+    fn test_forever_is_lazy() {
+        let echo = free_do! {
+            x <= read();
+            write(x * 2)
+        };
+        let (written, result) = run(forever::<_, u32>(echo), 300_000);
+        assert!(result.is_none());
+        assert_eq!(written.len(), 150_000);
+        assert_eq!(written[..3], [2, 4, 6]);
+    }
+
+    #[test]
+    #[should_panic(expected = "never yields")]
+    fn test_forever_pure_panics() {
+        let _: FreeVec<()> = forever(FreeVec::Pure(1));
+    }
+
+    #[test]
+    fn test_loop_m() {
+        //sums up inputs until the sum exceeds 100.
+        let program = loop_m(0, |sum: u32| {
+            free_do! {
+                x <= read();
+                write(sum + x);
+                yield if sum + x > 100 { ControlFlow::Break(sum + x) } else { ControlFlow::Continue(sum + x) }
+            }
+        });
+        let (written, result) = run(program, 1000);
+        assert_eq!(result, Some(105));
+        assert_eq!(
+            written,
+            vec![1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 66, 78, 91, 105]
+        );
+    }
+
+    #[test]
+    fn test_loop_m_pure_steps() {
+        let program: FreeVec<u64> = loop_m(0u64, |x| {
+            FreeVec::Pure(if x < 1_000_000 {
+                ControlFlow::Continue(x + 1)
+            } else {
+                ControlFlow::Break(x)
+            })
+        });
+        assert!(matches!(program, FreeVec::Pure(1_000_000)));
+    }
+
+    #[test]
+    fn test_loop_m_data_functor() {
+        //every iteration either stops or branches.
+        let program = loop_m(0, |x: u32| {
+            if x >= 2 {
+                FreeVec::Pure(ControlFlow::Break(x))
+            } else {
+                FreeVec::lift_f(vec![
+                    ControlFlow::Continue(x + 1),
+                    ControlFlow::Break(x * 10),
+                ])
+            }
+        });
+        assert_eq!(program.into_leaves().collect::<Vec<_>>(), vec![2, 10, 0]);
+    }
+
+    #[test]
+    fn test_iterate_until() {
+        let program = iterate_until(
+            1,
+            |x: u32| {
+                free_do! {
+                    write(x);
+                    y <= read();
+                    yield x + y
+                }
+            },
+            |x| *x > 10,
+        );
+        assert_eq!(run(program, 100), (vec![1, 2, 4, 7], Some(11)));
+
+        let untouched: FreeIo<u32> = iterate_until(20, |_| read(), |x| *x > 10);
+        assert_eq!(run(untouched, 100), (vec![], Some(20)));
+    }
+}
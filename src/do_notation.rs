@@ -0,0 +1,240 @@
+//! Contains the `free_do!` macro. The module itself is private, the macro is exported at the crate root.
+
+/// Do-notation for Free Monads (and any other [`Monad`][higher::Monad] from the [higher] crate).
+///
+/// This is similar to [`higher::run!`], but lifts two of its restrictions that get in the way when writing eDSL code:
+/// It supports patterns on the left hand side of bindings, and it allows to explicitly clone captured values.
+///
+/// The macro takes a list of statements separated by semicolons, and desugars them to nested calls of
+/// [`Bind::bind()`][higher::Bind::bind]. The supported statements are:
+/// - `pattern <= program;` runs `program`, and binds its result to the (irrefutable) `pattern` for the remaining statements.
+/// - `program;` runs `program` and discards its result.
+/// - `let pattern = expression;` is a plain `let` statement.
+/// - `clone a, b, c;` replaces the named variables by clones of themselves for the remaining statements.
+/// - `if condition { statements } else { statements }` and `match scrutinee { pattern => { statements }, ... }` run the statements of the
+///   branch that is taken. The branches are do-blocks themselves. Match arms may also be plain program expressions.
+///   An `if` without `else` does nothing if the condition is false.
+///
+/// The last statement is either a program, or `yield value`, which wraps `value` using [`Pure::pure()`][higher::Pure::pure].
+///
+/// The closures passed to [`bind()`][higher::Bind::bind] need to implement [`Fn`], because the continuation of a Free Monad may be
+/// called any number of times. That's why values that are moved into later statements need to be cloned, which
+/// is what the `clone` statement is for. It clones the variables each time the continuation it is part of runs.
+/// ```
+/// # #[macro_use] extern crate higher_free_macro;
+/// # use higher_free_macro::higher::*;
+/// free!(FreeOption<A>, Option<FreeOption<A>>);
+///
+/// #[derive(Clone, Debug, PartialEq)]
+/// struct Inventory(Vec<String>);
+///
+/// fn pick_up(inventory: Inventory, item: &str) -> FreeOption<(usize, Inventory)> {
+///     let mut inventory = inventory;
+///     inventory.0.push(item.to_owned());
+///     FreeOption::lift_f(Some((inventory.0.len(), inventory)))
+/// }
+///
+/// fn main() {
+///     let program = free_do! {
+///         let start = Inventory(Vec::new());
+///         (count, inventory) <= pick_up(start.clone(), "apple");
+///         clone inventory;
+///         if count > 0 {
+///             (_, inventory) <= pick_up(inventory, "pear");
+///             yield inventory
+///         } else {
+///             yield inventory
+///         }
+///     };
+///     let result = program.retract();
+///     assert_eq!(result, Some(Inventory(vec!["apple".to_owned(), "pear".to_owned()])));
+/// }
+/// ```
+#[macro_export]
+macro_rules! free_do {
+    //binding: munch the pattern until the <= token, then the expression until the ;
+    (@bind [$($pat:tt)+] [$($e:tt)+] ; $($rest:tt)+) => {
+        $crate::higher::Bind::bind($($e)*, move |free_do_value| {
+            let $($pat)* = free_do_value;
+            $crate::free_do!($($rest)*)
+        })
+    };
+    (@bind [$($pat:tt)+] [$($e:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::free_do!(@bind [$($pat)*] [$($e)* $t] $($rest)*)
+    };
+    (@stmt [$($pat:tt)+] <= $($rest:tt)*) => {
+        $crate::free_do!(@bind [$($pat)*] [] $($rest)*)
+    };
+    (@stmt [$($e:tt)+] ; $($rest:tt)+) => {
+        $crate::free_do!(@then [$($e)*] ; $($rest)*)
+    };
+    (@stmt [$($e:tt)+] $(;)?) => {
+        $($e)*
+    };
+    (@stmt [$($acc:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::free_do!(@stmt [$($acc)* $t] $($rest)*)
+    };
+
+    //a finished expression, that is either followed by more statements, or the last one.
+    (@then [$($e:tt)+] ; $($rest:tt)+) => {
+        $crate::higher::Bind::bind($($e)*, move |_| $crate::free_do!($($rest)*))
+    };
+    (@then [$($e:tt)+] $(;)?) => {
+        $($e)*
+    };
+    //if and match blocks don't need a semicolon after them.
+    (@then [$($e:tt)+] $($rest:tt)+) => {
+        $crate::free_do!(@then [$($e)*] ; $($rest)*)
+    };
+
+    (@let [$($acc:tt)*] ; $($rest:tt)*) => {
+        {
+            let $($acc)*;
+            $crate::free_do!($($rest)*)
+        }
+    };
+    (@let [$($acc:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::free_do!(@let [$($acc)* $t] $($rest)*)
+    };
+
+    (@if [$($cond:tt)+] { $($then:tt)* } else { $($else:tt)* } $($rest:tt)*) => {
+        $crate::free_do!(@then [if $($cond)* { $crate::free_do!($($then)*) } else { $crate::free_do!($($else)*) }] $($rest)*)
+    };
+    (@if [$($cond:tt)+] { $($then:tt)* } $($rest:tt)*) => {
+        $crate::free_do!(@then [if $($cond)* { $crate::free_do!($($then)*) } else { $crate::higher::Pure::pure(()) }] $($rest)*)
+    };
+    (@if [$($cond:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::free_do!(@if [$($cond)* $t] $($rest)*)
+    };
+
+    (@match [$($scrutinee:tt)+] { $($arms:tt)* } $($rest:tt)*) => {
+        $crate::free_do!(@arms [$($scrutinee)*] [] [$($rest)*] $($arms)*)
+    };
+    (@match [$($scrutinee:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::free_do!(@match [$($scrutinee)* $t] $($rest)*)
+    };
+    (@arms $scrutinee:tt [$($done:tt)*] $rest:tt $p:pat $(if $guard:expr)? => { $($body:tt)* } , $($arms:tt)*) => {
+        $crate::free_do!(@arms $scrutinee [$($done)* $p $(if $guard)? => { $crate::free_do!($($body)*) },] $rest $($arms)*)
+    };
+    (@arms $scrutinee:tt [$($done:tt)*] $rest:tt $p:pat $(if $guard:expr)? => { $($body:tt)* } $($arms:tt)*) => {
+        $crate::free_do!(@arms $scrutinee [$($done)* $p $(if $guard)? => { $crate::free_do!($($body)*) },] $rest $($arms)*)
+    };
+    (@arms $scrutinee:tt [$($done:tt)*] $rest:tt $p:pat $(if $guard:expr)? => $e:expr $(, $($arms:tt)*)?) => {
+        $crate::free_do!(@arms $scrutinee [$($done)* $p $(if $guard)? => $e,] $rest $($($arms)*)?)
+    };
+    (@arms [$($scrutinee:tt)*] [$($done:tt)*] [$($rest:tt)*]) => {
+        $crate::free_do!(@then [match $($scrutinee)* { $($done)* }] $($rest)*)
+    };
+
+    (yield $e:expr $(;)?) => {
+        $crate::higher::Pure::pure($e)
+    };
+    (let $($rest:tt)*) => {
+        $crate::free_do!(@let [] $($rest)*)
+    };
+    (clone $($x:ident),+ ; $($rest:tt)*) => {
+        {
+            $(let $x = ::core::clone::Clone::clone(&$x);)+
+            $crate::free_do!($($rest)*)
+        }
+    };
+    (if $($rest:tt)*) => {
+        $crate::free_do!(@if [] $($rest)*)
+    };
+    (match $($rest:tt)*) => {
+        $crate::free_do!(@match [] $($rest)*)
+    };
+    ($($rest:tt)+) => {
+        $crate::free_do!(@stmt [] $($rest)*)
+    };
+}
+
+#[cfg(test)]
+mod free_do_tests {
+    use higher::Pure;
+    use std::rc::Rc;
+
+    free!(FreeVec<A>, Vec<FreeVec<A>>);
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct NotCopy(u32);
+
+    fn pair(a: u32) -> FreeVec<(u32, NotCopy)> {
+        FreeVec::lift_f(vec![(a, NotCopy(a * 10)), (a + 1, NotCopy(a * 20))])
+    }
+
+    #[test]
+    fn test_bind_with_pattern_and_clone() {
+        let offset = NotCopy(5);
+        let program: FreeVec<u32> = free_do! {
+            (a, NotCopy(b)) <= pair(1);
+            clone offset;
+            let NotCopy(o) = offset;
+            c <= FreeVec::lift_f(vec![a + b + o]);
+            yield c
+        };
+        assert_eq!(program.into_leaves().collect::<Vec<_>>(), vec![16, 27]);
+    }
+
+    #[test]
+    fn test_discard_and_final_expression() {
+        let program: FreeVec<u32> = free_do! {
+            FreeVec::lift_f(vec![(), ()]);
+            FreeVec::pure(3);
+            FreeVec::lift_f(vec![4])
+        };
+        assert_eq!(program.into_leaves().collect::<Vec<_>>(), vec![4, 4]);
+    }
+
+    #[test]
+    fn test_if_blocks() {
+        let program = |x: u32| -> FreeVec<u32> {
+            free_do! {
+                y <= FreeVec::lift_f(vec![x]);
+                if y > 2 {
+                    z <= FreeVec::lift_f(vec![y, y * 2]);
+                    yield z
+                } else {
+                    yield 0
+                };
+                if y == 1 {
+                    FreeVec::lift_f(vec![(), ()])
+                }
+                yield y
+            }
+        };
+        assert_eq!(program(3).into_leaves().collect::<Vec<_>>(), vec![3, 3]);
+        assert_eq!(program(1).into_leaves().collect::<Vec<_>>(), vec![1, 1]);
+        assert_eq!(program(2).into_leaves().collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[test]
+    fn test_match_blocks() {
+        let seen = Rc::new(std::cell::Cell::new(0));
+        let program = |x: Option<u32>| -> FreeVec<u32> {
+            let seen = seen.clone();
+            free_do! {
+                v <= match x {
+                    Some(v) if v > 10 => FreeVec::pure(v),
+                    _ => FreeVec::lift_f(vec![1, 2]),
+                };
+                match v {
+                    1 => {
+                        let s = seen.get();
+                        yield s
+                    },
+                    2 => FreeVec::pure(20),
+                    other => {
+                        w <= FreeVec::lift_f(vec![other, other + 1]);
+                        yield w
+                    }
+                }
+            }
+        };
+        assert_eq!(program(None).into_leaves().collect::<Vec<_>>(), vec![0, 20]);
+        assert_eq!(
+            program(Some(11)).into_leaves().collect::<Vec<_>>(),
+            vec![11, 12]
+        );
+    }
+}
@@ -0,0 +1,261 @@
+//! The Clock effect: Programs that look at the time, wait, and give up on things that take too long.
+//!
+//! Time is measured as a [`Duration`] since the interpreter started running the program. [`run_real`] uses the system's
+//! monotonic clock and really sleeps, while [`run_simulated`] keeps a virtual clock that jumps forward instantly whenever the
+//! program sleeps. So the same program can be used in production and in fast, reproducible tests.
+//!
+//! ```
+//! # use higher_free_macro::free_do;
+//! use std::time::Duration;
+//! use higher_free_macro::effects::clock::{now, run_simulated, sleep, with_timeout, FreeClock};
+//! fn main() {
+//!     let slow_delivery = || -> FreeClock<&str> {
+//!         free_do! {
+//!             sleep(Duration::from_secs(3600));
+//!             yield "sausage roll"
+//!         }
+//!     };
+//!     let program = free_do! {
+//!         order <= with_timeout(slow_delivery(), Duration::from_secs(60));
+//!         time <= now();
+//!         yield (order, time)
+//!     };
+//!     assert_eq!(run_simulated(program).0, (None, Duration::from_secs(60)));
+//! }
+//! ```
+
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use higher::Functor;
+
+/// The [`Functor`] of the Clock effect.
+#[derive(Clone)]
+pub enum Clock<'a, A> {
+    /// Reads the current time, and continues with the program returned by the function for it.
+    Now(Rc<dyn Fn(Duration) -> A + 'a>),
+    /// Waits for the given duration.
+    Sleep(Duration, A),
+}
+
+impl<'a, A: 'a> Functor<'a, A> for Clock<'a, A> {
+    type Target<T> = Clock<'a, T>;
+
+    fn fmap<B, F>(self, f: F) -> Self::Target<B>
+    where
+        F: Fn(A) -> B + 'a,
+    {
+        match self {
+            Clock::Now(next) => Clock::Now(Rc::new(move |t| f(next(t)))),
+            Clock::Sleep(duration, next) => Clock::Sleep(duration, f(next)),
+        }
+    }
+}
+
+free!(<'a>, pub FreeClock<'a, A>, Clock<'a, FreeClock<'a, A>>);
+
+/// Returns the time since the interpreter started running the program.
+#[must_use]
+pub fn now<'a>() -> FreeClock<'a, Duration> {
+    FreeClock::lift_f(Clock::Now(Rc::new(|t| t)))
+}
+
+/// Waits for `duration`.
+#[must_use]
+pub fn sleep<'a>(duration: Duration) -> FreeClock<'a, ()> {
+    FreeClock::lift_f(Clock::Sleep(duration, ()))
+}
+
+/// Runs `program`, but gives up once `duration` has passed. The result is `None` in that case.
+///
+/// The deadline can only be checked when the program uses the clock: A [`sleep`] that would end after the deadline is cut
+/// short at the deadline, and reading the time after the deadline has passed ends the program. Computations between two
+/// clock commands are not interrupted.
+#[must_use]
+pub fn with_timeout<'a, A: Clone + 'a>(
+    program: FreeClock<'a, A>,
+    duration: Duration,
+) -> FreeClock<'a, Option<A>> {
+    fn with_deadline<'a, A: Clone + 'a>(
+        program: FreeClock<'a, A>,
+        deadline: Duration,
+    ) -> FreeClock<'a, Option<A>> {
+        match program.resume() {
+            Ok(a) => FreeClock::Pure(Some(a)),
+            Err(Clock::Now(next)) => FreeClock::Free(Box::new(Clock::Now(Rc::new(move |t| {
+                if t >= deadline {
+                    FreeClock::Pure(None)
+                } else {
+                    with_deadline(next(t), deadline)
+                }
+            })))),
+            //the time is needed to decide whether the sleep ends before the deadline.
+            Err(Clock::Sleep(duration, next)) => {
+                FreeClock::Free(Box::new(Clock::Now(Rc::new(move |t| {
+                    if t >= deadline {
+                        FreeClock::Pure(None)
+                    } else if t.checked_add(duration).map_or(true, |end| end > deadline) {
+                        FreeClock::Free(Box::new(Clock::Sleep(
+                            deadline.saturating_sub(t),
+                            FreeClock::Pure(None),
+                        )))
+                    } else {
+                        FreeClock::Free(Box::new(Clock::Sleep(
+                            duration,
+                            with_deadline(next.clone(), deadline),
+                        )))
+                    }
+                }))))
+            }
+        }
+    }
+    //a deadline that does not fit in a `Duration` is never reached.
+    FreeClock::Free(Box::new(Clock::Now(Rc::new(move |start| {
+        match start.checked_add(duration) {
+            Some(deadline) => with_deadline(program.clone(), deadline),
+            None => program.clone().fmap(Some),
+        }
+    }))))
+}
+
+/// Runs the program on a virtual clock, that starts at zero and jumps forward whenever the program sleeps.
+/// Returns the result of the program, and the virtual time at which it finished.
+///
+/// The virtual clock saturates: Once a sleep would take it beyond [`Duration::MAX`], it stays there.
+pub fn run_simulated<'a, A: 'a>(program: FreeClock<'a, A>) -> (A, Duration) {
+    let mut time = Duration::ZERO;
+    let mut program = program;
+    loop {
+        match program.resume() {
+            Ok(a) => return (a, time),
+            Err(Clock::Now(next)) => program = next(time),
+            Err(Clock::Sleep(duration, next)) => {
+                time = time.saturating_add(duration);
+                program = next;
+            }
+        }
+    }
+}
+
+/// Runs the program on the system's monotonic clock, using [`std::thread::sleep`] to wait.
+pub fn run_real<'a, A: 'a>(program: FreeClock<'a, A>) -> A {
+    let start = Instant::now();
+    let mut program = program;
+    loop {
+        match program.resume() {
+            Ok(a) => return a,
+            Err(Clock::Now(next)) => program = next(start.elapsed()),
+            Err(Clock::Sleep(duration, next)) => {
+                std::thread::sleep(duration);
+                program = next;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod clock_tests {
+    use super::{now, run_real, run_simulated, sleep, with_timeout, FreeClock};
+    use crate::collections::replicate_m;
+    use crate::free_do;
+    use std::time::Duration;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    //sleeps `count` times for a second, and returns the times it woke up.
+    fn ticks<'a>(count: usize) -> FreeClock<'a, Vec<Duration>> {
+        replicate_m(
+            count,
+            free_do! {
+                sleep(secs(1));
+                now()
+            },
+        )
+    }
+
+    #[test]
+    fn test_simulated() {
+        let (times, end) = run_simulated(ticks(3));
+        assert_eq!(times, vec![secs(1), secs(2), secs(3)]);
+        assert_eq!(end, secs(3));
+        //a day of waiting takes no time at all.
+        assert_eq!(run_simulated(sleep(secs(86_400))).1, secs(86_400));
+    }
+
+    #[test]
+    fn test_timeout() {
+        assert_eq!(
+            run_simulated(with_timeout(ticks(3), secs(5))),
+            (Some(vec![secs(1), secs(2), secs(3)]), secs(3))
+        );
+        //the third sleep gets cut short.
+        assert_eq!(
+            run_simulated(with_timeout(ticks(3), Duration::from_millis(2500))),
+            (None, Duration::from_millis(2500))
+        );
+        //a deadline exactly at the end of a sleep is met, but reading the time afterwards is too late.
+        assert_eq!(run_simulated(with_timeout(ticks(2), secs(2))).0, None);
+        assert_eq!(
+            run_simulated(with_timeout(sleep(secs(2)), secs(2))).0,
+            Some(())
+        );
+    }
+
+    #[test]
+    fn test_timeout_overflow() {
+        //neither the deadline nor the end of a sleep may overflow.
+        let program = free_do! {
+            sleep(secs(1));
+            with_timeout(ticks(2), Duration::MAX)
+        };
+        assert_eq!(
+            run_simulated(program),
+            (Some(vec![secs(2), secs(3)]), secs(3))
+        );
+        let program = free_do! {
+            sleep(secs(1));
+            with_timeout(sleep(Duration::MAX), secs(5))
+        };
+        assert_eq!(run_simulated(program), (None, secs(6)));
+        //the virtual clock stops at the largest time it can show.
+        let program = free_do! {
+            sleep(Duration::MAX);
+            sleep(secs(1));
+            now()
+        };
+        assert_eq!(run_simulated(program), (Duration::MAX, Duration::MAX));
+    }
+
+    #[test]
+    fn test_nested_timeouts() {
+        let program = free_do! {
+            sleep(secs(10));
+            inner <= with_timeout(ticks(100), secs(50));
+            yield inner.map(|ticks| ticks.len())
+        };
+        assert_eq!(
+            run_simulated(with_timeout(program.clone(), secs(40))),
+            (None, secs(40))
+        );
+        assert_eq!(
+            run_simulated(with_timeout(program, secs(70))),
+            (Some(None), secs(60))
+        );
+    }
+
+    #[test]
+    fn test_real() {
+        let program = with_timeout(
+            free_do! {
+                sleep(Duration::from_millis(20));
+                now()
+            },
+            secs(10),
+        );
+        let elapsed = run_real(program).unwrap();
+        assert!(elapsed >= Duration::from_millis(20));
+        assert!(elapsed < secs(10));
+    }
+}
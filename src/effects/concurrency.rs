@@ -0,0 +1,654 @@
+//! The Concurrency effect: Many programs that run at the same time, and talk to each other through channels.
+//!
+//! A program can [`fork`] other programs (called tasks), [`join`] them, and exchange messages with them through channels.
+//! Everything else it does goes through [`command`]s, which [`run_tasks`] routes to a single handler shared by all tasks.
+//!
+//! The tasks are coroutines: [`run_tasks`] steps one task by one layer, and then picks the next task according to a
+//! [`Policy`]. No threads are involved, so the interleaving depends only on the policy. With [`Policy::Random`] and a fixed
+//! seed, a run can be repeated exactly, which makes it possible to test concurrent code by trying many seeds, and to
+//! reproduce the failing ones.
+//!
+//! ```
+//! # use higher_free_macro::free_do;
+//! use higher_free_macro::collections::for_each_m;
+//! use higher_free_macro::effects::concurrency::{
+//!     channel, command, fork, join, receive, run_tasks, send, FreeTask, Policy,
+//! };
+//! fn main() {
+//!     //commands are lines for a shared log, and messages are numbers.
+//!     let program: FreeTask<u32, String, (), u32> = free_do! {
+//!         numbers <= channel();
+//!         producer <= fork(for_each_m(1..=3, move |n| send(numbers, n)));
+//!         first <= receive(numbers);
+//!         second <= receive(numbers);
+//!         join(producer);
+//!         command(format!("got {first} and {second}"));
+//!         yield first + second
+//!     };
+//!     let mut log = Vec::new();
+//!     let result = run_tasks(program, Policy::Random(7), |task, line| {
+//!         log.push(format!("task {}: {line}", task.index()));
+//!     });
+//!     assert_eq!(result, Ok(3));
+//!     assert_eq!(log, vec!["task 0: got 1 and 2"]);
+//! }
+//! ```
+//!
+//! Programs written for another effect can run as tasks too, by passing each of their layers to the handler as a command,
+//! and continuing with the rest of the program the handler returns. The handler then works like a usual interpreter of that
+//! effect, one layer at a time:
+//! ```
+//! # use higher_free_macro::free_do;
+//! use higher_free_macro::higher::Bind;
+//! use higher_free_macro::effects::concurrency::{command, fork, join, run_tasks, FreeTask, Policy};
+//! use higher_free_macro::effects::console::{print_line, read_line, Console, FreeConsole};
+//!
+//! type Layer<'a> = Console<'a, FreeConsole<'a, ()>>;
+//!
+//! fn lift<'a>(program: FreeConsole<'a, ()>) -> FreeTask<'a, (), Layer<'a>, FreeConsole<'a, ()>, ()> {
+//!     match program.resume() {
+//!         Ok(()) => FreeTask::Pure(()),
+//!         Err(layer) => command(layer).bind(lift),
+//!     }
+//! }
+//!
+//! fn main() {
+//!     let greeter = |name: &'static str| -> FreeConsole<()> {
+//!         free_do! {
+//!             print_line(format!("{name}: What's your name?"));
+//!             reply <= read_line();
+//!             print_line(format!("{name}: Hello, {reply}!"))
+//!         }
+//!     };
+//!     let program = free_do! {
+//!         a <= fork(lift(greeter("A")));
+//!         b <= fork(lift(greeter("B")));
+//!         join(a);
+//!         join(b)
+//!     };
+//!     let mut printed = Vec::new();
+//!     let result = run_tasks(program, Policy::RoundRobin, |task, layer| match layer {
+//!         Console::PrintLine(line, next) => {
+//!             printed.push(line);
+//!             next
+//!         }
+//!         Console::ReadLine(next) => next(format!("task {}", task.index())),
+//!         Console::ChooseFrom(_, next) => next(0),
+//!     });
+//!     assert!(result.is_ok());
+//!     assert_eq!(printed, vec![
+//!         "A: What's your name?",
+//!         "B: What's your name?",
+//!         "A: Hello, task 1!",
+//!         "B: Hello, task 2!",
+//!     ]);
+//! }
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use higher::Functor;
+
+use crate::prng::SplitMix64;
+
+/// Identifies a task. The program passed to [`run_tasks`] has number 0, forked tasks are numbered in the order they were
+/// created.
+///
+/// Ids belong to the call of [`run_tasks`] that created them. Using them in another call is reported as
+/// [`TaskError::UnknownTask`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskId {
+    run: u64,
+    index: usize,
+}
+
+impl TaskId {
+    /// Returns the number of the task.
+    #[must_use]
+    pub fn index(self) -> usize {
+        self.index
+    }
+
+    /// Returns true for the task of the program passed to [`run_tasks`].
+    #[must_use]
+    pub fn is_main(self) -> bool {
+        self.index == 0
+    }
+}
+
+/// Identifies a channel created by [`channel`]. Like a [`TaskId`], it belongs to the call of [`run_tasks`] that created it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChannelId {
+    run: u64,
+    index: usize,
+}
+
+/// The [`Functor`] of the Concurrency effect. `C` are the commands for the shared handler, `R` its responses, and `M` the
+/// messages sent through channels.
+#[derive(Clone)]
+pub enum Thread<'a, C, R, M, A> {
+    /// Passes the command to the shared handler, and continues with the program returned by the function for its response.
+    Command(C, Rc<dyn Fn(R) -> A + 'a>),
+    /// Starts a new task with the given priority, and continues with the program returned by the function for its id.
+    Fork(FreeTask<'a, (), C, R, M>, i32, Rc<dyn Fn(TaskId) -> A + 'a>),
+    /// Waits until the task has finished.
+    Join(TaskId, A),
+    /// Creates a new channel, and continues with the program returned by the function for its id.
+    Channel(Rc<dyn Fn(ChannelId) -> A + 'a>),
+    /// Puts the message into the channel. Channels are unbounded, so this never waits.
+    Send(ChannelId, M, A),
+    /// Waits until the channel holds a message, and continues with the program returned by the function for the oldest one.
+    Receive(ChannelId, Rc<dyn Fn(M) -> A + 'a>),
+    /// Does nothing, but lets other tasks run.
+    Yield(A),
+}
+
+impl<'a, C: 'a, R: 'a, M: 'a, A: 'a> Functor<'a, A> for Thread<'a, C, R, M, A> {
+    type Target<T> = Thread<'a, C, R, M, T>;
+
+    fn fmap<B, F>(self, f: F) -> Self::Target<B>
+    where
+        F: Fn(A) -> B + 'a,
+    {
+        match self {
+            Thread::Command(command, next) => {
+                Thread::Command(command, Rc::new(move |r| f(next(r))))
+            }
+            Thread::Fork(task, priority, next) => {
+                Thread::Fork(task, priority, Rc::new(move |id| f(next(id))))
+            }
+            Thread::Join(task, next) => Thread::Join(task, f(next)),
+            Thread::Channel(next) => Thread::Channel(Rc::new(move |id| f(next(id)))),
+            Thread::Send(channel, message, next) => Thread::Send(channel, message, f(next)),
+            Thread::Receive(channel, next) => {
+                Thread::Receive(channel, Rc::new(move |m| f(next(m))))
+            }
+            Thread::Yield(next) => Thread::Yield(f(next)),
+        }
+    }
+}
+
+free!(<'a>, pub FreeTask<'a, A, C, R, M>, Thread<'a, C, R, M, FreeTask<'a, A, C, R, M>>);
+
+/// Passes `command` to the handler shared by all tasks, and returns its response.
+pub fn command<'a, C: 'a, R: 'a, M: 'a>(command: C) -> FreeTask<'a, R, C, R, M> {
+    FreeTask::lift_f(Thread::Command(command, Rc::new(|r| r)))
+}
+
+/// Starts `task` with priority 0, and returns its id.
+#[must_use]
+pub fn fork<'a, C: 'a, R: 'a, M: 'a>(
+    task: FreeTask<'a, (), C, R, M>,
+) -> FreeTask<'a, TaskId, C, R, M> {
+    fork_with_priority(0, task)
+}
+
+/// Starts `task`, and returns its id. With [`Policy::Priority`], tasks with a higher `priority` run first.
+#[must_use]
+pub fn fork_with_priority<'a, C: 'a, R: 'a, M: 'a>(
+    priority: i32,
+    task: FreeTask<'a, (), C, R, M>,
+) -> FreeTask<'a, TaskId, C, R, M> {
+    FreeTask::lift_f(Thread::Fork(task, priority, Rc::new(|id| id)))
+}
+
+/// Waits until `task` has finished.
+#[must_use]
+pub fn join<'a, C: 'a, R: 'a, M: 'a>(task: TaskId) -> FreeTask<'a, (), C, R, M> {
+    FreeTask::lift_f(Thread::Join(task, ()))
+}
+
+/// Creates a new channel, and returns its id.
+#[must_use]
+pub fn channel<'a, C: 'a, R: 'a, M: 'a>() -> FreeTask<'a, ChannelId, C, R, M> {
+    FreeTask::lift_f(Thread::Channel(Rc::new(|id| id)))
+}
+
+/// Sends `message` through `channel`, without waiting for it to be received.
+pub fn send<'a, C: 'a, R: 'a, M: 'a>(channel: ChannelId, message: M) -> FreeTask<'a, (), C, R, M> {
+    FreeTask::lift_f(Thread::Send(channel, message, ()))
+}
+
+/// Waits for a message on `channel`, and returns it. Messages are received in the order they were sent.
+#[must_use]
+pub fn receive<'a, C: 'a, R: 'a, M: 'a>(channel: ChannelId) -> FreeTask<'a, M, C, R, M> {
+    FreeTask::lift_f(Thread::Receive(channel, Rc::new(|m| m)))
+}
+
+/// Lets other tasks run, before this one continues.
+#[must_use]
+pub fn yield_now<'a, C: 'a, R: 'a, M: 'a>() -> FreeTask<'a, (), C, R, M> {
+    FreeTask::lift_f(Thread::Yield(()))
+}
+
+/// How [`run_tasks`] picks the next task to step, among the ones that are not waiting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// The tasks take turns, in the order they were created.
+    RoundRobin,
+    /// The task with the highest priority runs. Tasks with the same priority take turns.
+    Priority,
+    /// A task is picked at random, using a [`SplitMix64`] generator with this seed.
+    Random(u64),
+}
+
+/// The errors returned by [`run_tasks`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskError {
+    /// The main task waits for something that can never happen. Holds the tasks that were waiting, either for a message or for
+    /// another task.
+    Deadlock(Vec<TaskId>),
+    /// A task joined a task id that was not created by this run.
+    UnknownTask(TaskId),
+    /// A task used a channel id that was not created by this run.
+    UnknownChannel(ChannelId),
+}
+
+impl Display for TaskError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskError::Deadlock(waiting) => write!(
+                f,
+                "deadlock: all {} unfinished tasks are waiting",
+                waiting.len()
+            ),
+            TaskError::UnknownTask(task) => {
+                write!(f, "task {} was not created by this run", task.index)
+            }
+            TaskError::UnknownChannel(channel) => {
+                write!(f, "channel {} was not created by this run", channel.index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TaskError {}
+
+//numbers the calls of `run_tasks`, so ids from one call are not mistaken for ids of another.
+static RUNS: AtomicU64 = AtomicU64::new(0);
+
+type Layer<'a, A, C, R, M> = Thread<'a, C, R, M, FreeTask<'a, A, C, R, M>>;
+
+//the result once a task has finished, otherwise the layer it continues with.
+type State<'a, A, C, R, M> = Result<A, Layer<'a, A, C, R, M>>;
+
+struct Task<'a, A, C, R, M> {
+    priority: i32,
+    //`None` only while the task is being stepped.
+    state: Option<State<'a, A, C, R, M>>,
+}
+
+//The main task is kept apart from the forked ones, because its result has a different type.
+struct Scheduler<'a, A, C, R, M> {
+    run: u64,
+    main: Task<'a, A, C, R, M>,
+    forked: Vec<Task<'a, (), C, R, M>>,
+    channels: Vec<VecDeque<M>>,
+    //the task that ran last. The search for the next one starts after it, so tasks take turns.
+    last: usize,
+    rng: Option<SplitMix64>,
+}
+
+impl<'a, A: 'a, C: 'a, R: 'a, M: 'a> Scheduler<'a, A, C, R, M> {
+    fn task_id(&self, index: usize) -> TaskId {
+        TaskId {
+            run: self.run,
+            index,
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.forked.len() + 1
+    }
+
+    fn priority(&self, task: usize) -> i32 {
+        match task {
+            0 => self.main.priority,
+            _ => self.forked[task - 1].priority,
+        }
+    }
+
+    fn finished(&self, task: usize) -> bool {
+        match task {
+            0 => matches!(self.main.state, Some(Ok(_))),
+            _ => matches!(self.forked[task - 1].state, Some(Ok(()))),
+        }
+    }
+
+    //checks that the ids a task uses belong to this run, so the other methods can rely on them.
+    fn check_task(&self, task: TaskId) -> Result<(), TaskError> {
+        (task.run == self.run && task.index < self.count())
+            .then_some(())
+            .ok_or(TaskError::UnknownTask(task))
+    }
+
+    fn check_channel(&self, channel: ChannelId) -> Result<(), TaskError> {
+        (channel.run == self.run && channel.index < self.channels.len())
+            .then_some(())
+            .ok_or(TaskError::UnknownChannel(channel))
+    }
+
+    fn check<X: 'a>(
+        &self,
+        program: FreeTask<'a, X, C, R, M>,
+    ) -> Result<State<'a, X, C, R, M>, TaskError> {
+        let state = program.resume();
+        match &state {
+            Err(Thread::Join(other, _)) => self.check_task(*other)?,
+            Err(Thread::Send(channel, ..) | Thread::Receive(channel, _)) => {
+                self.check_channel(*channel)?;
+            }
+            _ => {}
+        }
+        Ok(state)
+    }
+
+    fn spawn(
+        &mut self,
+        task: FreeTask<'a, (), C, R, M>,
+        priority: i32,
+    ) -> Result<TaskId, TaskError> {
+        self.forked.push(Task {
+            priority,
+            state: None,
+        });
+        let index = self.count() - 1;
+        self.forked[index - 1].state = Some(self.check(task)?);
+        Ok(self.task_id(index))
+    }
+
+    fn waits<X>(&self, layer: &Layer<'a, X, C, R, M>) -> bool {
+        match layer {
+            Thread::Join(other, _) => !self.finished(other.index),
+            Thread::Receive(channel, _) => self.channels[channel.index].is_empty(),
+            _ => false,
+        }
+    }
+
+    fn is_ready(&self, task: usize) -> bool {
+        match task {
+            0 => matches!(&self.main.state, Some(Err(layer)) if !self.waits(layer)),
+            _ => matches!(&self.forked[task - 1].state, Some(Err(layer)) if !self.waits(layer)),
+        }
+    }
+
+    fn pick(&mut self, policy: Policy) -> Option<usize> {
+        let count = self.count();
+        let ready: Vec<_> = (1..=count)
+            .map(|offset| (self.last + offset) % count)
+            .filter(|task| self.is_ready(*task))
+            .collect();
+        match (policy, &mut self.rng) {
+            (Policy::Random(_), Some(rng)) => {
+                (!ready.is_empty()).then(|| ready[rng.index(ready.len())])
+            }
+            (Policy::Priority, _) => ready
+                .into_iter()
+                .min_by_key(|task| std::cmp::Reverse(self.priority(*task))),
+            _ => ready.first().copied(),
+        }
+    }
+
+    fn perform<X: 'a, H: FnMut(TaskId, C) -> R>(
+        &mut self,
+        task: usize,
+        layer: Layer<'a, X, C, R, M>,
+        handler: &mut H,
+    ) -> Result<State<'a, X, C, R, M>, TaskError> {
+        let program = match layer {
+            Thread::Command(command, next) => next(handler(self.task_id(task), command)),
+            Thread::Fork(child, priority, next) => next(self.spawn(child, priority)?),
+            Thread::Join(_, next) | Thread::Yield(next) => next,
+            Thread::Channel(next) => {
+                self.channels.push(VecDeque::new());
+                next(ChannelId {
+                    run: self.run,
+                    index: self.channels.len() - 1,
+                })
+            }
+            Thread::Send(channel, message, next) => {
+                self.channels[channel.index].push_back(message);
+                next
+            }
+            Thread::Receive(channel, next) => match self.channels[channel.index].pop_front() {
+                Some(message) => next(message),
+                //only ready tasks are stepped, so this does not happen.
+                None => FreeTask::Free(Box::new(Thread::Receive(channel, next))),
+            },
+        };
+        self.check(program)
+    }
+
+    fn step<H: FnMut(TaskId, C) -> R>(
+        &mut self,
+        task: usize,
+        handler: &mut H,
+    ) -> Result<(), TaskError> {
+        self.last = task;
+        if task == 0 {
+            if let Some(Err(layer)) = self.main.state.take() {
+                self.main.state = Some(self.perform(task, layer, handler)?);
+            }
+        } else if let Some(Err(layer)) = self.forked[task - 1].state.take() {
+            self.forked[task - 1].state = Some(self.perform(task, layer, handler)?);
+        }
+        Ok(())
+    }
+}
+
+/// Runs `main` and the tasks it forks, interleaving them one layer at a time, until `main` finishes.
+///
+/// Commands of all tasks are passed to `handler`, together with the id of the task that issued them. Tasks that are still
+/// running when `main` finishes are dropped.
+///
+/// # Errors
+/// Returns [`TaskError::Deadlock`] if `main` has not finished, but all tasks are waiting, and the other variants of
+/// [`TaskError`] if a task uses an id that was not created by this call.
+pub fn run_tasks<'a, A: 'a, C: 'a, R: 'a, M: 'a, H>(
+    main: FreeTask<'a, A, C, R, M>,
+    policy: Policy,
+    mut handler: H,
+) -> Result<A, TaskError>
+where
+    H: FnMut(TaskId, C) -> R,
+{
+    let mut scheduler = Scheduler {
+        run: RUNS.fetch_add(1, Ordering::Relaxed),
+        main: Task {
+            priority: 0,
+            state: None,
+        },
+        forked: Vec::new(),
+        channels: Vec::new(),
+        last: 0,
+        rng: match policy {
+            Policy::Random(seed) => Some(SplitMix64::new(seed)),
+            _ => None,
+        },
+    };
+    scheduler.main.state = Some(scheduler.check(main)?);
+    loop {
+        if let Some(Ok(a)) = scheduler.main.state {
+            return Ok(a);
+        }
+        match scheduler.pick(policy) {
+            Some(task) => scheduler.step(task, &mut handler)?,
+            None => {
+                return Err(TaskError::Deadlock(
+                    (0..scheduler.count())
+                        .filter(|task| !scheduler.finished(*task))
+                        .map(|task| scheduler.task_id(task))
+                        .collect(),
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod concurrency_tests {
+    use super::{
+        channel, command, fork, fork_with_priority, join, receive, run_tasks, send, yield_now,
+        FreeTask, Policy, TaskError,
+    };
+    use crate::collections::for_each_m;
+    use crate::free_do;
+
+    //commands are names of steps, which the handler logs together with the task. Messages are numbers.
+    type Task<'a, A> = FreeTask<'a, A, &'static str, (), u32>;
+
+    fn steps<'a>(names: Vec<&'static str>) -> Task<'a, ()> {
+        for_each_m(names, command)
+    }
+
+    fn run_logged<'a, A: 'a>(
+        program: Task<'a, A>,
+        policy: Policy,
+    ) -> (Result<A, TaskError>, Vec<(usize, &'static str)>) {
+        let mut log = Vec::new();
+        let result = run_tasks(program, policy, |task, step| log.push((task.index(), step)));
+        (result, log)
+    }
+
+    fn workers<'a>(first_priority: i32, second_priority: i32) -> Task<'a, ()> {
+        free_do! {
+            a <= fork_with_priority(first_priority, steps(vec!["a1", "a2", "a3"]));
+            b <= fork_with_priority(second_priority, steps(vec!["b1", "b2"]));
+            join(a);
+            join(b);
+            command("done")
+        }
+    }
+
+    fn names(log: &[(usize, &'static str)]) -> Vec<&'static str> {
+        log.iter().map(|(_, name)| *name).collect()
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let (result, log) = run_logged(workers(0, 0), Policy::RoundRobin);
+        assert_eq!(result, Ok(()));
+        assert_eq!(names(&log), vec!["a1", "a2", "b1", "a3", "b2", "done"]);
+        assert_eq!(log[2], (2, "b1"));
+        assert_eq!(log[5], (0, "done"));
+    }
+
+    #[test]
+    fn test_main_ends_with_join() {
+        //the main task only finishes once its last layer has run, even if nothing but data follows it.
+        let program: Task<()> = free_do! {
+            worker <= fork(steps(vec!["w1", "w2"]));
+            join(worker)
+        };
+        let (result, log) = run_logged(program, Policy::RoundRobin);
+        assert_eq!(result, Ok(()));
+        assert_eq!(names(&log), vec!["w1", "w2"]);
+    }
+
+    #[test]
+    fn test_priority() {
+        //the main task has priority 0.
+        let (_, log) = run_logged(workers(-1, 5), Policy::Priority);
+        assert_eq!(names(&log), vec!["b1", "b2", "a1", "a2", "a3", "done"]);
+        //equal priorities take turns.
+        let (_, log) = run_logged(workers(-1, -1), Policy::Priority);
+        assert_eq!(names(&log), vec!["a1", "b1", "a2", "b2", "a3", "done"]);
+    }
+
+    #[test]
+    fn test_random_is_reproducible() {
+        let logs: Vec<_> = (0..20)
+            .map(|seed| run_logged(workers(0, 0), Policy::Random(seed)).1)
+            .collect();
+        for (seed, log) in (0..).zip(&logs) {
+            assert_eq!(*log, run_logged(workers(0, 0), Policy::Random(seed)).1);
+            let names = names(log);
+            assert_eq!(names.len(), 6);
+            assert_eq!(names.last(), Some(&"done"));
+            let a: Vec<_> = names.iter().filter(|n| n.starts_with('a')).collect();
+            assert_eq!(a, vec![&"a1", &"a2", &"a3"]);
+        }
+        assert!(logs.iter().any(|log| *log != logs[0]));
+    }
+
+    //a task that doubles numbers, forever.
+    fn doubler<'a>() -> Task<'a, [u32; 3]> {
+        free_do! {
+            requests <= channel();
+            replies <= channel();
+            fork(crate::control::forever(free_do! {
+                n <= receive(requests);
+                yield_now();
+                send(replies, n * 2)
+            }));
+            for_each_m(1..=3, move |n| send(requests, n));
+            a <= receive(replies);
+            b <= receive(replies);
+            c <= receive(replies);
+            yield [a, b, c]
+        }
+    }
+
+    #[test]
+    fn test_channels() {
+        for policy in [Policy::RoundRobin, Policy::Priority, Policy::Random(3)] {
+            //the doubler still waits for requests when the main task finishes.
+            assert_eq!(run_logged(doubler(), policy).0, Ok([2, 4, 6]));
+        }
+    }
+
+    #[test]
+    fn test_deadlock() {
+        let stuck: Task<u32> = free_do! {
+            nobody <= channel();
+            fork(free_do! {
+                command("waiting");
+                x <= receive(nobody);
+                command(if x > 0 { "positive" } else { "zero" })
+            });
+            receive(nobody)
+        };
+        let (result, log) = run_logged(stuck, Policy::RoundRobin);
+        let Err(TaskError::Deadlock(waiting)) = result else {
+            panic!("expected a deadlock");
+        };
+        assert!(waiting[0].is_main());
+        assert_eq!(waiting[1].index(), 1);
+        assert_eq!(names(&log), vec!["waiting"]);
+    }
+
+    #[test]
+    fn test_ids_of_other_runs() {
+        //ids escape a run through its result, and are rejected by the next one.
+        let ids: Task<_> = free_do! {
+            task <= fork(steps(vec!["forked"]));
+            numbers <= channel();
+            yield (task, numbers)
+        };
+        let (task, numbers) = run_logged(ids, Policy::RoundRobin).0.unwrap();
+        let (result, _) = run_logged(join(task), Policy::RoundRobin);
+        assert_eq!(result, Err(TaskError::UnknownTask(task)));
+        let (result, log) = run_logged(
+            free_do! {
+                command("sending");
+                send(numbers, 1)
+            },
+            Policy::RoundRobin,
+        );
+        assert_eq!(result, Err(TaskError::UnknownChannel(numbers)));
+        assert_eq!(names(&log), vec!["sending"]);
+        //also in forked tasks.
+        let forks: Task<()> = free_do! {
+            fork(free_do! {
+                receive(numbers);
+                yield ()
+            });
+            yield ()
+        };
+        let (result, _) = run_logged(forks, Policy::RoundRobin);
+        assert_eq!(result, Err(TaskError::UnknownChannel(numbers)));
+    }
+}
@@ -0,0 +1,113 @@
+//! Implementations of the traits of the [`interpret`][crate::interpret] helpers and the [renderers][crate::render] for the
+//! Console effect.
+//!
+//! They live in their own module, so the effect itself does not depend on any of the helpers.
+
+use super::{Console, ConsoleInput, FreeConsole};
+use crate::interpret::explore::Choices;
+use crate::interpret::fuzz::Domain;
+use crate::interpret::journal::Feed;
+use crate::prng::SplitMix64;
+use crate::render::DescribeLayer;
+
+impl<'a, A: 'a> Feed<FreeConsole<'a, A>> for Console<'a, FreeConsole<'a, A>> {
+    type Input = ConsoleInput;
+
+    fn proceed(self) -> Result<FreeConsole<'a, A>, Self> {
+        match self {
+            Console::PrintLine(_, next) => Ok(next),
+            other => Err(other),
+        }
+    }
+
+    fn feed(self, input: ConsoleInput) -> Result<FreeConsole<'a, A>, Self> {
+        match (self, input) {
+            (Console::ReadLine(next), ConsoleInput::Line(line)) => Ok(next(line)),
+            (Console::ChooseFrom(options, next), ConsoleInput::Choice(choice))
+                if choice < options.len() =>
+            {
+                Ok(next(choice))
+            }
+            (other, _) => Err(other),
+        }
+    }
+}
+
+/// Lists every option of [`Console::ChooseFrom`]. A line read by [`Console::ReadLine`] can be anything, so no inputs are listed for it.
+impl<'a, A: 'a> Choices<FreeConsole<'a, A>> for Console<'a, FreeConsole<'a, A>> {
+    fn inputs(&self) -> Vec<ConsoleInput> {
+        match self {
+            Console::ChooseFrom(options, _) => {
+                (0..options.len()).map(ConsoleInput::Choice).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Lines read by [`Console::ReadLine`] are random words of up to 8 lowercase letters, and get simplified by making them shorter.
+/// Choices get simplified towards the first option.
+impl<'a, A: 'a> Domain<FreeConsole<'a, A>> for Console<'a, FreeConsole<'a, A>> {
+    fn generate(&self, rng: &mut SplitMix64) -> Option<ConsoleInput> {
+        match self {
+            Console::PrintLine(..) => None,
+            Console::ReadLine(_) => {
+                const LETTERS: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+                let length = rng.index(9);
+                let word = (0..length).map(|_| char::from(LETTERS[rng.index(LETTERS.len())]));
+                Some(ConsoleInput::Line(word.collect()))
+            }
+            Console::ChooseFrom(options, _) => {
+                (!options.is_empty()).then(|| ConsoleInput::Choice(rng.index(options.len())))
+            }
+        }
+    }
+
+    fn simplify(input: &ConsoleInput) -> Vec<ConsoleInput> {
+        match input {
+            ConsoleInput::Line(line) => {
+                let length = line.chars().count();
+                let mut simpler = Vec::new();
+                if length > 0 {
+                    simpler.push(ConsoleInput::Line(String::new()));
+                }
+                if length > 1 {
+                    simpler.push(ConsoleInput::Line(line.chars().take(length / 2).collect()));
+                }
+                simpler
+            }
+            ConsoleInput::Choice(choice) => {
+                let mut simpler = Vec::new();
+                if *choice > 0 {
+                    simpler.push(ConsoleInput::Choice(0));
+                }
+                if *choice > 1 {
+                    simpler.push(ConsoleInput::Choice(choice - 1));
+                }
+                simpler
+            }
+        }
+    }
+}
+
+/// Names the command and its arguments. Only [`Console::PrintLine`] can be entered without an input.
+impl<'a, A: 'a> DescribeLayer<FreeConsole<'a, A>> for Console<'a, FreeConsole<'a, A>> {
+    fn describe(&self) -> String {
+        match self {
+            Console::PrintLine(line, _) => format!("print {line:?}"),
+            Console::ReadLine(_) => String::from("read a line"),
+            Console::ChooseFrom(options, _) => format!("choose from {options:?}"),
+        }
+    }
+
+    fn children(&self) -> Vec<&FreeConsole<'a, A>> {
+        match self {
+            Console::PrintLine(_, next) => vec![next],
+            _ => Vec::new(),
+        }
+    }
+
+    fn has_opaque_continuation(&self) -> bool {
+        !matches!(self, Console::PrintLine(..))
+    }
+}
@@ -0,0 +1,359 @@
+//! The Console effect: Programs that print lines of text, read lines of text, and let the user pick from a list of options.
+//!
+//! There are two interpreters. [`run_console`] talks to real input and output handles (like [`std::io::stdin()`] and
+//! [`std::io::stdout()`]), while [`run_scripted`] feeds the program a fixed list of inputs and returns everything it printed,
+//! which makes it easy to test whole programs. [`Console`] also implements [`Feed`][crate::interpret::journal::Feed], so the inputs of a console program can be
+//! recorded in a [journal][crate::interpret::journal] and replayed later. It implements [`Choices`][crate::interpret::explore::Choices] too, so every way to pick
+//! from the options can be [explored][crate::interpret::explore], [`Domain`][crate::interpret::fuzz::Domain] for [fuzzing][crate::interpret::fuzz], and
+//! [`DescribeLayer`][crate::render::DescribeLayer] for the [debugger][crate::interpret::debugger] and the
+//! [renderers][crate::render].
+//!
+//! ```
+//! # use higher_free_macro::free_do;
+//! use higher_free_macro::effects::console::{choose_from, print_line, read_line, run_scripted, FreeConsole};
+//! fn main() {
+//!     let program: FreeConsole<usize> = free_do! {
+//!         print_line("What's your name?");
+//!         name <= read_line();
+//!         print_line(format!("Hello, {name}! Which snack would you like?"));
+//!         choose_from(vec!["Sausage roll", "Pickles"])
+//!     };
+//!     let transcript = run_scripted(program, ["Bob", "3", "1"]);
+//!     assert_eq!(transcript.result.unwrap(), 0);
+//!     assert_eq!(transcript.output[1], "Hello, Bob! Which snack would you like?");
+//! }
+//! ```
+
+use std::fmt::Display;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+
+use higher::Functor;
+
+mod interpret;
+
+/// The [`Functor`] of the Console effect.
+#[derive(Clone)]
+pub enum Console<'a, A> {
+    /// Prints a line of text.
+    PrintLine(String, A),
+    /// Reads a line of text, without the line break, and continues with the program returned by the function.
+    ReadLine(Rc<dyn Fn(String) -> A + 'a>),
+    /// Lets the user pick one of the options, and continues with the program returned by the function for the index of the chosen option.
+    ChooseFrom(Vec<String>, Rc<dyn Fn(usize) -> A + 'a>),
+}
+
+impl<'a, A: 'a> Functor<'a, A> for Console<'a, A> {
+    type Target<T> = Console<'a, T>;
+
+    fn fmap<B, F>(self, f: F) -> Self::Target<B>
+    where
+        F: Fn(A) -> B + 'a,
+    {
+        match self {
+            Console::PrintLine(line, next) => Console::PrintLine(line, f(next)),
+            Console::ReadLine(next) => Console::ReadLine(Rc::new(move |line| f(next(line)))),
+            Console::ChooseFrom(options, next) => {
+                Console::ChooseFrom(options, Rc::new(move |choice| f(next(choice))))
+            }
+        }
+    }
+}
+
+free!(<'a>, pub FreeConsole<'a, A>, Console<'a, FreeConsole<'a, A>>);
+
+/// Prints a line of text.
+pub fn print_line<'a>(line: impl Into<String>) -> FreeConsole<'a, ()> {
+    FreeConsole::lift_f(Console::PrintLine(line.into(), ()))
+}
+
+/// Reads a line of text. The line break is not part of the result.
+#[must_use]
+pub fn read_line<'a>() -> FreeConsole<'a, String> {
+    FreeConsole::lift_f(Console::ReadLine(Rc::new(|line| line)))
+}
+
+/// Lets the user choose one of the options, and returns the index of the chosen one.
+///
+/// # Panics
+/// Panics if `options` is empty.
+pub fn choose_from<'a, S: Into<String>>(options: Vec<S>) -> FreeConsole<'a, usize> {
+    assert!(
+        !options.is_empty(),
+        "choose_from() needs at least one option"
+    );
+    FreeConsole::lift_f(Console::ChooseFrom(
+        options.into_iter().map(Into::into).collect(),
+        Rc::new(|choice| choice),
+    ))
+}
+
+/// The inputs a console program can get, for use with [journals][crate::interpret::journal].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConsoleInput {
+    /// A line read by [`Console::ReadLine`].
+    Line(String),
+    /// The index of the option chosen in [`Console::ChooseFrom`].
+    Choice(usize),
+}
+
+/// Shows the input as a user would type it, so a list of inputs can be passed to [`run_scripted`]. Choices are counted from 1.
+impl Display for ConsoleInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConsoleInput::Line(line) => write!(f, "{line}"),
+            ConsoleInput::Choice(choice) => write!(f, "{}", choice + 1),
+        }
+    }
+}
+
+/// Printed by the interpreters if the user's choice is not one of the options.
+pub const INVALID_CHOICE: &str = "Invalid choice. Please select one of the options given above.";
+
+fn read_input_line(input: &mut impl BufRead) -> io::Result<String> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "input ended while the program was waiting for a line",
+        ));
+    }
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    Ok(line)
+}
+
+//The interpreters differ only in where lines come from and go to.
+trait Terminal {
+    fn print(&mut self, line: String) -> io::Result<()>;
+    fn read(&mut self) -> io::Result<String>;
+}
+
+struct Handles<R, W> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Terminal for Handles<R, W> {
+    fn print(&mut self, line: String) -> io::Result<()> {
+        writeln!(self.output, "{line}")
+    }
+
+    fn read(&mut self) -> io::Result<String> {
+        self.output.flush()?;
+        read_input_line(&mut self.input)
+    }
+}
+
+struct Script<I> {
+    inputs: I,
+    output: Vec<String>,
+}
+
+impl<I: Iterator<Item = S>, S: AsRef<str>> Terminal for Script<I> {
+    fn print(&mut self, line: String) -> io::Result<()> {
+        self.output.push(line);
+        Ok(())
+    }
+
+    fn read(&mut self) -> io::Result<String> {
+        match self.inputs.next() {
+            None => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "input ended while the program was waiting for a line",
+            )),
+            Some(line) if line.as_ref().contains('\n') => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a scripted input contains a line break",
+            )),
+            Some(line) => Ok(line.as_ref().to_owned()),
+        }
+    }
+}
+
+fn run_terminal<'a, A: 'a>(
+    program: FreeConsole<'a, A>,
+    terminal: &mut impl Terminal,
+) -> io::Result<A> {
+    let mut program = program;
+    loop {
+        program = match program.resume() {
+            Ok(a) => return Ok(a),
+            Err(Console::PrintLine(line, next)) => {
+                terminal.print(line)?;
+                next
+            }
+            Err(Console::ReadLine(next)) => next(terminal.read()?),
+            Err(Console::ChooseFrom(options, next)) => {
+                if options.is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "the program asked to choose from an empty list of options",
+                    ));
+                }
+                for (number, option) in options.iter().enumerate().map(|(i, o)| (i + 1, o)) {
+                    terminal.print(format!("{number}: {option}"))?;
+                }
+                let choice = loop {
+                    let line = terminal.read()?;
+                    match line.trim().parse::<usize>() {
+                        Ok(number) if (1..=options.len()).contains(&number) => break number - 1,
+                        _ => terminal.print(INVALID_CHOICE.to_owned())?,
+                    }
+                };
+                next(choice)
+            }
+        };
+    }
+}
+
+/// Runs the program on the given input and output handles.
+///
+/// `ChooseFrom` prints the options numbered starting at 1, and reads lines until one of them is a valid number,
+/// printing [`INVALID_CHOICE`] for each line that isn't.
+///
+/// # Errors
+/// Returns an error if reading or writing fails, if the input ends before the program does (as
+/// [`io::ErrorKind::UnexpectedEof`]), or if the program asks to choose from an empty list of options (as [`io::ErrorKind::InvalidInput`]).
+pub fn run_console<'a, A: 'a>(
+    program: FreeConsole<'a, A>,
+    input: impl BufRead,
+    output: impl Write,
+) -> io::Result<A> {
+    let mut handles = Handles { input, output };
+    let result = run_terminal(program, &mut handles)?;
+    handles.output.flush()?;
+    Ok(result)
+}
+
+/// What a program printed when run by [`run_scripted`], and its result.
+#[derive(Debug)]
+pub struct Transcript<A> {
+    /// All lines printed, in order, each as it was printed. Inputs are not echoed.
+    pub output: Vec<String>,
+    /// The result of the program, or the error [`run_console`] would have returned, for instance if the script ran out of
+    /// inputs.
+    pub result: io::Result<A>,
+}
+
+/// Runs the program, using the given lines as input, and returns what it printed.
+///
+/// This behaves exactly like [`run_console`], including the validation of choices. Each input is a line as the user would type
+/// it, without the line break. If an input contains a line break, the run stops with an [`io::ErrorKind::InvalidInput`] error
+/// when it gets read.
+pub fn run_scripted<'a, A: 'a, S: AsRef<str>>(
+    program: FreeConsole<'a, A>,
+    inputs: impl IntoIterator<Item = S>,
+) -> Transcript<A> {
+    let mut script = Script {
+        inputs: inputs.into_iter(),
+        output: Vec::new(),
+    };
+    let result = run_terminal(program, &mut script);
+    Transcript {
+        output: script.output,
+        result,
+    }
+}
+
+#[cfg(test)]
+mod console_tests {
+    use super::{
+        choose_from, print_line, read_line, run_console, run_scripted, Console, FreeConsole,
+        INVALID_CHOICE,
+    };
+    use crate::control::loop_m;
+    use crate::free_do;
+    use std::io;
+    use std::ops::ControlFlow;
+    use std::rc::Rc;
+
+    fn guessing_game<'a>() -> FreeConsole<'a, u32> {
+        loop_m(1, |attempt: u32| {
+            free_do! {
+                print_line("Guess a number:");
+                guess <= read_line();
+                if guess == "7" {
+                    print_line("Correct!");
+                    yield ControlFlow::Break(attempt)
+                } else {
+                    print_line(format!("{guess} is wrong."));
+                    yield ControlFlow::Continue(attempt + 1)
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_scripted() {
+        let transcript = run_scripted(guessing_game(), ["3", "7", "unused"]);
+        assert_eq!(transcript.result.unwrap(), 2);
+        assert_eq!(
+            transcript.output,
+            vec![
+                "Guess a number:",
+                "3 is wrong.",
+                "Guess a number:",
+                "Correct!"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scripted_runs_out() {
+        let transcript = run_scripted(guessing_game(), ["1"]);
+        assert_eq!(
+            transcript.result.unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        assert_eq!(transcript.output.len(), 3);
+    }
+
+    #[test]
+    fn test_choose_from() {
+        let program = choose_from(vec!["a", "b"]);
+        let transcript = run_scripted(program, ["0", "x", " 2 "]);
+        assert_eq!(transcript.result.unwrap(), 1);
+        assert_eq!(
+            transcript.output,
+            vec!["1: a", "2: b", INVALID_CHOICE, INVALID_CHOICE]
+        );
+
+        let empty: FreeConsole<usize> =
+            FreeConsole::lift_f(Console::ChooseFrom(Vec::new(), Rc::new(|c| c)));
+        assert_eq!(
+            run_scripted(empty, ["1"]).result.unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn test_scripted_line_breaks() {
+        //printed lines are kept whole, and inputs can't sneak in a second line.
+        let transcript = run_scripted(print_line("two\nlines"), Vec::<String>::new());
+        assert_eq!(transcript.output, vec!["two\nlines"]);
+        let transcript = run_scripted(guessing_game(), ["3\n7"]);
+        assert_eq!(
+            transcript.result.unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(transcript.output, vec!["Guess a number:"]);
+    }
+
+    #[test]
+    fn test_run_console_handles() {
+        let mut output = Vec::new();
+        let result = run_console(guessing_game(), "8\r\n7\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(result, 2);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Guess a number:\n8 is wrong.\nGuess a number:\nCorrect!\n"
+        );
+        let eof = run_console(read_line(), "".as_bytes(), Vec::new()).unwrap_err();
+        assert_eq!(eof.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
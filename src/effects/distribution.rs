@@ -0,0 +1,274 @@
+//! The Distribution effect: A small probabilistic programming language.
+//!
+//! A program makes weighted random choices (with [`bernoulli`], [`categorical`] and [`uniform`]), and can discard runs
+//! that don't match an observation (with [`condition`]). It describes a probability distribution over its results.
+//! [`enumerate`] computes this distribution exactly, by following every choice. [`monte_carlo`] estimates it by running the
+//! program many times with random choices drawn from a seeded [`SplitMix64`] generator, which also works for programs with
+//! too many (or infinitely many) possible runs.
+//!
+//! ```
+//! # use higher_free_macro::free_do;
+//! use higher_free_macro::effects::distribution::{condition, enumerate, uniform, FreeDistribution};
+//! fn main() {
+//!     //two dice are thrown, and at least one shows a six. How likely is a double six?
+//!     let dice = || -> FreeDistribution<bool> {
+//!         free_do! {
+//!             a <= uniform(vec![1, 2, 3, 4, 5, 6]);
+//!             b <= uniform(vec![1, 2, 3, 4, 5, 6]);
+//!             condition(a == 6 || b == 6);
+//!             yield a == b
+//!         }
+//!     };
+//!     let probability = enumerate(dice())[&true];
+//!     assert!((probability - 1.0 / 11.0).abs() < 1e-12);
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use higher::Functor;
+
+use crate::prng::SplitMix64;
+
+/// The [`Functor`] of the Distribution effect. All random choices are expressed through this single command.
+#[derive(Clone)]
+pub enum Weighted<'a, A> {
+    /// Picks an index into the weights, with probabilities proportional to the weights, and continues with the program returned
+    /// by the function for it. The weights are finite and not negative. If they sum up to zero, the run is discarded.
+    Choose(Vec<f64>, Rc<dyn Fn(usize) -> A + 'a>),
+}
+
+impl<'a, A: 'a> Functor<'a, A> for Weighted<'a, A> {
+    type Target<T> = Weighted<'a, T>;
+
+    fn fmap<B, F>(self, f: F) -> Self::Target<B>
+    where
+        F: Fn(A) -> B + 'a,
+    {
+        match self {
+            Weighted::Choose(weights, next) => {
+                Weighted::Choose(weights, Rc::new(move |i| f(next(i))))
+            }
+        }
+    }
+}
+
+free!(<'a>, pub FreeDistribution<'a, A>, Weighted<'a, FreeDistribution<'a, A>>);
+
+/// Returns one of the outcomes, with a probability proportional to its weight.
+///
+/// If there are no outcomes, or all weights are zero, the current run is discarded, like by [`condition`] with `false`.
+///
+/// # Panics
+/// Panics if a weight is negative, infinite or NaN.
+#[must_use]
+pub fn categorical<'a, A: Clone + 'a>(outcomes: Vec<(A, f64)>) -> FreeDistribution<'a, A> {
+    assert!(
+        outcomes.iter().all(|(_, w)| w.is_finite() && *w >= 0.0),
+        "categorical() needs finite, non-negative weights"
+    );
+    let (values, weights): (Vec<_>, Vec<_>) = outcomes.into_iter().unzip();
+    FreeDistribution::lift_f(Weighted::Choose(
+        weights,
+        Rc::new(move |i| values[i].clone()),
+    ))
+}
+
+/// Returns `true` with probability `p`.
+///
+/// # Panics
+/// Panics if `p` is not in the range `0.0..=1.0`.
+#[must_use]
+pub fn bernoulli<'a>(p: f64) -> FreeDistribution<'a, bool> {
+    assert!(
+        (0.0..=1.0).contains(&p),
+        "bernoulli() needs a probability between 0 and 1"
+    );
+    categorical(vec![(true, p), (false, 1.0 - p)])
+}
+
+/// Returns one of the `options`, each with the same probability.
+///
+/// If `options` is empty, there is nothing to return, and the current run is discarded, like by [`condition`] with `false`.
+#[must_use]
+pub fn uniform<'a, A: Clone + 'a>(options: Vec<A>) -> FreeDistribution<'a, A> {
+    categorical(options.into_iter().map(|o| (o, 1.0)).collect())
+}
+
+/// Discards the current run, unless `observation` holds. The other runs keep their relative probabilities.
+#[must_use]
+pub fn condition<'a>(observation: bool) -> FreeDistribution<'a, ()> {
+    if observation {
+        FreeDistribution::Pure(())
+    } else {
+        FreeDistribution::lift_f(Weighted::Choose(Vec::new(), Rc::new(|_| ())))
+    }
+}
+
+fn normalize<A>(mut outcomes: BTreeMap<A, f64>) -> BTreeMap<A, f64> {
+    let total: f64 = outcomes.values().sum();
+    for probability in outcomes.values_mut() {
+        *probability /= total;
+    }
+    outcomes
+}
+
+/// Computes the exact distribution of the program's results, by following every possible run.
+///
+/// The probabilities in the returned map sum up to one. If every run is discarded by [`condition`], the map is empty.
+/// Since all runs are followed, this never returns for programs that can make infinitely many choices.
+pub fn enumerate<'a, A: Ord + 'a>(program: FreeDistribution<'a, A>) -> BTreeMap<A, f64> {
+    let mut outcomes = BTreeMap::new();
+    let mut runs = vec![(program, 1.0)];
+    while let Some((program, probability)) = runs.pop() {
+        match program.resume() {
+            Ok(a) => *outcomes.entry(a).or_insert(0.0) += probability,
+            Err(Weighted::Choose(weights, next)) => {
+                let total: f64 = weights.iter().sum();
+                runs.extend(
+                    weights
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, w)| **w > 0.0)
+                        .map(|(i, w)| (next(i), probability * w / total)),
+                );
+            }
+        }
+    }
+    normalize(outcomes)
+}
+
+/// Runs the program once, drawing its choices from `rng`. Returns `None` if the run got discarded by [`condition`].
+pub fn sample<'a, A: 'a>(program: FreeDistribution<'a, A>, rng: &mut SplitMix64) -> Option<A> {
+    let mut program = program;
+    loop {
+        match program.resume() {
+            Ok(a) => return Some(a),
+            Err(Weighted::Choose(weights, next)) => {
+                let total: f64 = weights.iter().sum();
+                if total <= 0.0 {
+                    return None;
+                }
+                let mut remaining = rng.next_f64() * total;
+                //rounding can leave a tiny remainder, which goes to the last choice with weight.
+                let mut choice = weights.iter().rposition(|w| *w > 0.0).unwrap_or(0);
+                for (i, w) in weights.iter().enumerate() {
+                    if remaining < *w {
+                        choice = i;
+                        break;
+                    }
+                    remaining -= w;
+                }
+                program = next(choice);
+            }
+        }
+    }
+}
+
+/// Estimates the distribution of the program's results, by running it `runs` times with choices drawn from `rng`.
+///
+/// Runs that get discarded by [`condition`] are not counted, so the estimate gets less precise the more runs are discarded.
+/// The probabilities in the returned map sum up to one, unless all runs got discarded, in which case it is empty.
+pub fn monte_carlo<'a, A: Ord + Clone + 'a>(
+    program: &FreeDistribution<'a, A>,
+    runs: usize,
+    rng: &mut SplitMix64,
+) -> BTreeMap<A, f64> {
+    let mut outcomes = BTreeMap::new();
+    for _ in 0..runs {
+        if let Some(a) = sample(program.clone(), rng) {
+            *outcomes.entry(a).or_insert(0.0) += 1.0;
+        }
+    }
+    normalize(outcomes)
+}
+
+#[cfg(test)]
+mod distribution_tests {
+    use super::{
+        bernoulli, categorical, condition, enumerate, monte_carlo, sample, uniform,
+        FreeDistribution,
+    };
+    use crate::free_do;
+    use crate::prng::SplitMix64;
+    use higher::Bind;
+
+    fn assert_close(a: f64, b: f64, tolerance: f64) {
+        assert!((a - b).abs() < tolerance, "{a} is not close to {b}");
+    }
+
+    //the player picks a door, the host opens another one without the prize. Does switching help?
+    //the host's choice is the point of the puzzle. If they picked any door, and runs where it hides the prize were discarded,
+    //switching would not help.
+    fn monty_hall<'a>(switch: bool) -> FreeDistribution<'a, bool> {
+        free_do! {
+            prize <= uniform(vec![0, 1, 2]);
+            pick <= uniform(vec![0, 1, 2]);
+            opened <= uniform((0..3).filter(|d| *d != prize && *d != pick).collect());
+            let pick = if switch { 3 - pick - opened } else { pick };
+            yield pick == prize
+        }
+    }
+
+    #[test]
+    fn test_enumerate() {
+        let stay = enumerate(monty_hall(false));
+        assert_close(stay[&true], 1.0 / 3.0, 1e-12);
+        assert_close(stay[&false], 2.0 / 3.0, 1e-12);
+        assert_close(enumerate(monty_hall(true))[&true], 2.0 / 3.0, 1e-12);
+
+        let loot = enumerate(categorical(vec![
+            ("coin", 3.0),
+            ("gem", 1.0),
+            ("nothing", 0.0),
+        ]));
+        assert_eq!(loot.len(), 2);
+        assert_close(loot["gem"], 0.25, 1e-12);
+
+        let impossible = enumerate(bernoulli(0.5).bind(|b| {
+            free_do! {
+                condition(b);
+                condition(!b);
+                yield b
+            }
+        }));
+        assert!(impossible.is_empty());
+    }
+
+    #[test]
+    fn test_monte_carlo() {
+        let mut rng = SplitMix64::new(11);
+        let estimate = monte_carlo(&monty_hall(true), 20_000, &mut rng);
+        assert_close(estimate[&true], 2.0 / 3.0, 0.02);
+        let coin = monte_carlo(&bernoulli(0.1), 20_000, &mut rng);
+        assert_close(coin[&true], 0.1, 0.01);
+        //same seed, same samples.
+        let draws = |seed| {
+            let mut rng = SplitMix64::new(seed);
+            (0..50)
+                .map(|_| sample(uniform(vec![1, 2, 3]), &mut rng))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(draws(5), draws(5));
+        assert_eq!(sample(condition(false), &mut rng), None);
+    }
+
+    //geometric distribution: the number of failed attempts before the first success. It has infinitely many runs.
+    fn attempts<'a>(p: f64, failures: u32) -> FreeDistribution<'a, u32> {
+        bernoulli(p).bind(move |success| {
+            if success {
+                FreeDistribution::Pure(failures)
+            } else {
+                attempts(p, failures + 1)
+            }
+        })
+    }
+
+    #[test]
+    fn test_infinite_program_sampling() {
+        let estimate = monte_carlo(&attempts(0.5, 0), 20_000, &mut SplitMix64::new(3));
+        assert_close(estimate[&0], 0.5, 0.02);
+        assert_close(estimate[&1], 0.25, 0.02);
+    }
+}
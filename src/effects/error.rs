@@ -0,0 +1,331 @@
+//! The Error effect: Programs that can abort with an error of type `E`, and handle such errors.
+//!
+//! The combinators [`throw`], [`catch`], [`finally`] and [`bracket`] work on any Free Monad whose [`Functor`] layers implement
+//! [`ThrowLayer`], so an eDSL can get error handling by adding a variant for aborting to its command type. The [`Error`]
+//! Functor in this module is the minimal such type, and [`run_error`] interprets its Free Monad into a [`Result`].
+//!
+//! ```
+//! use higher_free_macro::effects::error::{catch, run_error, throw, FreeError};
+//! fn main() {
+//!     let failing: FreeError<u32, String> = throw(String::from("out of sausage rolls"));
+//!     let recovered = catch(failing.clone(), |e: String| FreeError::Pure(u32::try_from(e.len()).unwrap()));
+//!     assert_eq!(run_error(failing), Err(String::from("out of sausage rolls")));
+//!     assert_eq!(run_error(recovered), Ok(20));
+//! }
+//! ```
+
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use higher::{Bind, Functor};
+
+use crate::MonadFree;
+
+/// A [`Functor`] layer that might abort the program with an error of type `E`.
+pub trait ThrowLayer<E>: Sized {
+    /// Creates a layer that aborts the program with `error`.
+    fn from_error(error: E) -> Self;
+
+    /// Checks whether this layer aborts the program.
+    ///
+    /// # Errors
+    /// Returns the error if this layer aborts the program. Otherwise the layer itself is returned as `Ok`.
+    fn into_result(self) -> Result<Self, E>;
+}
+
+/// The [`Functor`] of the Error effect. Its only variant aborts the program, so it never continues with an `A`.
+#[derive(Clone)]
+pub enum Error<E, A> {
+    /// Aborts the program with the given error.
+    Throw(E, PhantomData<A>),
+}
+
+impl<'a, E, A> Functor<'a, A> for Error<E, A> {
+    type Target<T> = Error<E, T>;
+
+    fn fmap<B, F>(self, _: F) -> Self::Target<B>
+    where
+        F: Fn(A) -> B + 'a,
+    {
+        match self {
+            Error::Throw(e, _) => Error::Throw(e, PhantomData),
+        }
+    }
+}
+
+impl<E, A> ThrowLayer<E> for Error<E, A> {
+    fn from_error(error: E) -> Self {
+        Error::Throw(error, PhantomData)
+    }
+
+    fn into_result(self) -> Result<Self, E> {
+        match self {
+            Error::Throw(e, _) => Err(e),
+        }
+    }
+}
+
+free!(pub FreeError<A, E>, Error<E, FreeError<A, E>>);
+
+/// Runs the program, and returns either its result or the error it was aborted with.
+///
+/// # Errors
+/// Returns the error if the program throws one.
+pub fn run_error<A, E>(program: FreeError<A, E>) -> Result<A, E> {
+    match program.resume() {
+        Ok(a) => Ok(a),
+        Err(Error::Throw(e, _)) => Err(e),
+    }
+}
+
+/// Aborts the program with `error`.
+pub fn throw<'a, M, E>(error: E) -> M
+where
+    M: MonadFree<'a>,
+    M::Functor: ThrowLayer<E>,
+{
+    M::wrap(ThrowLayer::from_error(error))
+}
+
+/// Runs `program`. If it throws an error, the rest of it is skipped, and the program returned by `handler` runs instead.
+///
+/// Errors thrown by `handler` are not caught.
+pub fn catch<'a, M, E, H>(program: M, handler: H) -> M
+where
+    M: MonadFree<'a> + 'a,
+    M::Functor: ThrowLayer<E> + Functor<'a, M, Target<M> = <M as MonadFree<'a>>::Functor>,
+    H: Fn(E) -> M + 'a,
+{
+    fn catch_impl<'a, M, E, H>(program: M, handler: Rc<H>) -> M
+    where
+        M: MonadFree<'a> + 'a,
+        M::Functor: ThrowLayer<E> + Functor<'a, M, Target<M> = <M as MonadFree<'a>>::Functor>,
+        H: Fn(E) -> M + 'a,
+    {
+        match program.resume() {
+            Ok(value) => M::pure(value),
+            Err(layer) => match layer.into_result() {
+                Err(error) => handler(error),
+                Ok(layer) => M::wrap(layer.fmap(move |next| catch_impl(next, handler.clone()))),
+            },
+        }
+    }
+    catch_impl(program, Rc::new(handler))
+}
+
+/// Runs `program`, and then `cleanup`, even if `program` throws an error. In that case, the error is re-thrown after `cleanup` has run.
+pub fn finally<'a, M, C, A, E>(program: M, cleanup: C) -> M
+where
+    M: MonadFree<'a, Value = A> + 'a,
+    M::Functor: ThrowLayer<E> + Functor<'a, M, Target<M> = <M as MonadFree<'a>>::Functor>,
+    C: Bind<'a, (), Target<A> = M> + Clone + 'a,
+    A: Clone + 'a,
+    E: Clone + 'a,
+{
+    match program.resume() {
+        Ok(value) => cleanup.bind::<A, _>(move |()| M::pure(value.clone())),
+        Err(layer) => match layer.into_result() {
+            Err(error) => cleanup.bind::<A, _>(move |()| throw::<M, E>(error.clone())),
+            Ok(layer) => M::wrap(layer.fmap(move |next| finally(next, cleanup.clone()))),
+        },
+    }
+}
+
+/// Runs `acquire`, passes the resource it yields to `body`, and finally passes it to `release`.
+/// `release` also runs if `body` throws an error, which is re-thrown afterwards. If `acquire` throws, neither runs.
+pub fn bracket<'a, MR, MB, C, R, B, E, U, Rel>(acquire: MR, release: Rel, body: U) -> MB
+where
+    MR: Bind<'a, R, Target<B> = MB>,
+    MB: MonadFree<'a, Value = B> + 'a,
+    MB::Functor: ThrowLayer<E> + Functor<'a, MB, Target<MB> = <MB as MonadFree<'a>>::Functor>,
+    C: Bind<'a, (), Target<B> = MB> + Clone + 'a,
+    R: Clone,
+    B: Clone + 'a,
+    E: Clone + 'a,
+    U: Fn(R) -> MB + 'a,
+    Rel: Fn(R) -> C + 'a,
+{
+    acquire.bind::<B, _>(move |resource: R| finally(body(resource.clone()), release(resource)))
+}
+
+#[cfg(test)]
+mod error_tests {
+    use super::{bracket, catch, finally, run_error, throw, FreeError, ThrowLayer};
+    use crate::free_do;
+    use higher::Functor;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_run_error() {
+        let ok: FreeError<u32, &str> = free_do! {
+            x <= FreeError::Pure(2);
+            yield x * 3
+        };
+        assert_eq!(run_error(ok), Ok(6));
+        let failed: FreeError<u32, &str> = free_do! {
+            x <= FreeError::Pure(2);
+            if x > 1 {
+                throw::<FreeError<(), &str>, _>("too big")
+            }
+            yield x * 3
+        };
+        assert_eq!(run_error(failed), Err("too big"));
+    }
+
+    //A shop, where purchases can fail. The log records what the interpreter saw.
+    #[derive(Clone)]
+    enum Shop<'a, A> {
+        Say(&'static str, A),
+        Buy(&'static str, Rc<dyn Fn(u32) -> A + 'a>),
+        Fail(String),
+    }
+
+    impl<'a, A: 'a> Functor<'a, A> for Shop<'a, A> {
+        type Target<T> = Shop<'a, T>;
+
+        fn fmap<B, F>(self, f: F) -> Self::Target<B>
+        where
+            F: Fn(A) -> B + 'a,
+        {
+            match self {
+                Shop::Say(s, next) => Shop::Say(s, f(next)),
+                Shop::Buy(item, next) => Shop::Buy(item, Rc::new(move |x| f(next(x)))),
+                Shop::Fail(e) => Shop::Fail(e),
+            }
+        }
+    }
+
+    impl<A> ThrowLayer<String> for Shop<'_, A> {
+        fn from_error(error: String) -> Self {
+            Shop::Fail(error)
+        }
+
+        fn into_result(self) -> Result<Self, String> {
+            match self {
+                Shop::Fail(e) => Err(e),
+                other => Ok(other),
+            }
+        }
+    }
+
+    free!(<'a>, FreeShop<'a, A>, Shop<'a, FreeShop<'a, A>>);
+
+    fn say<'a>(text: &'static str) -> FreeShop<'a, ()> {
+        FreeShop::lift_f(Shop::Say(text, ()))
+    }
+
+    fn fail<'a, A: 'a>(error: String) -> FreeShop<'a, A> {
+        throw(error)
+    }
+
+    fn buy<'a>(item: &'static str) -> FreeShop<'a, u32> {
+        free_do! {
+            price <= FreeShop::lift_f(Shop::Buy(item, Rc::new(|price| price)));
+            if price == 0 {
+                fail(format!("{item} is sold out"))
+            }
+            yield price
+        }
+    }
+
+    //the shop has no pickles.
+    fn run<'a, A: 'a>(program: FreeShop<'a, A>) -> (Vec<String>, Result<A, String>) {
+        let mut log = Vec::new();
+        let mut program = program;
+        loop {
+            match program.resume() {
+                Ok(a) => return (log, Ok(a)),
+                Err(Shop::Say(s, next)) => {
+                    log.push(s.to_owned());
+                    program = next;
+                }
+                Err(Shop::Buy(item, next)) => {
+                    log.push(format!("buy {item}"));
+                    program = next(if item == "pickles" { 0 } else { 3 });
+                }
+                Err(Shop::Fail(e)) => return (log, Err(e)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_catch() {
+        let purchase = |item| {
+            catch(buy(item), |e: String| {
+                free_do! {
+                    say("The shop assistant apologizes.");
+                    yield u32::try_from(e.len()).unwrap()
+                }
+            })
+        };
+        let program = free_do! {
+            a <= purchase("pickles");
+            b <= purchase("sausage roll");
+            say("Done.");
+            yield (a, b)
+        };
+        assert_eq!(
+            run(program),
+            (
+                vec![
+                    String::from("buy pickles"),
+                    String::from("The shop assistant apologizes."),
+                    String::from("buy sausage roll"),
+                    String::from("Done.")
+                ],
+                Ok((19, 3))
+            )
+        );
+    }
+
+    #[test]
+    fn test_finally() {
+        let program = finally(buy("pickles"), say("Leaving the shop."));
+        assert_eq!(
+            run(program),
+            (
+                vec![
+                    String::from("buy pickles"),
+                    String::from("Leaving the shop.")
+                ],
+                Err(String::from("pickles is sold out"))
+            )
+        );
+        let program = finally(buy("beer"), say("Leaving the shop."));
+        assert_eq!(run(program).1, Ok(3));
+    }
+
+    #[test]
+    fn test_bracket() {
+        let program = |item| {
+            bracket(
+                free_do! { say("Grab a basket."); yield "basket" },
+                |basket| {
+                    if basket == "basket" {
+                        say("Return the basket.")
+                    } else {
+                        say("Where did the basket go?")
+                    }
+                },
+                move |_| buy(item),
+            )
+        };
+        let (log, result) = run(program("pickles"));
+        assert_eq!(log.last().map(String::as_str), Some("Return the basket."));
+        assert_eq!(log.len(), 3);
+        assert!(result.is_err());
+        let (log, result) = run(program("cheese"));
+        assert_eq!(log.len(), 3);
+        assert_eq!(result, Ok(3));
+
+        let failed_acquire = bracket(
+            fail::<&str>(String::from("No baskets.")),
+            |_| say("Return the basket."),
+            |_| buy("beer"),
+        );
+        assert_eq!(
+            run(failed_acquire),
+            (vec![], Err(String::from("No baskets.")))
+        );
+    }
+}
@@ -0,0 +1,383 @@
+//! The Key-Value effect: Programs that store, look up and delete values of type `V` under keys of type `K`.
+//!
+//! [`run_in_memory`] keeps the data in a [`BTreeMap`], which is what tests usually want. [`run_file_backed`] stores it
+//! in an append-only log file in a directory, so the data survives between runs. Both behave the same, so a program does not
+//! need to know which one runs it.
+//!
+//! ```
+//! # use higher_free_macro::free_do;
+//! use std::collections::BTreeMap;
+//! use higher_free_macro::effects::key_value::{get, put, run_in_memory, scan_prefix, Entries, FreeKeyValue};
+//! fn main() {
+//!     let program: FreeKeyValue<Entries<String, u32>, String, u32> = free_do! {
+//!         put(String::from("inventory/sausage roll"), 1);
+//!         put(String::from("inventory/pickles"), 3);
+//!         put(String::from("money"), 12);
+//!         scan_prefix(String::from("inventory/"))
+//!     };
+//!     let mut store = BTreeMap::new();
+//!     let inventory = run_in_memory(program, &mut store);
+//!     assert_eq!(inventory, vec![(String::from("inventory/pickles"), 3), (String::from("inventory/sausage roll"), 1)]);
+//!     assert_eq!(store.len(), 3);
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use higher::Functor;
+
+/// Keys that can be searched by prefix with [`scan_prefix`].
+pub trait KeyPrefix {
+    /// Returns `true` if `prefix` is a prefix of this key.
+    fn has_prefix(&self, prefix: &Self) -> bool;
+}
+
+impl KeyPrefix for String {
+    fn has_prefix(&self, prefix: &Self) -> bool {
+        self.starts_with(prefix.as_str())
+    }
+}
+
+impl<T: PartialEq> KeyPrefix for Vec<T> {
+    fn has_prefix(&self, prefix: &Self) -> bool {
+        self.starts_with(prefix)
+    }
+}
+
+/// Key-value pairs, as returned by [`scan_prefix`].
+pub type Entries<K, V> = Vec<(K, V)>;
+
+/// The [`Functor`] of the Key-Value effect.
+#[derive(Clone)]
+pub enum KeyValue<'a, K, V, A> {
+    /// Looks up the value stored under the key, and continues with the program returned by the function for it.
+    Get(K, Rc<dyn Fn(Option<V>) -> A + 'a>),
+    /// Stores the value under the key, replacing the previous one.
+    Put(K, V, A),
+    /// Removes the key and its value.
+    Delete(K, A),
+    /// Looks up all entries whose keys start with the given prefix, sorted by key, and continues with the program returned
+    /// by the function for them.
+    ScanPrefix(K, Rc<dyn Fn(Entries<K, V>) -> A + 'a>),
+}
+
+impl<'a, K: 'a, V: 'a, A: 'a> Functor<'a, A> for KeyValue<'a, K, V, A> {
+    type Target<T> = KeyValue<'a, K, V, T>;
+
+    fn fmap<B, F>(self, f: F) -> Self::Target<B>
+    where
+        F: Fn(A) -> B + 'a,
+    {
+        match self {
+            KeyValue::Get(key, next) => KeyValue::Get(key, Rc::new(move |v| f(next(v)))),
+            KeyValue::Put(key, value, next) => KeyValue::Put(key, value, f(next)),
+            KeyValue::Delete(key, next) => KeyValue::Delete(key, f(next)),
+            KeyValue::ScanPrefix(prefix, next) => {
+                KeyValue::ScanPrefix(prefix, Rc::new(move |entries| f(next(entries))))
+            }
+        }
+    }
+}
+
+free!(<'a>, pub FreeKeyValue<'a, A, K, V>, KeyValue<'a, K, V, FreeKeyValue<'a, A, K, V>>);
+
+/// Returns the value stored under `key`, if any.
+pub fn get<'a, K: 'a, V: 'a>(key: K) -> FreeKeyValue<'a, Option<V>, K, V> {
+    FreeKeyValue::lift_f(KeyValue::Get(key, Rc::new(|v| v)))
+}
+
+/// Stores `value` under `key`, replacing the previous value.
+pub fn put<'a, K: 'a, V: 'a>(key: K, value: V) -> FreeKeyValue<'a, (), K, V> {
+    FreeKeyValue::lift_f(KeyValue::Put(key, value, ()))
+}
+
+/// Removes `key` and its value. Does nothing if there is no such key.
+pub fn delete<'a, K: 'a, V: 'a>(key: K) -> FreeKeyValue<'a, (), K, V> {
+    FreeKeyValue::lift_f(KeyValue::Delete(key, ()))
+}
+
+/// Returns all entries whose keys start with `prefix`, sorted by key.
+pub fn scan_prefix<'a, K: 'a, V: 'a>(prefix: K) -> FreeKeyValue<'a, Entries<K, V>, K, V> {
+    FreeKeyValue::lift_f(KeyValue::ScanPrefix(prefix, Rc::new(|entries| entries)))
+}
+
+//Runs the program on `store`, and tells `log` about every change before it is made.
+fn run_with_log<'a, A: 'a, K, V, E>(
+    program: FreeKeyValue<'a, A, K, V>,
+    store: &mut BTreeMap<K, V>,
+    mut log: impl FnMut(&K, Option<&V>) -> Result<(), E>,
+) -> Result<A, E>
+where
+    K: Ord + Clone + KeyPrefix + 'a,
+    V: Clone + 'a,
+{
+    let mut program = program;
+    loop {
+        program = match program.resume() {
+            Ok(a) => return Ok(a),
+            Err(KeyValue::Get(key, next)) => next(store.get(&key).cloned()),
+            Err(KeyValue::Put(key, value, next)) => {
+                log(&key, Some(&value))?;
+                store.insert(key, value);
+                next
+            }
+            Err(KeyValue::Delete(key, next)) => {
+                if store.contains_key(&key) {
+                    log(&key, None)?;
+                    store.remove(&key);
+                }
+                next
+            }
+            Err(KeyValue::ScanPrefix(prefix, next)) => next(
+                store
+                    .range(&prefix..)
+                    .take_while(|(key, _)| key.has_prefix(&prefix))
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect(),
+            ),
+        };
+    }
+}
+
+/// Runs the program on the given in-memory `store`, which holds the data afterwards.
+pub fn run_in_memory<'a, A: 'a, K, V>(
+    program: FreeKeyValue<'a, A, K, V>,
+    store: &mut BTreeMap<K, V>,
+) -> A
+where
+    K: Ord + Clone + KeyPrefix + 'a,
+    V: Clone + 'a,
+{
+    match run_with_log(program, store, |_, _| Ok::<(), Infallible>(())) {
+        Ok(a) => a,
+        Err(never) => match never {},
+    }
+}
+
+/// The name of the log file [`run_file_backed`] uses inside its directory.
+pub const LOG_FILE_NAME: &str = "key-value.log";
+
+//Tabs and line breaks separate records and fields in the log, so they get escaped.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        unescaped.push(match c {
+            '\\' => match chars.next()? {
+                '\\' => '\\',
+                't' => '\t',
+                'n' => '\n',
+                'r' => '\r',
+                _ => return None,
+            },
+            c => c,
+        });
+    }
+    Some(unescaped)
+}
+
+fn parse_field<T: FromStr>(field: Option<&str>) -> Option<T> {
+    unescape(field?)?.parse().ok()
+}
+
+fn load_log<K: Ord + FromStr, V: FromStr>(file: &mut File) -> io::Result<BTreeMap<K, V>> {
+    let mut log = Vec::new();
+    file.read_to_end(&mut log)?;
+    //a crash while appending can leave a last line without a line break. It is cut off, so the next change starts on a new line.
+    let complete = log
+        .iter()
+        .rposition(|b| *b == b'\n')
+        .map_or(0, |end| end + 1);
+    if complete < log.len() {
+        file.set_len(complete as u64)?;
+    }
+    let mut store = BTreeMap::new();
+    for (number, line) in log[..complete].lines().enumerate() {
+        let line = line?;
+        let mut fields = line.split('\t');
+        let applied = match fields.next() {
+            Some("put") => parse_field(fields.next())
+                .zip(parse_field(fields.next()))
+                .map(|(key, value)| {
+                    store.insert(key, value);
+                }),
+            Some("delete") => parse_field(fields.next()).map(|key: K| {
+                store.remove(&key);
+            }),
+            _ => None,
+        };
+        if applied.is_none() || fields.next().is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {} of the key-value log is corrupt", number + 1),
+            ));
+        }
+    }
+    Ok(store)
+}
+
+/// Runs the program on data stored in `directory`, which is created if it does not exist yet.
+///
+/// All changes are appended to a log file named [`LOG_FILE_NAME`] in the directory, one line per change, using the
+/// [`Display`] implementations of the keys and values. When the program starts, the log is replayed with their [`FromStr`]
+/// implementations. Reads are answered from memory. The log is never compacted, so it keeps growing with every change.
+/// Each change is synced to storage (with [`File::sync_data`]) before the program continues, so a crash can only leave the
+/// last line incomplete. Such a line is removed from the log.
+///
+/// # Errors
+/// Returns an error if the directory or the log file cannot be created, read or written, or if the log contains a line
+/// that cannot be parsed (as [`io::ErrorKind::InvalidData`]).
+pub fn run_file_backed<'a, A: 'a, K, V>(
+    program: FreeKeyValue<'a, A, K, V>,
+    directory: impl AsRef<Path>,
+) -> io::Result<A>
+where
+    K: Ord + Clone + KeyPrefix + Display + FromStr + 'a,
+    V: Clone + Display + FromStr + 'a,
+{
+    std::fs::create_dir_all(directory.as_ref())?;
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(directory.as_ref().join(LOG_FILE_NAME))?;
+    let mut store = load_log(&mut file)?;
+    run_with_log(program, &mut store, |key, value| {
+        let key = escape(&key.to_string());
+        let line = match value {
+            Some(value) => format!("put\t{key}\t{}\n", escape(&value.to_string())),
+            None => format!("delete\t{key}\n"),
+        };
+        file.write_all(line.as_bytes())?;
+        file.sync_data()
+    })
+}
+
+#[cfg(test)]
+mod key_value_tests {
+    use super::{
+        delete, get, put, run_file_backed, run_in_memory, scan_prefix, Entries, FreeKeyValue,
+        LOG_FILE_NAME,
+    };
+    use crate::free_do;
+    use std::collections::BTreeMap;
+
+    fn shopping<'a>() -> FreeKeyValue<'a, (Option<u32>, Entries<String, u32>), String, u32> {
+        free_do! {
+            pickles <= get(String::from("cart/pickles"));
+            put(String::from("cart/pickles"), pickles.unwrap_or(0) + 2);
+            put(String::from("cart/sausage\troll\n"), 1);
+            put(String::from("cartography"), 5);
+            delete(String::from("cart/cheese"));
+            pickles <= get(String::from("cart/pickles"));
+            cart <= scan_prefix(String::from("cart/"));
+            yield (pickles, cart)
+        }
+    }
+
+    #[test]
+    fn test_in_memory() {
+        let mut store = BTreeMap::from([(String::from("cart/cheese"), 1)]);
+        let (pickles, cart) = run_in_memory(shopping(), &mut store);
+        assert_eq!(pickles, Some(2));
+        assert_eq!(
+            cart,
+            vec![
+                (String::from("cart/pickles"), 2),
+                (String::from("cart/sausage\troll\n"), 1)
+            ]
+        );
+        assert_eq!(store.len(), 3);
+        assert_eq!(run_in_memory(shopping(), &mut store).0, Some(4));
+    }
+
+    #[test]
+    fn test_vec_keys() {
+        let program: FreeKeyValue<Entries<Vec<u8>, char>, Vec<u8>, char> = free_do! {
+            put(vec![1, 2], 'a');
+            put(vec![1], 'b');
+            put(vec![2, 1], 'c');
+            scan_prefix(vec![1])
+        };
+        assert_eq!(
+            run_in_memory(program, &mut BTreeMap::new()),
+            vec![(vec![1], 'b'), (vec![1, 2], 'a')]
+        );
+    }
+
+    #[test]
+    fn test_file_backed() {
+        let directory = std::env::temp_dir().join(format!(
+            "higher-free-macro-key-value-test-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&directory);
+
+        let first = run_file_backed(shopping(), &directory).unwrap();
+        assert_eq!(first.0, Some(2));
+        //deleting a key that isn't there does not write to the log.
+        let log = std::fs::read_to_string(directory.join(LOG_FILE_NAME)).unwrap();
+        assert_eq!(log.lines().count(), 3);
+        //the second run sees the data of the first one.
+        let (pickles, cart) = run_file_backed(shopping(), &directory).unwrap();
+        assert_eq!(pickles, Some(4));
+        let mut in_memory = BTreeMap::new();
+        run_in_memory(shopping(), &mut in_memory);
+        run_in_memory(shopping(), &mut in_memory);
+        assert_eq!(
+            cart,
+            run_in_memory(scan_prefix(String::from("cart/")), &mut in_memory)
+        );
+
+        std::fs::write(directory.join(LOG_FILE_NAME), "put\tonly a key\n").unwrap();
+        let corrupt = run_file_backed(shopping(), &directory).unwrap_err();
+        assert_eq!(corrupt.kind(), std::io::ErrorKind::InvalidData);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_torn_log() {
+        let directory = std::env::temp_dir().join(format!(
+            "higher-free-macro-key-value-torn-test-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        //the second record was only half written.
+        std::fs::write(
+            directory.join(LOG_FILE_NAME),
+            "put\tcart/pickles\t2\nput\tcart/sau",
+        )
+        .unwrap();
+
+        let (pickles, cart) = run_file_backed(shopping(), &directory).unwrap();
+        assert_eq!(pickles, Some(4));
+        assert_eq!(cart.len(), 2);
+        //the torn record was cut off, so the records written after it can be read again.
+        assert_eq!(run_file_backed(shopping(), &directory).unwrap().0, Some(6));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
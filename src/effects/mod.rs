@@ -0,0 +1,33 @@
+//! Ready-made effect [`Functor`][higher::Functor]s, together with their Free Monads, smart constructors and interpreters.
+//!
+//! Each submodule contains one effect. The Free Monads are generated by the [`free!`][crate::free] macro, so they implement
+//! the same [higher] traits (and [`MonadFree`][crate::MonadFree]) as the ones in user code, and work with [`free_do!`][crate::free_do]
+//! and the combinators in [`control`][crate::control] and [`collections`][crate::collections].
+//!
+//! The interpreters are plain functions that run a program to completion. They handle one layer at a time in a loop,
+//! so long programs don't use up the stack.
+//!
+//! ```
+//! # use higher_free_macro::free_do;
+//! use higher_free_macro::effects::state::{get, put, run_state, FreeState};
+//! fn main() {
+//!     let counter: FreeState<u32, u32> = free_do! {
+//!         count <= get();
+//!         put(count + 1);
+//!         yield count * 10
+//!     };
+//!     assert_eq!(run_state(counter, 4), (40, 5));
+//! }
+//! ```
+
+pub mod clock;
+pub mod concurrency;
+pub mod console;
+pub mod distribution;
+pub mod error;
+pub mod key_value;
+pub mod nondeterminism;
+pub mod random;
+pub mod reader;
+pub mod state;
+pub mod writer;
@@ -0,0 +1,421 @@
+//! Nondeterminism: Programs that [`choose`] between several alternatives, and [`fail`] if an alternative leads nowhere.
+//!
+//! Such a program is a search tree, with a layer for each choice and a `Pure` leaf for each result. The interpreters
+//! [`depth_first`], [`breadth_first`] and [`iterative_deepening`] walk this tree in different orders, and return the results as
+//! lazy iterators. So it is possible to only take the first few solutions, and breadth first search and iterative deepening even
+//! find solutions in infinite search trees.
+//!
+//! The free Monad of a plain [`Vec`] (like `free!(FreeVec<A>, Vec<FreeVec<A>>)`) is such a search tree too, and the interpreters
+//! accept it, because [`Vec`] implements [`Branching`]. However, `bind()` maps a [`Vec`] eagerly, so the whole tree gets built
+//! up front. [`FreeSearch`] stores its alternatives as closures instead, so only the parts of the tree that actually get visited
+//! are built.
+//!
+//! ```
+//! # use higher_free_macro::free_do;
+//! use higher_free_macro::effects::nondeterminism::{choose, depth_first, guard, FreeSearch};
+//! fn main() {
+//!     //which two-digit numbers have digits that sum to 5, with the first digit being larger?
+//!     let digits = || (0..10).collect::<Vec<u32>>();
+//!     let program: FreeSearch<u32> = free_do! {
+//!         tens <= choose(digits());
+//!         ones <= choose(digits());
+//!         guard(tens + ones == 5 && tens > ones);
+//!         yield 10 * tens + ones
+//!     };
+//!     assert_eq!(depth_first(program).collect::<Vec<_>>(), vec![32, 41, 50]);
+//! }
+//! ```
+
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use higher::Functor;
+
+use crate::MonadFree;
+
+/// A [`Functor`] layer that branches the program into several alternatives.
+pub trait Branching<T> {
+    /// An iterator over the alternatives.
+    type Branches: Iterator<Item = T>;
+
+    /// Returns the alternatives, in order.
+    fn into_branches(self) -> Self::Branches;
+}
+
+impl<T> Branching<T> for Vec<T> {
+    type Branches = std::vec::IntoIter<T>;
+
+    fn into_branches(self) -> Self::Branches {
+        self.into_iter()
+    }
+}
+
+type Thunk<'a, A> = Rc<dyn Fn() -> A + 'a>;
+
+/// The [`Functor`] of [`FreeSearch`]. Each alternative is only computed once it is needed.
+#[derive(Clone)]
+pub struct Alternatives<'a, A>(Vec<Thunk<'a, A>>);
+
+impl<'a, A: 'a> Functor<'a, A> for Alternatives<'a, A> {
+    type Target<T> = Alternatives<'a, T>;
+
+    fn fmap<B, F>(self, f: F) -> Self::Target<B>
+    where
+        F: Fn(A) -> B + 'a,
+    {
+        let f = Rc::new(f);
+        Alternatives(
+            self.0
+                .into_iter()
+                .map(|alternative| {
+                    let f = f.clone();
+                    Rc::new(move || f(alternative())) as Thunk<'a, B>
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Iterator over the [`Alternatives`], that computes each of them when it gets visited.
+pub struct AlternativesIter<'a, A>(std::vec::IntoIter<Thunk<'a, A>>);
+
+impl<A> Iterator for AlternativesIter<'_, A> {
+    type Item = A;
+
+    fn next(&mut self) -> Option<A> {
+        self.0.next().map(|alternative| alternative())
+    }
+}
+
+impl<'a, A> Branching<A> for Alternatives<'a, A> {
+    type Branches = AlternativesIter<'a, A>;
+
+    fn into_branches(self) -> Self::Branches {
+        AlternativesIter(self.0.into_iter())
+    }
+}
+
+free!(<'a>, pub FreeSearch<'a, A>, Alternatives<'a, FreeSearch<'a, A>>);
+
+/// Continues the program with each of the `options`, in order.
+#[must_use]
+pub fn choose<'a, A: Clone + 'a>(options: Vec<A>) -> FreeSearch<'a, A> {
+    FreeSearch::lift_f(Alternatives(
+        options
+            .into_iter()
+            .map(|option| Rc::new(move || option.clone()) as Thunk<'a, A>)
+            .collect(),
+    ))
+}
+
+/// Gives up on the current alternative. This is a choice between zero alternatives.
+#[must_use]
+pub fn fail<'a, A: 'a>() -> FreeSearch<'a, A> {
+    FreeSearch::wrap(Alternatives(Vec::new()))
+}
+
+/// Gives up on the current alternative, unless `condition` holds.
+#[must_use]
+pub fn guard<'a>(condition: bool) -> FreeSearch<'a, ()> {
+    if condition {
+        FreeSearch::Pure(())
+    } else {
+        fail()
+    }
+}
+
+type BranchesOf<'a, M> = <<M as MonadFree<'a>>::Functor as Branching<M>>::Branches;
+
+/// Iterator over the results of a search in depth first order. Created by [`depth_first`].
+pub struct DepthFirst<'a, M>
+where
+    M: MonadFree<'a>,
+    M::Functor: Branching<M>,
+{
+    root: Option<M>,
+    stack: Vec<BranchesOf<'a, M>>,
+    lifetime: PhantomData<&'a ()>,
+}
+
+impl<'a, M> Iterator for DepthFirst<'a, M>
+where
+    M: MonadFree<'a>,
+    M::Functor: Branching<M>,
+{
+    type Item = M::Value;
+
+    fn next(&mut self) -> Option<M::Value> {
+        if let Some(root) = self.root.take() {
+            match root.resume() {
+                Ok(value) => return Some(value),
+                Err(layer) => self.stack.push(layer.into_branches()),
+            }
+        }
+        while let Some(branches) = self.stack.last_mut() {
+            match branches.next() {
+                None => {
+                    self.stack.pop();
+                }
+                Some(node) => match node.resume() {
+                    Ok(value) => return Some(value),
+                    Err(layer) => self.stack.push(layer.into_branches()),
+                },
+            }
+        }
+        None
+    }
+}
+
+/// Returns the results of the program in depth first order: All results of the first alternative come before those of the second.
+///
+/// This uses little memory, but never returns from an infinite branch.
+pub fn depth_first<'a, M>(program: M) -> DepthFirst<'a, M>
+where
+    M: MonadFree<'a>,
+    M::Functor: Branching<M>,
+{
+    DepthFirst {
+        root: Some(program),
+        stack: Vec::new(),
+        lifetime: PhantomData,
+    }
+}
+
+/// Iterator over the results of a search in breadth first order. Created by [`breadth_first`].
+pub struct BreadthFirst<'a, M>
+where
+    M: MonadFree<'a>,
+    M::Functor: Branching<M>,
+{
+    root: Option<M>,
+    queue: VecDeque<BranchesOf<'a, M>>,
+    lifetime: PhantomData<&'a ()>,
+}
+
+impl<'a, M> Iterator for BreadthFirst<'a, M>
+where
+    M: MonadFree<'a>,
+    M::Functor: Branching<M>,
+{
+    type Item = M::Value;
+
+    fn next(&mut self) -> Option<M::Value> {
+        if let Some(root) = self.root.take() {
+            match root.resume() {
+                Ok(value) => return Some(value),
+                Err(layer) => self.queue.push_back(layer.into_branches()),
+            }
+        }
+        while let Some(branches) = self.queue.front_mut() {
+            match branches.next() {
+                None => {
+                    self.queue.pop_front();
+                }
+                Some(node) => match node.resume() {
+                    Ok(value) => return Some(value),
+                    Err(layer) => self.queue.push_back(layer.into_branches()),
+                },
+            }
+        }
+        None
+    }
+}
+
+/// Returns the results of the program in breadth first order: Results that need fewer choices come first.
+///
+/// This finds every result, even in infinite search trees, but it keeps a whole level of the tree in memory.
+pub fn breadth_first<'a, M>(program: M) -> BreadthFirst<'a, M>
+where
+    M: MonadFree<'a>,
+    M::Functor: Branching<M>,
+{
+    BreadthFirst {
+        root: Some(program),
+        queue: VecDeque::new(),
+        lifetime: PhantomData,
+    }
+}
+
+/// Iterator over the results of an iterative deepening search. Created by [`iterative_deepening`].
+pub struct IterativeDeepening<'a, M>
+where
+    M: MonadFree<'a> + Clone,
+    M::Functor: Branching<M>,
+{
+    program: M,
+    limit: usize,
+    //whether the current pass skipped nodes below the limit, so another pass is needed.
+    cut_off: bool,
+    search: DepthFirst<'a, M>,
+}
+
+impl<'a, M> Iterator for IterativeDeepening<'a, M>
+where
+    M: MonadFree<'a> + Clone,
+    M::Functor: Branching<M>,
+{
+    type Item = M::Value;
+
+    fn next(&mut self) -> Option<M::Value> {
+        loop {
+            let search = &mut self.search;
+            if let Some(root) = search.root.take() {
+                match root.resume() {
+                    Ok(value) if self.limit == 0 => return Some(value),
+                    Ok(_) => {}
+                    Err(_) if self.limit == 0 => self.cut_off = true,
+                    Err(layer) => search.stack.push(layer.into_branches()),
+                }
+            }
+            while let Some(branches) = search.stack.last_mut() {
+                match branches.next() {
+                    None => {
+                        search.stack.pop();
+                    }
+                    Some(node) => match node.resume() {
+                        //shallower results were returned by an earlier pass.
+                        Ok(value) if search.stack.len() == self.limit => return Some(value),
+                        Ok(_) => {}
+                        Err(_) if search.stack.len() == self.limit => self.cut_off = true,
+                        Err(layer) => search.stack.push(layer.into_branches()),
+                    },
+                }
+            }
+            if !self.cut_off {
+                return None;
+            }
+            self.limit += 1;
+            self.cut_off = false;
+            search.root = Some(self.program.clone());
+        }
+    }
+}
+
+/// Returns the results of the program in the same order as [`breadth_first`], but using as little memory as [`depth_first`].
+///
+/// It does so by running a depth first search that stops after one choice, then one that stops after two choices, and so on,
+/// returning the results found at the respective limit. This means that the upper levels of the tree are computed several times.
+/// The search ends once a pass reaches the bottom of every branch.
+pub fn iterative_deepening<'a, M>(program: M) -> IterativeDeepening<'a, M>
+where
+    M: MonadFree<'a> + Clone,
+    M::Functor: Branching<M>,
+{
+    IterativeDeepening {
+        search: depth_first(program.clone()),
+        program,
+        limit: 0,
+        cut_off: false,
+    }
+}
+
+#[cfg(test)]
+mod nondeterminism_tests {
+    use super::{breadth_first, choose, depth_first, fail, guard, iterative_deepening, FreeSearch};
+    use crate::free_do;
+    use higher::Bind;
+
+    free!(FreeVec<A>, Vec<FreeVec<A>>);
+
+    fn tree() -> FreeVec<u32> {
+        FreeVec::Free(Box::new(vec![
+            FreeVec::Free(Box::new(vec![
+                FreeVec::Free(Box::new(vec![FreeVec::Pure(1)])),
+                FreeVec::Pure(2),
+            ])),
+            FreeVec::Pure(3),
+            FreeVec::Free(Box::new(vec![])),
+            FreeVec::Free(Box::new(vec![FreeVec::Pure(4)])),
+        ]))
+    }
+
+    #[test]
+    fn test_orders() {
+        assert_eq!(depth_first(tree()).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        assert_eq!(breadth_first(tree()).collect::<Vec<_>>(), vec![3, 2, 4, 1]);
+        assert_eq!(
+            iterative_deepening(tree()).collect::<Vec<_>>(),
+            vec![3, 2, 4, 1]
+        );
+        assert_eq!(depth_first(FreeVec::Pure(5)).collect::<Vec<_>>(), vec![5]);
+        assert_eq!(breadth_first(FreeVec::Pure(5)).collect::<Vec<_>>(), vec![5]);
+        assert_eq!(
+            iterative_deepening(FreeVec::Pure(5)).collect::<Vec<_>>(),
+            vec![5]
+        );
+    }
+
+    fn queens<'a>(n: u32, placed: Vec<u32>) -> FreeSearch<'a, Vec<u32>> {
+        if placed.len() == n as usize {
+            return FreeSearch::Pure(placed);
+        }
+        choose((0..n).collect()).bind(move |column| {
+            let row = placed.len();
+            let safe = placed
+                .iter()
+                .enumerate()
+                .all(|(r, &c)| c != column && c.abs_diff(column) as usize != row - r);
+            let mut placed = placed.clone();
+            placed.push(column);
+            if safe {
+                queens(n, placed)
+            } else {
+                fail()
+            }
+        })
+    }
+
+    #[test]
+    fn test_queens() {
+        let solutions: Vec<_> = depth_first(queens(6, Vec::new())).collect();
+        assert_eq!(
+            solutions,
+            vec![
+                vec![1, 3, 5, 0, 2, 4],
+                vec![2, 5, 1, 4, 0, 3],
+                vec![3, 0, 4, 1, 5, 2],
+                vec![4, 2, 0, 5, 3, 1]
+            ]
+        );
+        assert_eq!(breadth_first(queens(8, Vec::new())).count(), 92);
+        assert_eq!(iterative_deepening(queens(5, Vec::new())).count(), 10);
+    }
+
+    //0, 1, 2, ... one choice deeper each.
+    fn natural<'a>(from: u32) -> FreeSearch<'a, u32> {
+        choose(vec![true, false]).bind(move |stop| {
+            if stop {
+                FreeSearch::Pure(from)
+            } else {
+                natural(from + 1)
+            }
+        })
+    }
+
+    #[test]
+    fn test_infinite_tree() {
+        let triples = || -> FreeSearch<(u32, u32, u32)> {
+            free_do! {
+                c <= natural(1);
+                a <= choose((1..c).collect());
+                b <= choose((a..c).collect());
+                guard(a * a + b * b == c * c);
+                yield (a, b, c)
+            }
+        };
+        assert_eq!(
+            breadth_first(triples()).take(3).collect::<Vec<_>>(),
+            vec![(3, 4, 5), (6, 8, 10), (5, 12, 13)]
+        );
+        assert_eq!(
+            iterative_deepening(triples()).take(3).collect::<Vec<_>>(),
+            vec![(3, 4, 5), (6, 8, 10), (5, 12, 13)]
+        );
+        //depth first search is fine as long as the infinite branch comes last.
+        assert_eq!(
+            depth_first(natural(0)).take(3).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! The Random effect: Programs that make random decisions, while staying pure data.
+//!
+//! A program only asks for random numbers. Which numbers it gets is decided by the interpreter: [`run_random`] draws them from
+//! a seedable [`SplitMix64`] generator, so the same seed always gives the same run, and [`run_replay`] takes them from a fixed
+//! list of outcomes, which is handy to test specific cases.
+//!
+//! ```
+//! # use higher_free_macro::free_do;
+//! use higher_free_macro::effects::random::{choose, random_range, run_random, run_replay, FreeRandom};
+//! use higher_free_macro::prng::SplitMix64;
+//! fn main() {
+//!     let deli_stock = || -> FreeRandom<(&str, u64)> {
+//!         free_do! {
+//!             item <= choose(vec!["sausage roll", "pickles", "cheese"]);
+//!             amount <= random_range(1..6);
+//!             yield (item, amount)
+//!         }
+//!     };
+//!     let seeded = run_random(deli_stock(), &mut SplitMix64::new(42));
+//!     assert_eq!(seeded, run_random(deli_stock(), &mut SplitMix64::new(42)));
+//!     assert_eq!(run_replay(deli_stock(), [1, 4]), Ok(("pickles", 5)));
+//! }
+//! ```
+
+use std::fmt::Display;
+use std::ops::Range;
+use std::rc::Rc;
+
+use higher::Functor;
+
+use crate::collections::traverse;
+use crate::prng::SplitMix64;
+
+/// The [`Functor`] of the Random effect. All randomness is expressed through this single command.
+#[derive(Clone)]
+pub enum Random<'a, A> {
+    /// Draws a number in the range `0..bound`, and continues with the program returned by the function for it.
+    /// The bound must not be zero, the interpreters panic if it is.
+    Below(u64, Rc<dyn Fn(u64) -> A + 'a>),
+}
+
+impl<'a, A: 'a> Functor<'a, A> for Random<'a, A> {
+    type Target<T> = Random<'a, T>;
+
+    fn fmap<B, F>(self, f: F) -> Self::Target<B>
+    where
+        F: Fn(A) -> B + 'a,
+    {
+        match self {
+            Random::Below(bound, next) => Random::Below(bound, Rc::new(move |x| f(next(x)))),
+        }
+    }
+}
+
+free!(<'a>, pub FreeRandom<'a, A>, Random<'a, FreeRandom<'a, A>>);
+
+fn below<'a>(bound: u64) -> FreeRandom<'a, u64> {
+    FreeRandom::lift_f(Random::Below(bound, Rc::new(|x| x)))
+}
+
+//usize is at most 64 bits wide on all supported platforms, so neither conversion can fail.
+fn index<'a>(len: usize) -> FreeRandom<'a, usize> {
+    below(len as u64).fmap(|index| usize::try_from(index).unwrap())
+}
+
+/// Returns a random number in `range`.
+///
+/// # Panics
+/// Panics if `range` is empty.
+#[must_use]
+pub fn random_range<'a>(range: Range<u64>) -> FreeRandom<'a, u64> {
+    assert!(
+        !range.is_empty(),
+        "random_range() called with an empty range"
+    );
+    let start = range.start;
+    below(range.end - range.start).fmap(move |offset| start + offset)
+}
+
+/// Returns one of the `options`, picked at random.
+///
+/// # Panics
+/// Panics if `options` is empty.
+#[must_use]
+pub fn choose<'a, T: Clone + 'a>(options: Vec<T>) -> FreeRandom<'a, T> {
+    assert!(!options.is_empty(), "choose() needs at least one option");
+    index(options.len()).fmap(move |index| options[index].clone())
+}
+
+/// Returns the `items` in random order.
+///
+/// This is a Fisher-Yates shuffle, so it draws one number for each item but the first.
+#[must_use]
+pub fn shuffle<'a, T: Clone + 'a>(items: Vec<T>) -> FreeRandom<'a, Vec<T>> {
+    let swaps = traverse((1..items.len()).rev(), |i| {
+        index(i + 1).fmap(move |j| (i, j))
+    });
+    swaps.fmap(move |swaps: Vec<(usize, usize)>| {
+        let mut items = items.clone();
+        for (i, j) in swaps {
+            items.swap(i, j);
+        }
+        items
+    })
+}
+
+/// Runs the program, drawing all random numbers from `rng`.
+///
+/// # Panics
+/// Panics if the program asks for a number below a bound of zero.
+pub fn run_random<'a, A: 'a>(program: FreeRandom<'a, A>, rng: &mut SplitMix64) -> A {
+    let mut program = program;
+    loop {
+        match program.resume() {
+            Ok(a) => return a,
+            Err(Random::Below(bound, next)) => {
+                assert!(
+                    bound > 0,
+                    "the program asked for a number below a bound of zero"
+                );
+                program = next(rng.below(bound));
+            }
+        }
+    }
+}
+
+/// The reasons why [`run_replay`] can fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The program asked for more random numbers than there were outcomes.
+    OutOfOutcomes,
+    /// An outcome was not below the bound the program asked for.
+    OutOfRange {
+        /// The offending outcome.
+        outcome: u64,
+        /// The bound the program asked for.
+        bound: u64,
+    },
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::OutOfOutcomes => write!(
+                f,
+                "the program asked for more random numbers than there were outcomes"
+            ),
+            ReplayError::OutOfRange { outcome, bound } => {
+                write!(
+                    f,
+                    "the outcome {outcome} is not below the requested bound {bound}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Runs the program, using the given `outcomes` as random numbers, in order.
+///
+/// Each outcome is the number drawn below the bound the program asks for, so for [`random_range`] it is the offset from
+/// the start of the range, for [`choose`] it is the index of the option, and for [`shuffle`] it is one swap index per item,
+/// starting at the last item. Outcomes that are left over are ignored.
+///
+/// # Errors
+/// Returns an error if the program asks for more numbers than there are `outcomes`, or if an outcome is out of range.
+///
+/// # Panics
+/// Panics if the program asks for a number below a bound of zero, like [`run_random`].
+pub fn run_replay<'a, A: 'a>(
+    program: FreeRandom<'a, A>,
+    outcomes: impl IntoIterator<Item = u64>,
+) -> Result<A, ReplayError> {
+    let mut outcomes = outcomes.into_iter();
+    let mut program = program;
+    loop {
+        match program.resume() {
+            Ok(a) => return Ok(a),
+            Err(Random::Below(bound, next)) => {
+                assert!(
+                    bound > 0,
+                    "the program asked for a number below a bound of zero"
+                );
+                let outcome = outcomes.next().ok_or(ReplayError::OutOfOutcomes)?;
+                if outcome >= bound {
+                    return Err(ReplayError::OutOfRange { outcome, bound });
+                }
+                program = next(outcome);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod random_tests {
+    use super::{
+        choose, random_range, run_random, run_replay, shuffle, FreeRandom, Random, ReplayError,
+    };
+    use crate::collections::replicate_m;
+    use crate::free_do;
+    use crate::prng::SplitMix64;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_seeded_is_reproducible() {
+        let dice = || replicate_m(20, random_range(1..7));
+        let first: Vec<u64> = run_random(dice(), &mut SplitMix64::new(7));
+        assert_eq!(first, run_random(dice(), &mut SplitMix64::new(7)));
+        assert!(first.iter().all(|x| (1..7).contains(x)));
+        assert_ne!(first, run_random(dice(), &mut SplitMix64::new(8)));
+    }
+
+    #[test]
+    fn test_replay() {
+        let program: FreeRandom<(u64, char)> = free_do! {
+            x <= random_range(10..20);
+            c <= choose(vec!['a', 'b', 'c']);
+            yield (x, c)
+        };
+        assert_eq!(run_replay(program.clone(), [3, 2, 99]), Ok((13, 'c')));
+        assert_eq!(
+            run_replay(program.clone(), [3]),
+            Err(ReplayError::OutOfOutcomes)
+        );
+        assert_eq!(
+            run_replay(program, [3, 3]),
+            Err(ReplayError::OutOfRange {
+                outcome: 3,
+                bound: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_shuffle() {
+        //swap 3<->0, 2<->2, 1<->0.
+        assert_eq!(
+            run_replay(shuffle(vec![1, 2, 3, 4]), [0, 2, 0]),
+            Ok(vec![2, 4, 3, 1])
+        );
+        let mut shuffled = run_random(shuffle((0..50).collect()), &mut SplitMix64::new(3));
+        assert_ne!(shuffled, (0..50).collect::<Vec<_>>());
+        shuffled.sort_unstable();
+        assert_eq!(shuffled, (0..50).collect::<Vec<_>>());
+        assert_eq!(run_replay(shuffle(vec!['x']), []), Ok(vec!['x']));
+    }
+
+    fn below_zero<'a>() -> FreeRandom<'a, u64> {
+        FreeRandom::lift_f(Random::Below(0, Rc::new(|x| x)))
+    }
+
+    //both interpreters reject a zero bound the same way, even if replaying would have an outcome for it.
+    #[test]
+    #[should_panic(expected = "bound of zero")]
+    fn test_random_zero_bound() {
+        run_random(below_zero(), &mut SplitMix64::new(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "bound of zero")]
+    fn test_replay_zero_bound() {
+        let _ = run_replay(below_zero(), [0]);
+    }
+}
@@ -0,0 +1,91 @@
+//! The Reader effect: Programs that can read a shared environment of type `R`.
+
+use std::rc::Rc;
+
+use higher::Functor;
+
+/// The [`Functor`] of the Reader effect.
+#[derive(Clone)]
+pub enum Reader<'a, R, A> {
+    /// Reads the environment, and continues with the program returned by the function.
+    Ask(Rc<dyn Fn(R) -> A + 'a>),
+}
+
+impl<'a, R: 'a, A: 'a> Functor<'a, A> for Reader<'a, R, A> {
+    type Target<T> = Reader<'a, R, T>;
+
+    fn fmap<B, F>(self, f: F) -> Self::Target<B>
+    where
+        F: Fn(A) -> B + 'a,
+    {
+        match self {
+            Reader::Ask(next) => Reader::Ask(Rc::new(move |r| f(next(r)))),
+        }
+    }
+}
+
+free!(<'a>, pub FreeReader<'a, A, R>, Reader<'a, R, FreeReader<'a, A, R>>);
+
+/// Returns the environment.
+#[must_use]
+pub fn ask<'a, R: 'a>() -> FreeReader<'a, R, R> {
+    FreeReader::lift_f(Reader::Ask(Rc::new(|r| r)))
+}
+
+/// Returns the result of applying `f` to the environment.
+pub fn asks<'a, R: 'a, T: 'a>(f: impl Fn(R) -> T + 'a) -> FreeReader<'a, T, R> {
+    FreeReader::lift_f(Reader::Ask(Rc::new(f)))
+}
+
+/// Runs `program` in an environment that is modified by `f`.
+pub fn local<'a, A: 'a, R: 'a>(
+    f: impl Fn(R) -> R + 'a,
+    program: FreeReader<'a, A, R>,
+) -> FreeReader<'a, A, R> {
+    fn local_impl<'a, A: 'a, R: 'a, F: Fn(R) -> R + 'a>(
+        f: Rc<F>,
+        program: FreeReader<'a, A, R>,
+    ) -> FreeReader<'a, A, R> {
+        match program.resume() {
+            Ok(a) => FreeReader::Pure(a),
+            Err(Reader::Ask(next)) => FreeReader::Free(Box::new(Reader::Ask(Rc::new(move |r| {
+                local_impl(f.clone(), next(f(r)))
+            })))),
+        }
+    }
+    local_impl(Rc::new(f), program)
+}
+
+/// Runs the program in the given environment, and returns its result.
+pub fn run_reader<'a, A: 'a, R: Clone + 'a>(program: FreeReader<'a, A, R>, environment: R) -> A {
+    let mut program = program;
+    loop {
+        match program.resume() {
+            Ok(a) => return a,
+            Err(Reader::Ask(next)) => program = next(environment.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod reader_tests {
+    use super::{ask, asks, local, run_reader, FreeReader};
+    use crate::free_do;
+
+    #[test]
+    fn test_ask_local() {
+        let greeting = || asks(|name: String| format!("Hello, {name}!"));
+        let program: FreeReader<Vec<String>, String> = free_do! {
+            first <= greeting();
+            second <= local(|name: String| name.to_uppercase(), greeting());
+            clone first, second;
+            name <= ask();
+            clone first, second;
+            yield vec![first, second, name]
+        };
+        assert_eq!(
+            run_reader(program, String::from("Bob")),
+            vec!["Hello, Bob!", "Hello, BOB!", "Bob"]
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! The State effect: Programs that can read and replace a value of type `S`.
+
+use std::rc::Rc;
+
+use higher::{Bind, Functor};
+
+/// The [`Functor`] of the State effect.
+#[derive(Clone)]
+pub enum State<'a, S, A> {
+    /// Reads the current state, and continues with the program returned by the function.
+    Get(Rc<dyn Fn(S) -> A + 'a>),
+    /// Replaces the current state.
+    Put(S, A),
+}
+
+impl<'a, S: 'a, A: 'a> Functor<'a, A> for State<'a, S, A> {
+    type Target<T> = State<'a, S, T>;
+
+    fn fmap<B, F>(self, f: F) -> Self::Target<B>
+    where
+        F: Fn(A) -> B + 'a,
+    {
+        match self {
+            State::Get(next) => State::Get(Rc::new(move |s| f(next(s)))),
+            State::Put(s, next) => State::Put(s, f(next)),
+        }
+    }
+}
+
+free!(<'a>, pub FreeState<'a, A, S>, State<'a, S, FreeState<'a, A, S>>);
+
+/// Returns the current state.
+#[must_use]
+pub fn get<'a, S: 'a>() -> FreeState<'a, S, S> {
+    FreeState::lift_f(State::Get(Rc::new(|s| s)))
+}
+
+/// Returns the result of applying `f` to the current state.
+pub fn gets<'a, S: 'a, T: 'a>(f: impl Fn(S) -> T + 'a) -> FreeState<'a, T, S> {
+    FreeState::lift_f(State::Get(Rc::new(f)))
+}
+
+/// Replaces the current state.
+pub fn put<'a, S: 'a>(state: S) -> FreeState<'a, (), S> {
+    FreeState::lift_f(State::Put(state, ()))
+}
+
+/// Replaces the current state by the result of applying `f` to it.
+pub fn modify<'a, S: 'a>(f: impl Fn(S) -> S + 'a) -> FreeState<'a, (), S> {
+    let f = Rc::new(f);
+    get().bind(move |s| put(f(s)))
+}
+
+/// Runs the program starting with the `initial` state. Returns the program's result and the final state.
+pub fn run_state<'a, A: 'a, S: Clone + 'a>(program: FreeState<'a, A, S>, initial: S) -> (A, S) {
+    let mut program = program;
+    let mut state = initial;
+    loop {
+        match program.resume() {
+            Ok(a) => return (a, state),
+            Err(State::Get(next)) => program = next(state.clone()),
+            Err(State::Put(s, next)) => {
+                state = s;
+                program = next;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod state_tests {
+    use super::{get, gets, modify, put, run_state, FreeState};
+    use crate::collections::for_each_m;
+    use crate::free_do;
+
+    #[test]
+    fn test_get_put() {
+        let program: FreeState<String, Vec<u32>> = free_do! {
+            v: Vec<u32> <= get();
+            put(v.iter().map(|x| x * 2).collect());
+            len <= gets(|v: Vec<u32>| v.len());
+            yield format!("{len} items")
+        };
+        assert_eq!(
+            run_state(program, vec![1, 2, 3]),
+            (String::from("3 items"), vec![2, 4, 6])
+        );
+    }
+
+    #[test]
+    fn test_modify_many() {
+        let program = for_each_m(0..100_000u64, |x| modify(move |s: u64| s + x));
+        assert_eq!(run_state(program, 0), ((), 4_999_950_000));
+    }
+}
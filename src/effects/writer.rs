@@ -0,0 +1,63 @@
+//! The Writer effect: Programs that produce output of type `W`, which is accumulated using its [`Monoid`] instance.
+
+use higher::{Functor, Monoid};
+
+/// The [`Functor`] of the Writer effect.
+#[derive(Clone)]
+pub enum Writer<W, A> {
+    /// Appends to the output.
+    Tell(W, A),
+}
+
+impl<'a, W, A> Functor<'a, A> for Writer<W, A> {
+    type Target<T> = Writer<W, T>;
+
+    fn fmap<B, F>(self, f: F) -> Self::Target<B>
+    where
+        F: Fn(A) -> B + 'a,
+    {
+        match self {
+            Writer::Tell(w, next) => Writer::Tell(w, f(next)),
+        }
+    }
+}
+
+free!(pub FreeWriter<A, W>, Writer<W, FreeWriter<A, W>>);
+
+/// Appends `output` to the output of the program.
+pub fn tell<W>(output: W) -> FreeWriter<(), W> {
+    FreeWriter::lift_f(Writer::Tell(output, ()))
+}
+
+/// Runs the program, and returns its result together with the accumulated output.
+pub fn run_writer<A, W: Monoid>(program: FreeWriter<A, W>) -> (A, W) {
+    let mut program = program;
+    let mut output = W::default();
+    loop {
+        match program.resume() {
+            Ok(a) => return (a, output),
+            Err(Writer::Tell(w, next)) => {
+                output = output.mappend(w);
+                program = next;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod writer_tests {
+    use super::{run_writer, tell, FreeWriter};
+    use crate::free_do;
+
+    #[test]
+    fn test_tell() {
+        let program: FreeWriter<u32, Vec<&str>> = free_do! {
+            tell(vec!["one"]);
+            tell(vec!["two", "three"]);
+            yield 3
+        };
+        assert_eq!(run_writer(program), (3, vec!["one", "two", "three"]));
+        let sum: FreeWriter<(), i32> = free_do! { tell(1); tell(2) };
+        assert_eq!(run_writer(sum), ((), 3));
+    }
+}
@@ -0,0 +1,333 @@
+//! Differential testing: Running two programs side by side, and checking that they do the same.
+//!
+//! When a program gets rewritten, the old and the new version should usually issue the same layers, and finish with the same
+//! result. [`compare_programs`] steps both programs in lockstep, compares their layers with an equality supplied by the
+//! caller, and feeds both the same inputs, for layers that need one. It stops at the first difference, and reports it together
+//! with the inputs fed until then.
+//!
+//! The inputs can come from a script, or be generated, for instance with [`Domain::generate`][super::fuzz::Domain::generate].
+//!
+//! ```
+//! # use higher_free_macro::free_do;
+//! use higher_free_macro::effects::console::{choose_from, print_line, Console, ConsoleInput, FreeConsole};
+//! use higher_free_macro::interpret::compare::compare_programs;
+//! fn main() {
+//!     let old: FreeConsole<()> = free_do! {
+//!         choice <= choose_from(vec!["left", "right"]);
+//!         print_line(if choice == 0 { "You go left." } else { "You go right." })
+//!     };
+//!     //the rewrite mixed up the directions.
+//!     let new: FreeConsole<()> = free_do! {
+//!         choice <= choose_from(vec!["left", "right"]);
+//!         print_line(["You go right.", "You go left."][choice])
+//!     };
+//!     let same = |left: &Console<_>, right: &Console<_>| match (left, right) {
+//!         (Console::PrintLine(a, _), Console::PrintLine(b, _)) => a == b,
+//!         (Console::ChooseFrom(a, _), Console::ChooseFrom(b, _)) => a == b,
+//!         _ => false,
+//!     };
+//!     let mut script = vec![ConsoleInput::Choice(1)].into_iter();
+//!     let divergence = compare_programs(old, new, 100, |_| script.next(), same).unwrap_err();
+//!     assert_eq!(divergence.step, 1);
+//!     assert_eq!(divergence.inputs, vec![ConsoleInput::Choice(1)]);
+//! }
+//! ```
+
+use super::journal::Feed;
+use crate::MonadFree;
+
+/// The result of [`compare_programs`], if no difference was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Agreement<A, I> {
+    /// The result both programs finished with, or `None` if the comparison stopped before, because of the step limit, or because
+    /// no more inputs were supplied.
+    pub result: Option<A>,
+    /// The number of layers compared.
+    pub steps: usize,
+    /// The inputs fed to both programs.
+    pub inputs: Vec<I>,
+}
+
+/// The first difference between two programs, found by [`compare_programs`].
+pub struct Divergence<M, I> {
+    /// The number of layers that were the same, before the difference.
+    pub step: usize,
+    /// The inputs fed to both programs, before the difference.
+    pub inputs: Vec<I>,
+    /// The rest of the first program, starting where it differs. This is either `Pure`, or the differing layer. If only the
+    /// second program needs an input there, the first one has already proceeded, and this is the rest after its layer.
+    pub left: M,
+    /// The rest of the second program, starting where it differs, the same way as `left`.
+    pub right: M,
+}
+
+type Input<'a, M> = <<M as MonadFree<'a>>::Functor as Feed<M>>::Input;
+
+/// The result of [`compare_programs`]: Either how far the programs agree, or where they differ.
+pub type Comparison<'a, M> =
+    Result<Agreement<<M as MonadFree<'a>>::Value, Input<'a, M>>, Divergence<M, Input<'a, M>>>;
+
+/// Steps `left` and `right` in lockstep, for at most `max_steps` layers, and checks that they do the same.
+///
+/// Layers are compared with `same`, and results with [`PartialEq`]. For layers that need an input, `supply` is called with the
+/// layer of `left`, and its input is fed to both programs. If `supply` returns `None`, the comparison stops.
+///
+/// The programs also differ, if only one of them needs an input for layers that are the same, or if the input does not fit
+/// one of them. Layers are consumed when they proceed or are fed, so only the layers of `left` that need an input are cloned,
+/// to be able to report them if `right` does not accept the input.
+///
+/// # Errors
+/// Returns the first [`Divergence`] between the programs.
+pub fn compare_programs<'a, M, S, E>(
+    left: M,
+    right: M,
+    max_steps: usize,
+    mut supply: S,
+    mut same: E,
+) -> Comparison<'a, M>
+where
+    M: MonadFree<'a>,
+    M::Value: PartialEq,
+    M::Functor: Feed<M> + Clone,
+    Input<'a, M>: Clone,
+    S: FnMut(&M::Functor) -> Option<Input<'a, M>>,
+    E: FnMut(&M::Functor, &M::Functor) -> bool,
+{
+    let mut inputs = Vec::new();
+    let (mut left, mut right) = (left, right);
+    for step in 0..max_steps {
+        let diverged = |inputs, left, right| {
+            Err(Divergence {
+                step,
+                inputs,
+                left,
+                right,
+            })
+        };
+        let (left_layer, right_layer) = match (left.resume(), right.resume()) {
+            (Ok(a), Ok(b)) if a == b => {
+                return Ok(Agreement {
+                    result: Some(a),
+                    steps: step,
+                    inputs,
+                })
+            }
+            (Err(l), Err(r)) if same(&l, &r) => (l, r),
+            (l, r) => {
+                let rest = |x: Result<M::Value, M::Functor>| x.map_or_else(M::wrap, M::pure);
+                return diverged(inputs, rest(l), rest(r));
+            }
+        };
+        (left, right) = match (left_layer.proceed(), right_layer.proceed()) {
+            (Ok(l), Ok(r)) => (l, r),
+            (Err(l), Err(r)) => {
+                let Some(input) = supply(&l) else {
+                    return Ok(Agreement {
+                        result: None,
+                        steps: step + 1,
+                        inputs,
+                    });
+                };
+                let original = l.clone();
+                let l = match l.feed(input.clone()) {
+                    Ok(l) => l,
+                    Err(l) => return diverged(inputs, M::wrap(l), M::wrap(r)),
+                };
+                match r.feed(input.clone()) {
+                    Ok(r) => {
+                        inputs.push(input);
+                        (l, r)
+                    }
+                    Err(r) => return diverged(inputs, M::wrap(original), M::wrap(r)),
+                }
+            }
+            (Ok(l), Err(r)) => return diverged(inputs, l, M::wrap(r)),
+            (Err(l), Ok(r)) => return diverged(inputs, M::wrap(l), r),
+        };
+    }
+    Ok(Agreement {
+        result: None,
+        steps: max_steps,
+        inputs,
+    })
+}
+
+#[cfg(test)]
+mod compare_tests {
+    use super::compare_programs;
+    use crate::collections::for_each_m;
+    use crate::control::loop_m;
+    use crate::effects::console::{
+        choose_from, print_line, read_line, Console, ConsoleInput, FreeConsole,
+    };
+    use crate::free_do;
+    use crate::interpret::fuzz::Domain;
+    use crate::prng::SplitMix64;
+    use std::ops::ControlFlow;
+
+    fn same<A>(left: &Console<'_, A>, right: &Console<'_, A>) -> bool {
+        match (left, right) {
+            (Console::PrintLine(a, _), Console::PrintLine(b, _)) => a == b,
+            (Console::ReadLine(_), Console::ReadLine(_)) => true,
+            (Console::ChooseFrom(a, _), Console::ChooseFrom(b, _)) => a == b,
+            _ => false,
+        }
+    }
+
+    fn rooms() -> Vec<&'static str> {
+        vec!["kitchen", "garden", "leave"]
+    }
+
+    //walks from room to room, and returns the number of rooms visited.
+    fn original<'a>() -> FreeConsole<'a, u32> {
+        loop_m(0, |visited: u32| {
+            free_do! {
+                room <= choose_from(rooms());
+                if room == 2 {
+                    yield ControlFlow::Break(visited)
+                } else {
+                    print_line(format!("You are in the {}.", rooms()[room]));
+                    yield ControlFlow::Continue(visited + 1)
+                }
+            }
+        })
+    }
+
+    //the same, written differently.
+    fn rewritten<'a>() -> FreeConsole<'a, u32> {
+        FreeConsole::tail_rec_m(0, |visited: u32| {
+            free_do! {
+                room <= choose_from(rooms());
+                match rooms().get(room).filter(|name| **name != "leave") {
+                    Some(name) => free_do! {
+                        print_line(format!("You are in the {name}."));
+                        yield ControlFlow::Continue(visited + 1)
+                    },
+                    None => FreeConsole::Pure(ControlFlow::Break(visited)),
+                }
+            }
+        })
+    }
+
+    //forgets to count the garden.
+    fn broken<'a>() -> FreeConsole<'a, u32> {
+        loop_m(0, |visited: u32| {
+            free_do! {
+                room <= choose_from(rooms());
+                if room == 2 {
+                    yield ControlFlow::Break(visited)
+                } else {
+                    print_line(format!("You are in the {}.", rooms()[room]));
+                    yield ControlFlow::Continue(if room == 0 { visited + 1 } else { visited })
+                }
+            }
+        })
+    }
+
+    fn script(
+        choices: &'static [usize],
+    ) -> impl FnMut(&Console<'_, FreeConsole<'_, u32>>) -> Option<ConsoleInput> {
+        let mut choices = choices.iter().map(|choice| ConsoleInput::Choice(*choice));
+        move |_| choices.next()
+    }
+
+    #[test]
+    fn test_agreement() {
+        let agreement = compare_programs(original(), rewritten(), 100, script(&[0, 1, 1, 2]), same)
+            .ok()
+            .unwrap();
+        assert_eq!(agreement.result, Some(3));
+        assert_eq!(agreement.steps, 7);
+        assert_eq!(agreement.inputs.len(), 4);
+
+        //generated inputs.
+        for seed in 0..20 {
+            let mut rng = SplitMix64::new(seed);
+            let generate =
+                |layer: &Console<'static, FreeConsole<'static, u32>>| layer.generate(&mut rng);
+            assert!(compare_programs(original(), rewritten(), 1000, generate, same).is_ok());
+        }
+
+        //the comparison stops if the inputs or the steps run out.
+        let stopped = compare_programs(original(), rewritten(), 100, script(&[0]), same)
+            .ok()
+            .unwrap();
+        assert_eq!((stopped.result, stopped.steps), (None, 3));
+        let stopped = compare_programs(original(), rewritten(), 2, script(&[0, 0]), same)
+            .ok()
+            .unwrap();
+        assert_eq!((stopped.result, stopped.steps), (None, 2));
+    }
+
+    #[test]
+    fn test_different_results() {
+        let divergence = compare_programs(original(), broken(), 100, script(&[0, 1, 2]), same)
+            .err()
+            .unwrap();
+        assert_eq!(divergence.step, 5);
+        assert_eq!(divergence.inputs.len(), 3);
+        assert_eq!(divergence.left.into_pure(), Some(2));
+        assert_eq!(divergence.right.into_pure(), Some(1));
+    }
+
+    #[test]
+    fn test_different_layers() {
+        let longer: FreeConsole<u32> = free_do! {
+            print_line("Welcome!");
+            original()
+        };
+        let divergence = compare_programs(original(), longer, 100, script(&[2]), same)
+            .err()
+            .unwrap();
+        assert_eq!(divergence.step, 0);
+        assert!(divergence.inputs.is_empty());
+        assert!(
+            matches!(divergence.left, FreeConsole::Free(layer) if matches!(*layer, Console::ChooseFrom(..)))
+        );
+
+        let shorter: FreeConsole<u32> = free_do! {
+            for_each_m(0..2, |_| free_do! {
+                room <= choose_from(rooms());
+                print_line(format!("You are in the {}.", rooms()[room]))
+            });
+            yield 2
+        };
+        let divergence = compare_programs(original(), shorter, 100, script(&[0, 1, 0]), same)
+            .err()
+            .unwrap();
+        assert_eq!(divergence.step, 4);
+        assert!(matches!(divergence.right, FreeConsole::Pure(2)));
+    }
+
+    #[test]
+    fn test_different_inputs() {
+        //an equality that does not look at the layers lets the input handling tell the programs apart.
+        let reads: FreeConsole<u32> = free_do! {
+            name <= read_line();
+            yield u32::try_from(name.len()).unwrap()
+        };
+        let divergence = compare_programs(original(), reads, 100, script(&[0]), |_, _| true)
+            .err()
+            .unwrap();
+        assert_eq!(divergence.step, 0);
+        assert!(divergence.inputs.is_empty());
+        assert!(
+            matches!(divergence.right, FreeConsole::Free(layer) if matches!(*layer, Console::ReadLine(_)))
+        );
+    }
+
+    #[test]
+    fn test_long_output() {
+        //layers are not copied at each step, which would take quadratic time for a chain of outputs.
+        let lines = || {
+            (0..10_000).rev().fold(FreeConsole::Pure(()), |next, i| {
+                FreeConsole::Free(Box::new(Console::PrintLine(i.to_string(), next)))
+            })
+        };
+        let agreement = compare_programs(lines(), lines(), 20_000, |_| None, same)
+            .ok()
+            .unwrap();
+        assert_eq!(agreement.result, Some(()));
+        assert_eq!(agreement.steps, 10_000);
+    }
+}
@@ -0,0 +1,428 @@
+//! A debugger: Running a program one layer at a time, stopping at breakpoints, and going back in time.
+//!
+//! A [`Debugger`] wraps a program and a handler. It can [`step`][Debugger::step] through the program one layer at a time,
+//! or run it until a breakpoint matches the current layer. Breakpoints are predicates on layers. Before each step, the
+//! debugger remembers the program, so it can [`rewind`][Debugger::rewind] to earlier states. Since a long running program
+//! would fill the memory that way, only a limited number of states is kept.
+//!
+//! Rewinding restores the program, but not the state of the handler. Side effects the handler had already performed stay done.
+//!
+//! Layers that implement [`DescribeLayer`] can be shown, and debugged interactively with [`repl`], a small line based front end.
+//!
+//! ```
+//! # use higher_free_macro::free_do;
+//! use higher_free_macro::effects::console::{print_line, read_line, Console, FreeConsole};
+//! use higher_free_macro::interpret::debugger::{Debugger, Stop};
+//! use higher_free_macro::render::DescribeLayer;
+//! fn main() {
+//!     let program: FreeConsole<usize> = free_do! {
+//!         print_line("Who are you?");
+//!         name <= read_line();
+//!         print_line(format!("Hello, {name}!"));
+//!         yield name.len()
+//!     };
+//!     let mut printed = Vec::new();
+//!     let handler = |layer| match layer {
+//!         Console::PrintLine(line, next) => {
+//!             printed.push(line);
+//!             next
+//!         }
+//!         Console::ReadLine(next) => next(String::from("Alice")),
+//!         Console::ChooseFrom(_, next) => next(0),
+//!     };
+//!     let mut debugger = Debugger::new(program, handler, 10);
+//!     debugger.add_breakpoint(|layer| matches!(layer, Console::ReadLine(_)));
+//!     assert_eq!(debugger.run_to_breakpoint(100), Stop::Breakpoint(0));
+//!     assert_eq!(debugger.current().unwrap_err().describe(), "read a line");
+//!     debugger.step();
+//!     debugger.rewind(1);
+//!     assert_eq!(debugger.steps(), 1);
+//!     assert_eq!(debugger.run_to_breakpoint(100), Stop::Finished);
+//!     assert_eq!(debugger.current().ok(), Some(&5));
+//!     drop(debugger);
+//!     assert_eq!(printed, vec!["Who are you?", "Hello, Alice!"]);
+//! }
+//! ```
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, BufRead, Write};
+
+use crate::render::DescribeLayer;
+use crate::MonadFree;
+
+/// Why [`Debugger::run_to_breakpoint`] stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    /// The program finished.
+    Finished,
+    /// The breakpoint with this id matches the current layer.
+    Breakpoint(usize),
+    /// The maximum number of steps was taken.
+    StepLimit,
+}
+
+type Breakpoint<'a, L> = Box<dyn Fn(&L) -> bool + 'a>;
+
+/// Runs a program step by step, with breakpoints and a bounded history. See the [module documentation][self].
+pub struct Debugger<'a, M: MonadFree<'a>, H> {
+    //`None` only while a step is taken.
+    program: Option<M>,
+    handler: H,
+    history: VecDeque<M>,
+    history_limit: usize,
+    breakpoints: BTreeMap<usize, Breakpoint<'a, M::Functor>>,
+    next_breakpoint: usize,
+    steps: usize,
+}
+
+impl<'a, M, H> Debugger<'a, M, H>
+where
+    M: MonadFree<'a> + Clone,
+    H: FnMut(M::Functor) -> M,
+{
+    /// Creates a debugger, that passes the layers of `program` to `handler`, and remembers up to `history_limit` past states.
+    pub fn new(program: M, handler: H, history_limit: usize) -> Self {
+        Debugger {
+            program: Some(program),
+            handler,
+            history: VecDeque::new(),
+            history_limit,
+            breakpoints: BTreeMap::new(),
+            next_breakpoint: 0,
+            steps: 0,
+        }
+    }
+
+    /// Returns the result of the program if it finished, otherwise the layer it continues with.
+    ///
+    /// # Errors
+    /// Returns the current layer, if the program has not finished.
+    pub fn current(&self) -> Result<&M::Value, &M::Functor> {
+        self.program().peel()
+    }
+
+    fn program(&self) -> &M {
+        self.program
+            .as_ref()
+            .expect("Debugger: the program is only missing during a step")
+    }
+
+    /// Returns the number of steps taken, minus the ones rewound.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Returns how many steps can be rewound.
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Passes the current layer to the handler. Returns false if the program had already finished.
+    pub fn step(&mut self) -> bool {
+        //a finished program is left as it is, and neither counted nor kept in the history.
+        if self.current().is_ok() {
+            return false;
+        }
+        let Some(program) = self.program.take() else {
+            return false;
+        };
+        //the program is only cloned if the history keeps it.
+        if self.history_limit > 0 {
+            if self.history.len() == self.history_limit {
+                self.history.pop_front();
+            }
+            self.history.push_back(program.clone());
+        }
+        let Err(layer) = program.resume() else {
+            unreachable!("the program was checked to not be finished")
+        };
+        self.program = Some((self.handler)(layer));
+        self.steps += 1;
+        true
+    }
+
+    /// Goes back up to `steps` steps, as far as the history reaches. Returns how many steps were rewound.
+    pub fn rewind(&mut self, steps: usize) -> usize {
+        let steps = steps.min(self.history.len());
+        for _ in 0..steps {
+            if let Some(previous) = self.history.pop_back() {
+                self.program = Some(previous);
+            }
+        }
+        self.steps -= steps;
+        steps
+    }
+
+    /// Adds a breakpoint, that stops [`run_to_breakpoint`][Self::run_to_breakpoint] at layers for which `predicate` returns true.
+    /// Returns the id of the breakpoint.
+    pub fn add_breakpoint<P>(&mut self, predicate: P) -> usize
+    where
+        P: Fn(&M::Functor) -> bool + 'a,
+    {
+        let id = self.next_breakpoint;
+        self.next_breakpoint += 1;
+        self.breakpoints.insert(id, Box::new(predicate));
+        id
+    }
+
+    /// Removes the breakpoint with the given id. Returns false if there was none.
+    pub fn remove_breakpoint(&mut self, id: usize) -> bool {
+        self.breakpoints.remove(&id).is_some()
+    }
+
+    /// Returns the ids of all breakpoints.
+    pub fn breakpoints(&self) -> Vec<usize> {
+        self.breakpoints.keys().copied().collect()
+    }
+
+    /// Takes up to `max_steps` steps, until the program finishes, or a breakpoint matches the current layer.
+    ///
+    /// The first step is always taken, so that running on from a breakpoint does not stop at the same one again.
+    pub fn run_to_breakpoint(&mut self, max_steps: usize) -> Stop {
+        for taken in 0..max_steps {
+            if let (true, Err(layer)) = (taken > 0, self.current()) {
+                if let Some((id, _)) = self.breakpoints.iter().find(|(_, matches)| matches(layer)) {
+                    return Stop::Breakpoint(*id);
+                }
+            }
+            if !self.step() {
+                return Stop::Finished;
+            }
+        }
+        match self.current() {
+            Ok(_) => Stop::Finished,
+            Err(_) => Stop::StepLimit,
+        }
+    }
+
+    /// Returns the program, in its current state.
+    pub fn into_program(self) -> M {
+        self.program.unwrap_or_else(|| unreachable!())
+    }
+}
+
+const HELP: &str = "commands:
+  step [n]        take n steps (default 1)
+  continue [n]    run until a breakpoint, or for at most n steps
+  break <text>    stop at layers whose description contains the text
+  delete <id>     remove a breakpoint
+  rewind [n]      go back n steps (default 1)
+  print           show the current layer
+  quit            leave the debugger
+commands can be shortened to their first letter.";
+
+fn show<'a, M, H, W>(debugger: &Debugger<'a, M, H>, output: &mut W) -> io::Result<()>
+where
+    M: MonadFree<'a> + Clone,
+    M::Functor: DescribeLayer<M>,
+    H: FnMut(M::Functor) -> M,
+    W: Write,
+{
+    match debugger.current() {
+        Ok(_) => writeln!(output, "{}: finished", debugger.steps()),
+        Err(layer) => writeln!(output, "{}: {}", debugger.steps(), layer.describe()),
+    }
+}
+
+/// Debugs a program interactively, reading commands from `input` and writing to `output`, until `quit` or the end of the input.
+///
+/// After each command, the current step and layer are shown. Type `help` for a list of commands.
+///
+/// # Errors
+/// Returns errors from reading `input` or writing `output`.
+pub fn repl<'a, M, H, R, W>(
+    debugger: &mut Debugger<'a, M, H>,
+    mut input: R,
+    mut output: W,
+) -> io::Result<()>
+where
+    M: MonadFree<'a> + Clone,
+    M::Functor: DescribeLayer<M>,
+    H: FnMut(M::Functor) -> M,
+    R: BufRead,
+    W: Write,
+{
+    show(debugger, &mut output)?;
+    loop {
+        write!(output, "> ")?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let line = line.trim();
+        let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+        let argument = argument.trim();
+        let count = argument.parse::<usize>().ok();
+        match command {
+            "s" | "step" => {
+                for _ in 0..count.unwrap_or(1) {
+                    if !debugger.step() {
+                        break;
+                    }
+                }
+            }
+            "c" | "continue" => {
+                if let Stop::Breakpoint(id) =
+                    debugger.run_to_breakpoint(count.unwrap_or(usize::MAX))
+                {
+                    writeln!(output, "stopped at breakpoint {id}")?;
+                }
+            }
+            "b" | "break" if argument.is_empty() => {
+                writeln!(output, "usage: break <text>")?;
+                continue;
+            }
+            "b" | "break" => {
+                let text = argument.to_owned();
+                let id = debugger.add_breakpoint(move |layer| layer.describe().contains(&text));
+                writeln!(output, "breakpoint {id}: {argument}")?;
+                continue;
+            }
+            "d" | "delete" => {
+                match count.filter(|id| debugger.remove_breakpoint(*id)) {
+                    Some(id) => writeln!(output, "deleted breakpoint {id}")?,
+                    None => writeln!(output, "no breakpoint {argument}")?,
+                }
+                continue;
+            }
+            "r" | "rewind" => {
+                let rewound = debugger.rewind(count.unwrap_or(1));
+                writeln!(output, "rewound: {rewound}")?;
+            }
+            "p" | "print" => {}
+            "q" | "quit" => return Ok(()),
+            "h" | "help" | "" => {
+                writeln!(output, "{HELP}")?;
+                continue;
+            }
+            _ => {
+                writeln!(output, "unknown command {command:?}, type help for a list")?;
+                continue;
+            }
+        }
+        show(debugger, &mut output)?;
+    }
+}
+
+#[cfg(test)]
+mod debugger_tests {
+    use super::{repl, Debugger, Stop, HELP};
+    use crate::effects::console::{choose_from, print_line, Console, FreeConsole};
+    use crate::free_do;
+    use crate::render::DescribeLayer;
+
+    fn menu<'a>() -> FreeConsole<'a, usize> {
+        free_do! {
+            print_line("Welcome!");
+            first <= choose_from(vec!["tea", "coffee"]);
+            print_line("Anything else?");
+            second <= choose_from(vec!["cake", "nothing"]);
+            yield first + second
+        }
+    }
+
+    //always picks the second option.
+    fn handler<'a>(layer: Console<'a, FreeConsole<'a, usize>>) -> FreeConsole<'a, usize> {
+        match layer {
+            Console::PrintLine(_, next) => next,
+            Console::ReadLine(next) => next(String::new()),
+            Console::ChooseFrom(_, next) => next(1),
+        }
+    }
+
+    #[test]
+    fn test_step_and_rewind() {
+        let mut debugger = Debugger::new(menu(), handler, 2);
+        assert_eq!(
+            debugger.current().err().unwrap().describe(),
+            "print \"Welcome!\""
+        );
+        assert!(debugger.step());
+        assert_eq!(
+            debugger.current().err().unwrap().describe(),
+            "choose from [\"tea\", \"coffee\"]"
+        );
+        for _ in 0..3 {
+            assert!(debugger.step());
+        }
+        assert!(!debugger.step());
+        assert_eq!(debugger.current().ok(), Some(&2));
+        assert_eq!(debugger.steps(), 4);
+
+        //only two states are kept.
+        assert_eq!(debugger.history_len(), 2);
+        assert_eq!(debugger.rewind(5), 2);
+        assert_eq!(debugger.steps(), 2);
+        assert_eq!(
+            debugger.current().err().unwrap().describe(),
+            "print \"Anything else?\""
+        );
+        assert_eq!(debugger.rewind(1), 0);
+
+        let rest = debugger.into_program();
+        assert_eq!(crate::interpret::run(rest, handler), 2);
+
+        let mut forgetful = Debugger::new(menu(), handler, 0);
+        assert!(forgetful.step());
+        assert_eq!(forgetful.steps(), 1);
+        assert_eq!(forgetful.rewind(1), 0);
+        assert_eq!(crate::interpret::run(forgetful.into_program(), handler), 2);
+
+        //stepping a finished program is not a step.
+        let mut debugger = Debugger::new(menu(), handler, 10);
+        while debugger.step() {}
+        assert!(!debugger.step());
+        assert_eq!((debugger.steps(), debugger.history_len()), (4, 4));
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut debugger = Debugger::new(menu(), handler, 10);
+        let choices = debugger.add_breakpoint(|layer| matches!(layer, Console::ChooseFrom(..)));
+        let cake = debugger.add_breakpoint(|layer| layer.describe().contains("cake"));
+        assert_eq!(debugger.breakpoints(), vec![choices, cake]);
+
+        assert_eq!(debugger.run_to_breakpoint(100), Stop::Breakpoint(choices));
+        assert_eq!(debugger.steps(), 1);
+        //running on does not stop at the same layer again. The first matching breakpoint wins.
+        assert_eq!(debugger.run_to_breakpoint(100), Stop::Breakpoint(choices));
+        assert_eq!(debugger.steps(), 3);
+        assert!(debugger.remove_breakpoint(choices));
+        assert!(!debugger.remove_breakpoint(choices));
+        debugger.rewind(1);
+        assert_eq!(debugger.run_to_breakpoint(100), Stop::Breakpoint(cake));
+
+        assert_eq!(debugger.run_to_breakpoint(0), Stop::StepLimit);
+        assert_eq!(debugger.run_to_breakpoint(100), Stop::Finished);
+        assert_eq!(debugger.run_to_breakpoint(100), Stop::Finished);
+    }
+
+    #[test]
+    fn test_repl() {
+        let commands = "help\nb\nbreak choose\ncontinue\nstep 2\nrewind\ndelete 0\nd 7\nfrobnicate\nc\np\nquit\nstep\n";
+        let mut debugger = Debugger::new(menu(), handler, 10);
+        let mut output = Vec::new();
+        repl(&mut debugger, commands.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let expected = format!(
+            "0: print \"Welcome!\"
+> {HELP}
+> usage: break <text>
+> breakpoint 0: choose
+> stopped at breakpoint 0
+1: choose from [\"tea\", \"coffee\"]
+> 3: choose from [\"cake\", \"nothing\"]
+> rewound: 1
+2: print \"Anything else?\"
+> deleted breakpoint 0
+> no breakpoint 7
+> unknown command \"frobnicate\", type help for a list
+> 4: finished
+> 4: finished
+> "
+        );
+        assert_eq!(output, expected);
+        assert_eq!(debugger.current().ok(), Some(&2));
+    }
+}
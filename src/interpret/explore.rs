@@ -0,0 +1,257 @@
+//! Exploration: Following every path through a program, to see all the ways it can end.
+//!
+//! If each input a program waits for comes from a finite set, like the options of a menu, the program is a finite tree up
+//! to any given depth. [`explore`] walks this tree, depth first, feeding every input the [`Choices`] trait lists for a
+//! layer. It reports the result of each path that finished, and the paths that were cut off by the depth limit. If no path
+//! was cut off, every run of the program ends, no matter what the inputs are.
+//!
+//! Each path is reported with the inputs that lead along it, which can be passed to [`replay`][super::journal::replay] to
+//! get back to its end, and with a trace of the layers on it, in the form returned by a function passed to [`explore`].
+//!
+//! ```
+//! # use higher_free_macro::free_do;
+//! use higher_free_macro::effects::console::{choose_from, print_line, FreeConsole};
+//! use higher_free_macro::interpret::explore::explore;
+//! fn main() {
+//!     let program: FreeConsole<&str> = free_do! {
+//!         print_line("Heads or tails?");
+//!         first <= choose_from(vec!["heads", "tails"]);
+//!         second <= choose_from(vec!["heads", "tails"]);
+//!         yield if first == second { "win" } else { "lose" }
+//!     };
+//!     let report = explore(program, 10, 100, |_| ());
+//!     let results: Vec<_> = report.finished.iter().map(|(result, _)| *result).collect();
+//!     assert_eq!(results, vec!["win", "lose", "lose", "win"]);
+//!     assert!(report.cut_off.is_empty());
+//! }
+//! ```
+
+use super::journal::Feed;
+use crate::MonadFree;
+
+/// A layer that waits for an input from a finite set.
+pub trait Choices<M>: Feed<M> {
+    /// Lists every input this layer can take. It is only called for layers that need an input.
+    fn inputs(&self) -> Vec<Self::Input>;
+}
+
+/// A path through a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path<I, T> {
+    /// The inputs that lead along this path.
+    pub inputs: Vec<I>,
+    /// What the function passed to [`explore`] returned for each layer on the path.
+    pub trace: Vec<T>,
+}
+
+impl<I, T> Path<I, T> {
+    /// Returns the number of layers on the path.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.trace.len()
+    }
+}
+
+/// The result of [`explore`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exploration<A, I, T> {
+    /// The paths that finished, together with the program's result.
+    pub finished: Vec<(A, Path<I, T>)>,
+    /// The paths that were cut off by the depth limit.
+    pub cut_off: Vec<Path<I, T>>,
+    /// The paths that ended at a layer for which [`Choices::inputs`] listed no inputs.
+    pub stuck: Vec<Path<I, T>>,
+    /// Whether all paths were walked, or the exploration stopped at the path limit.
+    pub exhaustive: bool,
+}
+
+impl<A, I, T> Exploration<A, I, T> {
+    /// Returns true if all paths were walked, and each of them finished.
+    #[must_use]
+    pub fn always_finishes(&self) -> bool {
+        self.exhaustive && self.cut_off.is_empty() && self.stuck.is_empty()
+    }
+
+    fn paths(&self) -> usize {
+        self.finished.len() + self.cut_off.len() + self.stuck.len()
+    }
+}
+
+/// Walks every path through `program`, up to `max_depth` layers deep, and stops after `max_paths` paths.
+///
+/// Paths are walked depth first, with the inputs of each layer tried in the order [`Choices::inputs`] lists them. `describe` is
+/// called for each layer on a path, and its results make up the trace of the path. Pass `|_| ()` if no traces are needed.
+///
+/// # Panics
+/// Panics if an input listed by [`Choices::inputs`] does not fit the layer.
+pub fn explore<'a, M, D, T>(
+    program: M,
+    max_depth: usize,
+    max_paths: usize,
+    mut describe: D,
+) -> Exploration<M::Value, <M::Functor as Feed<M>>::Input, T>
+where
+    M: MonadFree<'a>,
+    M::Functor: Choices<M> + Clone,
+    <M::Functor as Feed<M>>::Input: Clone,
+    D: FnMut(&M::Functor) -> T,
+    T: Clone,
+{
+    let mut report = Exploration {
+        finished: Vec::new(),
+        cut_off: Vec::new(),
+        stuck: Vec::new(),
+        exhaustive: true,
+    };
+    let mut branches = vec![(
+        program,
+        Path {
+            inputs: Vec::new(),
+            trace: Vec::new(),
+        },
+    )];
+    while let Some((mut program, mut path)) = branches.pop() {
+        if report.paths() >= max_paths {
+            report.exhaustive = false;
+            break;
+        }
+        loop {
+            let layer = match program.resume() {
+                Ok(a) => {
+                    report.finished.push((a, path));
+                    break;
+                }
+                Err(layer) => layer,
+            };
+            if path.depth() >= max_depth {
+                report.cut_off.push(path);
+                break;
+            }
+            path.trace.push(describe(&layer));
+            match layer.proceed() {
+                Ok(next) => program = next,
+                Err(layer) => {
+                    let inputs = layer.inputs();
+                    if inputs.is_empty() {
+                        report.stuck.push(path);
+                        break;
+                    }
+                    //pushed in reverse, so the first input is walked first.
+                    for input in inputs.into_iter().rev() {
+                        let next = layer.clone().feed(input.clone()).unwrap_or_else(|_| {
+                            panic!("explore(): an input listed by Choices::inputs() does not fit the layer")
+                        });
+                        let mut path = path.clone();
+                        path.inputs.push(input);
+                        branches.push((next, path));
+                    }
+                    break;
+                }
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod explore_tests {
+    use super::explore;
+    use crate::control::loop_m;
+    use crate::effects::console::{
+        choose_from, print_line, read_line, Console, ConsoleInput, FreeConsole,
+    };
+    use crate::free_do;
+    use crate::interpret::journal::replay;
+    use std::ops::ControlFlow;
+
+    fn describe<A>(layer: &Console<'_, A>) -> String {
+        match layer {
+            Console::PrintLine(line, _) => line.clone(),
+            Console::ReadLine(_) => String::from("read"),
+            Console::ChooseFrom(options, _) => options.join("/"),
+        }
+    }
+
+    //keeps asking until the player stops, and returns how often they went on.
+    fn pester<'a>() -> FreeConsole<'a, u32> {
+        loop_m(0, |count: u32| {
+            free_do! {
+                print_line("Again?");
+                choice <= choose_from(vec!["yes", "no"]);
+                yield if choice == 0 { ControlFlow::Continue(count + 1) } else { ControlFlow::Break(count) }
+            }
+        })
+    }
+
+    #[test]
+    fn test_finite_program() {
+        let program: FreeConsole<u32> = free_do! {
+            size <= choose_from(vec!["small", "large"]);
+            if size == 0 {
+                print_line("A small one.");
+                yield 1
+            } else {
+                extra <= choose_from(vec!["plain", "with pickle", "with mustard"]);
+                yield 2 + u32::try_from(extra).unwrap()
+            }
+        };
+        let report = explore(program, 10, 10, describe);
+        assert!(report.always_finishes());
+        let results: Vec<_> = report.finished.iter().map(|(result, _)| *result).collect();
+        assert_eq!(results, vec![1, 2, 3, 4]);
+        let (_, small) = &report.finished[0];
+        assert_eq!(small.inputs, vec![ConsoleInput::Choice(0)]);
+        assert_eq!(small.trace, vec!["small/large", "A small one."]);
+        let (_, mustard) = &report.finished[3];
+        assert_eq!(
+            mustard.inputs,
+            vec![ConsoleInput::Choice(1), ConsoleInput::Choice(2)]
+        );
+        assert_eq!(mustard.depth(), 2);
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        //each round is two layers, so the fourth round is cut off.
+        let report = explore(pester(), 7, 100, |_| ());
+        assert!(report.exhaustive);
+        assert!(!report.always_finishes());
+        let results: Vec<_> = report.finished.iter().map(|(result, _)| *result).collect();
+        assert_eq!(results, vec![2, 1, 0]);
+        assert_eq!(report.cut_off.len(), 1);
+        let cut_off = &report.cut_off[0];
+        assert_eq!(cut_off.depth(), 7);
+        //the inputs of a path lead back to where it ended.
+        let rest = replay(pester(), cut_off.inputs.clone()).unwrap();
+        assert!(
+            matches!(rest, FreeConsole::Free(layer) if matches!(*layer, Console::ChooseFrom(..)))
+        );
+    }
+
+    #[test]
+    fn test_path_limit() {
+        //the first path always says yes, until it hits the depth limit. Then the paths that say no on the way back finish.
+        let report = explore(pester(), 1000, 5, |_| ());
+        assert!(!report.exhaustive);
+        assert_eq!(report.cut_off.len(), 1);
+        let results: Vec<_> = report.finished.iter().map(|(result, _)| *result).collect();
+        assert_eq!(results, vec![499, 498, 497, 496]);
+    }
+
+    #[test]
+    fn test_stuck() {
+        let program: FreeConsole<String> = free_do! {
+            choice <= choose_from(vec!["guess", "pass"]);
+            if choice == 0 {
+                read_line()
+            } else {
+                yield String::new()
+            }
+        };
+        let report = explore(program, 10, 10, describe);
+        assert_eq!(report.finished.len(), 1);
+        assert_eq!(report.stuck.len(), 1);
+        assert_eq!(report.stuck[0].trace, vec!["guess/pass", "read"]);
+        assert!(!report.always_finishes());
+    }
+}
@@ -0,0 +1,176 @@
+//! Running programs with a limited budget, called fuel.
+//!
+//! Every layer the interpreter handles costs some fuel. Once the fuel would run out, the interpreter stops, and returns the
+//! rest of the program, which can be resumed later with more fuel, or dropped. This protects against scripts that loop forever.
+//!
+//! Only `Free` layers cost fuel. A program that loops forever without ever yielding a layer (for instance by recursing in
+//! [`bind()`][higher::Bind::bind] over `Pure` values) cannot be stopped this way.
+//!
+//! ```
+//! use higher_free_macro::control::forever;
+//! use higher_free_macro::effects::state::{modify, FreeState, State};
+//! use higher_free_macro::interpret::fuel::{run_with_fuel, Fuel};
+//! fn main() {
+//!     let endless: FreeState<(), u32> = forever(modify(|n| n + 1));
+//!     //each iteration is a Get and a Put layer.
+//!     let mut counter = 0;
+//!     let outcome = run_with_fuel(endless, 20, |layer| match layer {
+//!         State::Get(next) => next(counter),
+//!         State::Put(n, next) => {
+//!             counter = n;
+//!             next
+//!         }
+//!     });
+//!     assert!(matches!(outcome, Fuel::OutOfFuel(_)));
+//!     assert_eq!(counter, 10);
+//! }
+//! ```
+
+use crate::MonadFree;
+
+/// The outcome of running a program with limited fuel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fuel<A, M> {
+    /// The program finished with the given value, and this much fuel was left over.
+    Finished(A, u64),
+    /// The fuel ran out. This is the rest of the program, starting with the layer that could not be paid for.
+    OutOfFuel(M),
+}
+
+impl<A, M> Fuel<A, M> {
+    /// Returns the result of the program, if it finished.
+    pub fn finished(self) -> Option<A> {
+        match self {
+            Fuel::Finished(a, _) => Some(a),
+            Fuel::OutOfFuel(_) => None,
+        }
+    }
+
+    /// Returns the rest of the program, if it ran out of fuel.
+    pub fn out_of_fuel(self) -> Option<M> {
+        match self {
+            Fuel::Finished(..) => None,
+            Fuel::OutOfFuel(rest) => Some(rest),
+        }
+    }
+}
+
+/// Runs `program`, passing each layer to `handler`, and charging one unit of `fuel` per layer.
+pub fn run_with_fuel<'a, M, H>(program: M, fuel: u64, handler: H) -> Fuel<M::Value, M>
+where
+    M: MonadFree<'a>,
+    H: FnMut(M::Functor) -> M,
+{
+    run_with_fuel_cost(program, fuel, |_| 1, handler)
+}
+
+/// Runs `program`, passing each layer to `handler`, and charging the fuel `cost` returns for the layer, before it is handled.
+///
+/// If a layer costs more fuel than is left, the program stops before that layer, and the left over fuel is lost.
+pub fn run_with_fuel_cost<'a, M, C, H>(
+    program: M,
+    fuel: u64,
+    mut cost: C,
+    mut handler: H,
+) -> Fuel<M::Value, M>
+where
+    M: MonadFree<'a>,
+    C: FnMut(&M::Functor) -> u64,
+    H: FnMut(M::Functor) -> M,
+{
+    let mut fuel = fuel;
+    let mut program = program;
+    loop {
+        match program.resume() {
+            Ok(a) => return Fuel::Finished(a, fuel),
+            Err(layer) => {
+                let price = cost(&layer);
+                if price > fuel {
+                    return Fuel::OutOfFuel(M::wrap(layer));
+                }
+                fuel -= price;
+                program = handler(layer);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod fuel_tests {
+    use super::{run_with_fuel, run_with_fuel_cost, Fuel};
+    use crate::control::loop_m;
+    use crate::free_do;
+    use crate::test_support::{read, write, FreeIo, Io};
+    use std::ops::ControlFlow;
+
+    //reads numbers, and writes their running sum, until the sum exceeds `limit`.
+    fn summer<'a>(limit: u32) -> FreeIo<'a, u32> {
+        loop_m(0, move |sum: u32| {
+            free_do! {
+                x <= read();
+                write(sum + x);
+                yield if sum + x > limit { ControlFlow::Break(sum + x) } else { ControlFlow::Continue(sum + x) }
+            }
+        })
+    }
+
+    //feeds 10 to every read, and records the writes.
+    fn handler<'a, 'w>(
+        written: &'w mut Vec<u32>,
+    ) -> impl FnMut(Io<'a, FreeIo<'a, u32>>) -> FreeIo<'a, u32> + 'w {
+        move |layer| match layer {
+            Io::Read(next) => next(10),
+            Io::Write(x, next) => {
+                written.push(x);
+                next
+            }
+        }
+    }
+
+    #[test]
+    fn test_finishes() {
+        let mut written = Vec::new();
+        let outcome = run_with_fuel(summer(25), 10, handler(&mut written));
+        assert!(matches!(outcome, Fuel::Finished(30, 4)));
+        assert_eq!(written, vec![10, 20, 30]);
+    }
+
+    #[test]
+    fn test_resume() {
+        let mut written = Vec::new();
+        let mut program = summer(1000);
+        let mut rounds = 0;
+        let result = loop {
+            rounds += 1;
+            match run_with_fuel(program, 7, handler(&mut written)) {
+                Fuel::Finished(result, _) => break result,
+                Fuel::OutOfFuel(rest) => program = rest,
+            }
+        };
+        assert_eq!(result, 1010);
+        assert_eq!(written.len(), 101);
+        //202 layers, 7 per round.
+        assert_eq!(rounds, 29);
+    }
+
+    #[test]
+    fn test_cost() {
+        let mut written = Vec::new();
+        let cost = |layer: &Io<_>| match layer {
+            Io::Read(_) => 1,
+            Io::Write(..) => 5,
+        };
+        //read, write, read, and the second write is too expensive.
+        let rest = run_with_fuel_cost(summer(1000), 10, cost, handler(&mut written))
+            .out_of_fuel()
+            .unwrap();
+        assert_eq!(written, vec![10]);
+        assert!(matches!(rest.clone(), FreeIo::Free(layer) if matches!(*layer, Io::Write(20, _))));
+
+        let finished = run_with_fuel_cost(rest, 1000, cost, handler(&mut written));
+        assert_eq!(finished.finished(), Some(1010));
+        assert!(run_with_fuel(write(1), 0, |_| unreachable!())
+            .finished()
+            .is_none());
+    }
+}
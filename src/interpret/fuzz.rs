@@ -0,0 +1,403 @@
+//! Fuzzing: Running a program many times with random inputs, and checking that nothing goes wrong.
+//!
+//! Where [exploring][super::explore] every path is too expensive, [`fuzz`] walks random paths instead. Each input is drawn
+//! from a seeded [`SplitMix64`] generator by the layer waiting for it, as described by the [`Domain`] trait. After each step,
+//! and at the end of each run, user supplied [`Invariants`] are checked.
+//!
+//! Once an invariant breaks, the inputs of that run are shrunk: Parts of them are removed, and single inputs are replaced by
+//! simpler ones, as long as an invariant still breaks. The result is a [`Failure`], which can be printed as a script with one
+//! input per line. Since the program is a pure function of its inputs, the script reproduces the failure, for instance by
+//! passing the inputs to [`replay`][super::journal::replay].
+//!
+//! ```
+//! # use higher_free_macro::free_do;
+//! use higher_free_macro::effects::console::{choose_from, run_scripted, Console, FreeConsole};
+//! use higher_free_macro::interpret::fuzz::{fuzz, Invariants};
+//! use higher_free_macro::prng::SplitMix64;
+//! fn main() {
+//!     //picks two numbers, and should never come up with 13.
+//!     let program: FreeConsole<usize> = free_do! {
+//!         a <= choose_from((0..10).map(|n| n.to_string()).collect());
+//!         b <= choose_from((0..10).map(|n| n.to_string()).collect());
+//!         yield a + b
+//!     };
+//!     struct NotThirteen;
+//!     impl<L> Invariants<L, usize> for NotThirteen {
+//!         fn check_result(&mut self, sum: &usize) -> Result<(), String> {
+//!             if *sum == 13 { Err(String::from("unlucky")) } else { Ok(()) }
+//!         }
+//!     }
+//!     let failure = fuzz(&program, 1000, 10, &mut SplitMix64::new(1), &mut NotThirteen).unwrap_err();
+//!     assert_eq!(failure.message, "unlucky");
+//!     //the script is what a user would type, so it can be run with the Console interpreters.
+//!     let transcript = run_scripted(program, failure.script().lines());
+//!     assert_eq!(transcript.result.unwrap(), 13);
+//! }
+//! ```
+
+use std::fmt::{Debug, Display};
+
+use super::journal::Feed;
+use crate::prng::SplitMix64;
+use crate::MonadFree;
+
+/// A layer that can draw random inputs for itself.
+pub trait Domain<M>: Feed<M> {
+    /// Returns a random input that fits this layer, or `None` if there is none. It is only called for layers that need an input.
+    fn generate(&self, rng: &mut SplitMix64) -> Option<Self::Input>;
+
+    /// Returns simpler variants of `input`, to try while shrinking. Repeatedly simplifying must end eventually.
+    ///
+    /// By default, inputs are not simplified, but only removed while shrinking.
+    fn simplify(_input: &Self::Input) -> Vec<Self::Input> {
+        Vec::new()
+    }
+}
+
+/// Checks that are run for each layer, and for the result of each run. A broken check returns a message.
+///
+/// All checks pass by default, so only the needed ones have to be implemented.
+pub trait Invariants<L, A> {
+    /// Called before each run, including the ones while shrinking. Checks that keep track of the run should reset here.
+    fn restart(&mut self) {}
+
+    /// Checks a layer, when the run reaches it.
+    ///
+    /// # Errors
+    /// Returns a message, if the check fails.
+    fn check_layer(&mut self, _layer: &L) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Checks the result of a run, that finished.
+    ///
+    /// # Errors
+    /// Returns a message, if the check fails.
+    fn check_result(&mut self, _result: &A) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// A broken invariant, found by [`fuzz`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure<I> {
+    /// The message of the broken check.
+    pub message: String,
+    /// The shrunk inputs, that make the check fail.
+    pub inputs: Vec<I>,
+    /// The number of the run that found the failure, counting from 0.
+    pub run: usize,
+    /// The number of inputs, before they were shrunk.
+    pub original_length: usize,
+}
+
+impl<I: Display> Failure<I> {
+    /// Returns the inputs as a script, with one input per line.
+    #[must_use]
+    pub fn script(&self) -> String {
+        let mut script = String::new();
+        for input in &self.inputs {
+            script.push_str(&input.to_string());
+            script.push('\n');
+        }
+        script
+    }
+}
+
+impl<I: Display> Display for Failure<I> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "run {} broke an invariant: {}", self.run, self.message)?;
+        writeln!(
+            f,
+            "shrunk from {} to {} inputs, which are:",
+            self.original_length,
+            self.inputs.len()
+        )?;
+        write!(f, "{}", self.script())
+    }
+}
+
+impl<I: Debug + Display> std::error::Error for Failure<I> {}
+
+type Input<'a, M> = <<M as MonadFree<'a>>::Functor as Feed<M>>::Input;
+
+//runs the program for at most `max_steps` layers, taking inputs from `supply`. If a check fails, returns its message and the
+//inputs used until then. Running out of inputs or steps ends the run without failure.
+fn check_run<'a, M, S, V>(
+    program: M,
+    max_steps: usize,
+    mut supply: S,
+    invariants: &mut V,
+) -> Option<(String, Vec<Input<'a, M>>)>
+where
+    M: MonadFree<'a>,
+    M::Functor: Feed<M>,
+    Input<'a, M>: Clone,
+    S: FnMut(&M::Functor) -> Option<Input<'a, M>>,
+    V: Invariants<M::Functor, M::Value>,
+{
+    invariants.restart();
+    let mut used = Vec::new();
+    let mut program = program;
+    for _ in 0..max_steps {
+        let layer = match program.resume() {
+            Ok(a) => return invariants.check_result(&a).err().map(|m| (m, used)),
+            Err(layer) => layer,
+        };
+        if let Err(message) = invariants.check_layer(&layer) {
+            return Some((message, used));
+        }
+        program = match layer.proceed() {
+            Ok(next) => next,
+            Err(layer) => {
+                let input = supply(&layer)?;
+                used.push(input.clone());
+                layer.feed(input).ok()?
+            }
+        };
+    }
+    None
+}
+
+//all ways to remove a chunk of inputs, big chunks first.
+fn removals<I: Clone>(inputs: &[I]) -> impl Iterator<Item = Vec<I>> + '_ {
+    std::iter::successors(Some(inputs.len()), |chunk| Some(chunk / 2))
+        .take_while(|chunk| *chunk > 0)
+        .flat_map(move |chunk| {
+            (0..inputs.len()).step_by(chunk).map(move |start| {
+                let mut candidate = inputs[..start].to_vec();
+                candidate.extend_from_slice(&inputs[(start + chunk).min(inputs.len())..]);
+                candidate
+            })
+        })
+}
+
+//all ways to simplify a single input.
+fn simplifications<M, L>(inputs: &[L::Input]) -> impl Iterator<Item = Vec<L::Input>> + '_
+where
+    L: Domain<M>,
+    L::Input: Clone,
+{
+    (0..inputs.len()).flat_map(move |i| {
+        L::simplify(&inputs[i]).into_iter().map(move |simpler| {
+            let mut candidate = inputs.to_vec();
+            candidate[i] = simpler;
+            candidate
+        })
+    })
+}
+
+/// Runs `program` up to `runs` times, for at most `max_steps` layers each, with random inputs drawn from `rng`, and checks the
+/// `invariants` along the way.
+///
+/// Runs that take too many steps, or reach a layer for which [`Domain::generate`] returns `None`, end without failure.
+///
+/// # Errors
+/// Returns the first broken invariant, with the inputs of its run shrunk. While shrinking, any broken invariant counts, so the
+/// message can differ from the one of the original run.
+pub fn fuzz<'a, M, V>(
+    program: &M,
+    runs: usize,
+    max_steps: usize,
+    rng: &mut SplitMix64,
+    invariants: &mut V,
+) -> Result<(), Failure<Input<'a, M>>>
+where
+    M: MonadFree<'a> + Clone,
+    M::Functor: Domain<M>,
+    Input<'a, M>: Clone,
+    V: Invariants<M::Functor, M::Value>,
+{
+    for run in 0..runs {
+        let generate = |layer: &M::Functor| layer.generate(rng);
+        if let Some((message, inputs)) = check_run(program.clone(), max_steps, generate, invariants)
+        {
+            let original_length = inputs.len();
+            let (message, inputs) = shrink(program, max_steps, (message, inputs), invariants);
+            return Err(Failure {
+                message,
+                inputs,
+                run,
+                original_length,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn shrink<'a, M, V>(
+    program: &M,
+    max_steps: usize,
+    failure: (String, Vec<Input<'a, M>>),
+    invariants: &mut V,
+) -> (String, Vec<Input<'a, M>>)
+where
+    M: MonadFree<'a> + Clone,
+    M::Functor: Domain<M>,
+    Input<'a, M>: Clone,
+    V: Invariants<M::Functor, M::Value>,
+{
+    let mut best = failure;
+    loop {
+        let mut reproduce = |candidate: Vec<Input<'a, M>>| {
+            let mut candidate = candidate.into_iter();
+            check_run(program.clone(), max_steps, |_| candidate.next(), invariants)
+        };
+        let simpler = removals(&best.1)
+            .chain(simplifications::<M, M::Functor>(&best.1))
+            .find_map(&mut reproduce);
+        match simpler {
+            Some(simpler) => best = simpler,
+            None => return best,
+        }
+    }
+}
+
+#[cfg(test)]
+mod fuzz_tests {
+    use super::{fuzz, Domain, Invariants};
+    use crate::collections::replicate_m;
+    use crate::control::loop_m;
+    use crate::effects::console::{
+        choose_from, print_line, read_line, run_scripted, Console, ConsoleInput, FreeConsole,
+    };
+    use crate::free_do;
+    use crate::interpret::journal::replay;
+    use crate::prng::SplitMix64;
+    use std::ops::ControlFlow;
+
+    //a keypad. Typing 4 and then 2 opens the door, which is the bug the fuzzer should find. Returns the number of key presses.
+    fn keypad<'a>() -> FreeConsole<'a, u32> {
+        loop_m((0, 0), |(last, presses): (usize, u32)| {
+            free_do! {
+                key <= choose_from((0..10).map(|n| n.to_string()).chain(Some(String::from("leave"))).collect());
+                if key == 10 {
+                    yield ControlFlow::Break(presses)
+                } else {
+                    print_line(if last == 4 && key == 2 { "OPEN" } else { "beep" });
+                    yield ControlFlow::Continue((key, presses + 1))
+                }
+            }
+        })
+    }
+
+    //the door stays closed, and nobody presses more than 20 keys.
+    #[derive(Default)]
+    struct Closed {
+        checked_layers: usize,
+        restarts: usize,
+    }
+
+    impl<'a> Invariants<Console<'a, FreeConsole<'a, u32>>, u32> for Closed {
+        fn restart(&mut self) {
+            self.restarts += 1;
+        }
+
+        fn check_layer(&mut self, layer: &Console<'a, FreeConsole<'a, u32>>) -> Result<(), String> {
+            self.checked_layers += 1;
+            match layer {
+                Console::PrintLine(line, _) if line == "OPEN" => {
+                    Err(String::from("the door opened"))
+                }
+                _ => Ok(()),
+            }
+        }
+
+        fn check_result(&mut self, presses: &u32) -> Result<(), String> {
+            if *presses > 20 {
+                Err(format!("{presses} key presses"))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn test_finds_and_shrinks() {
+        let mut closed = Closed::default();
+        let failure = fuzz(&keypad(), 1000, 100, &mut SplitMix64::new(5), &mut closed).unwrap_err();
+        assert_eq!(failure.message, "the door opened");
+        assert_eq!(
+            failure.inputs,
+            vec![ConsoleInput::Choice(4), ConsoleInput::Choice(2)]
+        );
+        assert!(failure.original_length >= 2);
+        assert!(closed.restarts > failure.run + 1);
+        assert_eq!(failure.script(), "5\n3\n");
+        assert!(failure.to_string().ends_with("inputs, which are:\n5\n3\n"));
+
+        //the script reproduces the failure.
+        let transcript = run_scripted(keypad(), failure.script().lines());
+        assert_eq!(
+            transcript
+                .output
+                .iter()
+                .filter(|line| *line == "OPEN")
+                .count(),
+            1
+        );
+        assert!(replay(keypad(), failure.inputs).is_ok());
+
+        //the same seed finds the same failure.
+        let again = fuzz(
+            &keypad(),
+            1000,
+            100,
+            &mut SplitMix64::new(5),
+            &mut Closed::default(),
+        );
+        assert_eq!(again.unwrap_err().run, failure.run);
+    }
+
+    #[test]
+    fn test_result_invariant() {
+        //typing only zeros never opens the door, but too many key presses break the other invariant.
+        let zeros: FreeConsole<u32> = free_do! {
+            zeros <= replicate_m(25, choose_from(vec!["0"]));
+            yield u32::try_from(zeros.len()).unwrap()
+        };
+        let failure = fuzz(
+            &zeros,
+            1,
+            100,
+            &mut SplitMix64::new(1),
+            &mut Closed::default(),
+        )
+        .unwrap_err();
+        assert_eq!(failure.message, "25 key presses");
+        assert_eq!(failure.inputs.len(), 25);
+    }
+
+    #[test]
+    fn test_passes() {
+        //runs that are too long end without failure.
+        let mut closed = Closed::default();
+        assert!(fuzz(&keypad(), 10, 3, &mut SplitMix64::new(5), &mut closed).is_ok());
+        assert!(closed.checked_layers <= 30);
+        //programs that read lines get random words.
+        let echo: FreeConsole<u32> = free_do! {
+            word <= read_line();
+            yield u32::try_from(word.len()).unwrap()
+        };
+        let mut rng = SplitMix64::new(2);
+        let failure = fuzz(&echo, 100, 10, &mut rng, &mut Closed::default());
+        assert!(failure.is_ok());
+    }
+
+    #[test]
+    fn test_simplify() {
+        let simplify = <Console<'_, FreeConsole<'_, ()>> as Domain<FreeConsole<'_, ()>>>::simplify;
+        assert_eq!(
+            simplify(&ConsoleInput::Line(String::from("pickle"))),
+            vec![
+                ConsoleInput::Line(String::new()),
+                ConsoleInput::Line(String::from("pic"))
+            ]
+        );
+        assert_eq!(
+            simplify(&ConsoleInput::Choice(1)),
+            vec![ConsoleInput::Choice(0)]
+        );
+        assert!(simplify(&ConsoleInput::Choice(0)).is_empty());
+    }
+}
@@ -0,0 +1,294 @@
+//! Interceptors: Observing every layer a program runs, without changing the interpreter.
+//!
+//! An [`Interceptor`] gets called before and after a handler deals with a layer. [`intercept`] wraps a handler, so that it
+//! calls the interceptor, and returns a new handler, which can be wrapped again. That way interceptors stack: The outermost
+//! one sees each layer first, and its `after` hook runs last.
+//!
+//! ```
+//! use higher_free_macro::effects::state::{modify, FreeState, State};
+//! use higher_free_macro::interpret::intercept::{intercept, InstructionCounter, Logger};
+//! use higher_free_macro::interpret::run;
+//! fn main() {
+//!     let name = |layer: &State<'_, u32, _>| match layer {
+//!         State::Get(_) => "get",
+//!         State::Put(..) => "put",
+//!     };
+//!     let mut counter = InstructionCounter::new(name);
+//!     let mut logger = Logger::new(Vec::new(), |layer: &State<'_, u32, _>| name(layer).to_owned());
+//!
+//!     let program: FreeState<(), u32> = modify(|x| x + 1);
+//!     let mut state = 1;
+//!     let handler = |layer| match layer {
+//!         State::Get(next) => next(state),
+//!         State::Put(s, next) => {
+//!             state = s;
+//!             next
+//!         }
+//!     };
+//!     run(program, intercept(intercept(handler, &mut counter), &mut logger));
+//!
+//!     assert_eq!(state, 2);
+//!     assert_eq!(counter.counts()["put"], 1);
+//!     let log = String::from_utf8(logger.finish().unwrap()).unwrap();
+//!     assert!(log.starts_with("1: get ("));
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// Hooks that get called around each layer a handler deals with.
+///
+/// The handler consumes the layer, so `after` cannot look at it anymore. Instead, it gets the note that `before` took about it.
+pub trait Interceptor<L> {
+    /// What [`before`][Interceptor::before] remembers about a layer for [`after`][Interceptor::after].
+    type Note;
+
+    /// Called before the handler gets `layer`.
+    fn before(&mut self, layer: &L) -> Self::Note;
+
+    /// Called after the handler is done with the layer `note` was taken about. `elapsed` is the time the inner handler took,
+    /// including the interceptors wrapped inside this one, but not the ones wrapped around it.
+    fn after(&mut self, note: Self::Note, elapsed: Duration);
+}
+
+impl<L, I: Interceptor<L>> Interceptor<L> for &mut I {
+    type Note = I::Note;
+
+    fn before(&mut self, layer: &L) -> Self::Note {
+        (**self).before(layer)
+    }
+
+    fn after(&mut self, note: Self::Note, elapsed: Duration) {
+        (**self).after(note, elapsed);
+    }
+}
+
+/// Wraps `handler`, so that `interceptor` is called around every layer it handles.
+///
+/// Pass a mutable reference as `interceptor` to look at it after the program has run.
+pub fn intercept<L, M, H, I>(mut handler: H, mut interceptor: I) -> impl FnMut(L) -> M
+where
+    H: FnMut(L) -> M,
+    I: Interceptor<L>,
+{
+    move |layer| {
+        let note = interceptor.before(&layer);
+        let start = Instant::now();
+        let next = handler(layer);
+        interceptor.after(note, start.elapsed());
+        next
+    }
+}
+
+/// Counts how often each kind of layer was handled, and how long handling it took in total.
+///
+/// The kind of a layer is determined by a function, usually returning the name of the variant.
+pub struct InstructionCounter<K, C> {
+    kind: C,
+    counts: BTreeMap<K, usize>,
+    durations: BTreeMap<K, Duration>,
+}
+
+impl<K: Ord, C> InstructionCounter<K, C> {
+    /// Creates a counter, that uses `kind` to tell layers apart.
+    pub fn new(kind: C) -> Self {
+        InstructionCounter {
+            kind,
+            counts: BTreeMap::new(),
+            durations: BTreeMap::new(),
+        }
+    }
+
+    /// Returns how often each kind of layer was handled.
+    pub fn counts(&self) -> &BTreeMap<K, usize> {
+        &self.counts
+    }
+
+    /// Returns how long handling each kind of layer took in total.
+    pub fn durations(&self) -> &BTreeMap<K, Duration> {
+        &self.durations
+    }
+
+    /// Returns how many layers were handled in total.
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+}
+
+impl<L, K: Ord + Clone, C: Fn(&L) -> K> Interceptor<L> for InstructionCounter<K, C> {
+    type Note = K;
+
+    fn before(&mut self, layer: &L) -> K {
+        let kind = (self.kind)(layer);
+        *self.counts.entry(kind.clone()).or_insert(0) += 1;
+        kind
+    }
+
+    fn after(&mut self, kind: K, elapsed: Duration) {
+        *self.durations.entry(kind).or_insert(Duration::ZERO) += elapsed;
+    }
+}
+
+/// Writes a line for each handled layer, with its number, its description, and the time it took, like `3: get (1.2µs)`.
+///
+/// Logging must not disturb the program, so if writing fails, the logger stops writing, and reports the error in [`finish`][Logger::finish].
+pub struct Logger<W, D> {
+    output: W,
+    describe: D,
+    steps: usize,
+    error: Option<io::Error>,
+}
+
+impl<W: Write, D> Logger<W, D> {
+    /// Creates a logger, that writes to `output`, and uses `describe` to turn layers into text.
+    pub fn new(output: W, describe: D) -> Self {
+        Logger {
+            output,
+            describe,
+            steps: 0,
+            error: None,
+        }
+    }
+
+    /// Flushes the output and returns it.
+    ///
+    /// # Errors
+    /// Returns the first error that happened while writing the log.
+    pub fn finish(mut self) -> io::Result<W> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => self.output.flush().map(|()| self.output),
+        }
+    }
+}
+
+impl<L, W: Write, D: Fn(&L) -> String> Interceptor<L> for Logger<W, D> {
+    type Note = (usize, String);
+
+    fn before(&mut self, layer: &L) -> Self::Note {
+        self.steps += 1;
+        (self.steps, (self.describe)(layer))
+    }
+
+    fn after(&mut self, (step, description): Self::Note, elapsed: Duration) {
+        if self.error.is_none() {
+            if let Err(error) = writeln!(self.output, "{step}: {description} ({elapsed:?})") {
+                self.error = Some(error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod intercept_tests {
+    use super::{intercept, InstructionCounter, Interceptor, Logger};
+    use crate::collections::for_each_m;
+    use crate::effects::state::{get, put, FreeState, State};
+    use crate::free_do;
+    use crate::interpret::fuel::run_with_fuel;
+    use crate::interpret::run;
+    use std::time::Duration;
+
+    fn kind<S, A>(layer: &State<'_, S, A>) -> &'static str {
+        match layer {
+            State::Get(_) => "get",
+            State::Put(..) => "put",
+        }
+    }
+
+    fn doubler<'a>() -> FreeState<'a, u32, u32> {
+        free_do! {
+            for_each_m(0..3, |_| free_do! {
+                x <= get();
+                put(x * 2)
+            });
+            get()
+        }
+    }
+
+    fn handler<'a>(
+        state: &mut u32,
+    ) -> impl FnMut(State<'a, u32, FreeState<'a, u32, u32>>) -> FreeState<'a, u32, u32> + '_ {
+        move |layer| match layer {
+            State::Get(next) => next(*state),
+            State::Put(s, next) => {
+                *state = s;
+                next
+            }
+        }
+    }
+
+    //records the order in which hooks are called.
+    struct Tracer<'t> {
+        name: &'static str,
+        trace: &'t std::cell::RefCell<Vec<String>>,
+    }
+
+    impl<L> Interceptor<L> for Tracer<'_> {
+        type Note = ();
+
+        fn before(&mut self, _: &L) {
+            self.trace
+                .borrow_mut()
+                .push(format!("{} before", self.name));
+        }
+
+        fn after(&mut self, (): (), _: Duration) {
+            self.trace.borrow_mut().push(format!("{} after", self.name));
+        }
+    }
+
+    #[test]
+    fn test_counter() {
+        let mut state = 1;
+        let mut counter = InstructionCounter::new(kind);
+        let result = run(doubler(), intercept(handler(&mut state), &mut counter));
+        assert_eq!(result, 8);
+        assert_eq!(counter.counts()["get"], 4);
+        assert_eq!(counter.counts()["put"], 3);
+        assert_eq!(counter.total(), 7);
+        assert_eq!(counter.durations().len(), 2);
+    }
+
+    #[test]
+    fn test_logger() {
+        let mut state = 5;
+        let mut logger = Logger::new(Vec::new(), |layer: &State<'_, u32, _>| {
+            String::from(kind(layer))
+        });
+        run(doubler(), intercept(handler(&mut state), &mut logger));
+        let log = String::from_utf8(logger.finish().unwrap()).unwrap();
+        let lines: Vec<_> = log.lines().collect();
+        assert_eq!(lines.len(), 7);
+        assert!(lines[0].starts_with("1: get ("));
+        assert!(lines[5].starts_with("6: put ("));
+    }
+
+    #[test]
+    fn test_stacking() {
+        let trace = std::cell::RefCell::new(Vec::new());
+        let inner = Tracer {
+            name: "inner",
+            trace: &trace,
+        };
+        let outer = Tracer {
+            name: "outer",
+            trace: &trace,
+        };
+        let mut state = 0;
+        let mut counter = InstructionCounter::new(kind);
+        //interceptors also work with other interpreters, here one with limited fuel.
+        let handler = intercept(
+            intercept(intercept(handler(&mut state), inner), outer),
+            &mut counter,
+        );
+        assert!(run_with_fuel(doubler(), 1, handler).finished().is_none());
+        assert_eq!(
+            trace.into_inner(),
+            vec!["outer before", "inner before", "inner after", "outer after"]
+        );
+        assert_eq!(counter.total(), 1);
+    }
+}
@@ -0,0 +1,381 @@
+//! Journals: Recording the inputs a program got, and replaying them to get back to where it was.
+//!
+//! Continuations are closures, so a running program cannot be saved. The inputs fed into the continuations usually can be,
+//! and the program is a pure function of them. So a journal of all inputs, together with the initial program, is enough to
+//! rebuild the program's state, for instance to load a save game or to recover after a crash.
+//!
+//! This needs to know which layers take an input, and how to feed it, which is what the [`Feed`] trait describes.
+//! [`record`] runs a program with a function that supplies inputs, and passes each of them on to a sink that records the
+//! journal. [`replay`] feeds the journal to a fresh copy of the program, without running any side effects, and returns the
+//! program at the point where the journal ends.
+//!
+//! The [Console][crate::effects::console] effect implements [`Feed`], for instance:
+//! ```
+//! # use higher_free_macro::free_do;
+//! use higher_free_macro::effects::console::{print_line, read_line, Console, ConsoleInput, FreeConsole};
+//! use higher_free_macro::interpret::journal::{record, replay};
+//! fn main() {
+//!     let program = || -> FreeConsole<String> {
+//!         free_do! {
+//!             print_line("Who are you?");
+//!             name <= read_line();
+//!             print_line(format!("Welcome, {name}!"));
+//!             read_line()
+//!         }
+//!     };
+//!     let mut journal = Vec::new();
+//!     let supply = |layer: &Console<_>| match layer {
+//!         Console::PrintLine(line, _) => {
+//!             println!("{line}");
+//!             None
+//!         }
+//!         _ => Some(ConsoleInput::Line(String::from("Bob"))),
+//!     };
+//!     let result = record(program(), supply, |input| journal.push(input.clone()));
+//!     assert_eq!(result.ok(), Some(String::from("Bob")));
+//!     assert_eq!(journal.len(), 2);
+//!
+//!     //the first input is enough to get back to the second question, without printing anything.
+//!     let restored = replay(program(), journal.into_iter().take(1)).unwrap();
+//!     assert!(matches!(restored, FreeConsole::Free(layer) if matches!(*layer, Console::ReadLine(_))));
+//! }
+//! ```
+
+use std::fmt::Display;
+
+use crate::MonadFree;
+
+/// A [`Functor`][higher::Functor] layer, that might need an input to continue with the rest of the program `M`.
+pub trait Feed<M>: Sized {
+    /// The values fed into continuations. If different layers need different kinds of input, this is usually an enum.
+    type Input;
+
+    /// Returns the rest of the program, if this layer does not need an input. Otherwise returns the layer itself.
+    ///
+    /// # Errors
+    /// Returns the layer if it needs an input.
+    fn proceed(self) -> Result<M, Self>;
+
+    /// Feeds `input` into this layer's continuation, and returns the rest of the program.
+    ///
+    /// # Errors
+    /// Returns the layer if it does not take this input.
+    fn feed(self, input: Self::Input) -> Result<M, Self>;
+}
+
+/// Why [`record`] stopped before the program finished.
+///
+/// Both variants hold the rest of the program, starting with the layer that waits for an input, so it can be continued
+/// later, or rebuilt by replaying the recorded inputs.
+pub enum Interrupted<M> {
+    /// `supply` returned no input for a layer that needs one.
+    NoInput(M),
+    /// `supply` returned an input that does not fit the layer. This input was not recorded.
+    Mismatch(M),
+}
+
+/// Runs `program`, and passes every input it gets to `sink`, for instance to append it to a journal.
+///
+/// `supply` is called with each layer. It performs the layer's side effects (like printing text), and returns the input for
+/// layers that need one, and `None` for layers that don't. Each input is passed to `sink` once it has been fed to the
+/// program, so the journal can be written as the program goes, and is complete up to the last input, even if the program
+/// never finishes.
+///
+/// # Errors
+/// Stops at the first layer for which `supply` returns no fitting input, and returns the rest of the program. Returning
+/// `None` for a layer that needs input is therefore a clean way to stop, for instance when the player quits a game.
+pub fn record<'a, M, S, W>(
+    program: M,
+    mut supply: S,
+    mut sink: W,
+) -> Result<M::Value, Interrupted<M>>
+where
+    M: MonadFree<'a>,
+    M::Functor: Feed<M>,
+    <M::Functor as Feed<M>>::Input: Clone,
+    S: FnMut(&M::Functor) -> Option<<M::Functor as Feed<M>>::Input>,
+    W: FnMut(&<M::Functor as Feed<M>>::Input),
+{
+    let mut program = program;
+    loop {
+        program = match program.resume() {
+            Ok(a) => return Ok(a),
+            Err(layer) => {
+                let input = supply(&layer);
+                match layer.proceed() {
+                    Ok(next) => next,
+                    Err(layer) => {
+                        let Some(input) = input else {
+                            return Err(Interrupted::NoInput(M::wrap(layer)));
+                        };
+                        match layer.feed(input.clone()) {
+                            Ok(next) => {
+                                sink(&input);
+                                next
+                            }
+                            Err(layer) => return Err(Interrupted::Mismatch(M::wrap(layer))),
+                        }
+                    }
+                }
+            }
+        };
+    }
+}
+
+/// The reasons why a journal cannot be replayed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The entry at this position in the journal does not fit the layer that was waiting for input.
+    Mismatch(usize),
+    /// The program finished before the journal did. The number is how many entries were used.
+    Finished(usize),
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Mismatch(position) => {
+                write!(f, "journal entry {position} does not fit the program")
+            }
+            ReplayError::Finished(used) => write!(
+                f,
+                "the program finished after {used} journal entries, but the journal is longer"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Feeds the `journal` to `program`, and returns the program at the point where the journal ends.
+///
+/// Layers that do not need input are skipped, so side effects are not run again. If the journal ends exactly where the
+/// program finishes, the result is `Pure`. Otherwise it starts with the layer that waits for the next input.
+///
+/// # Errors
+/// Returns an error if an entry does not fit the layer waiting for input, or if the program finishes before the journal is used up.
+pub fn replay<'a, M, I>(program: M, journal: I) -> Result<M, ReplayError>
+where
+    M: MonadFree<'a>,
+    M::Functor: Feed<M>,
+    I: IntoIterator<Item = <M::Functor as Feed<M>>::Input>,
+{
+    let mut journal = journal.into_iter().enumerate();
+    let mut program = program;
+    loop {
+        program = match program.resume() {
+            Ok(a) => {
+                return match journal.next() {
+                    None => Ok(M::pure(a)),
+                    Some((used, _)) => Err(ReplayError::Finished(used)),
+                }
+            }
+            Err(layer) => match layer.proceed() {
+                Ok(next) => next,
+                Err(layer) => match journal.next() {
+                    None => return Ok(M::wrap(layer)),
+                    Some((position, input)) => layer
+                        .feed(input)
+                        .map_err(|_| ReplayError::Mismatch(position))?,
+                },
+            },
+        };
+    }
+}
+
+#[cfg(test)]
+mod journal_tests {
+    use super::{record, replay, Feed, Interrupted, ReplayError};
+    use crate::control::loop_m;
+    use crate::free_do;
+    use higher::Functor;
+    use std::io::Write;
+    use std::ops::ControlFlow;
+    use std::rc::Rc;
+
+    //A tiny game: The narrator says things, and the player picks options or names things.
+    #[derive(Clone)]
+    enum Game<'a, A> {
+        Say(String, A),
+        Pick(Vec<&'static str>, Rc<dyn Fn(usize) -> A + 'a>),
+        Name(Rc<dyn Fn(String) -> A + 'a>),
+    }
+
+    impl<'a, A: 'a> Functor<'a, A> for Game<'a, A> {
+        type Target<T> = Game<'a, T>;
+
+        fn fmap<B, F>(self, f: F) -> Self::Target<B>
+        where
+            F: Fn(A) -> B + 'a,
+        {
+            match self {
+                Game::Say(text, next) => Game::Say(text, f(next)),
+                Game::Pick(options, next) => Game::Pick(options, Rc::new(move |i| f(next(i)))),
+                Game::Name(next) => Game::Name(Rc::new(move |name| f(next(name)))),
+            }
+        }
+    }
+
+    free!(<'a>, FreeGame<'a, A>, Game<'a, FreeGame<'a, A>>);
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Input {
+        Picked(usize),
+        Named(String),
+    }
+
+    impl<'a, A: 'a> Feed<FreeGame<'a, A>> for Game<'a, FreeGame<'a, A>> {
+        type Input = Input;
+
+        fn proceed(self) -> Result<FreeGame<'a, A>, Self> {
+            match self {
+                Game::Say(_, next) => Ok(next),
+                other => Err(other),
+            }
+        }
+
+        fn feed(self, input: Input) -> Result<FreeGame<'a, A>, Self> {
+            match (self, input) {
+                (Game::Pick(options, next), Input::Picked(i)) if i < options.len() => Ok(next(i)),
+                (Game::Name(next), Input::Named(name)) => Ok(next(name)),
+                (other, _) => Err(other),
+            }
+        }
+    }
+
+    fn say<'a>(text: String) -> FreeGame<'a, ()> {
+        FreeGame::lift_f(Game::Say(text, ()))
+    }
+
+    fn pick<'a>(options: Vec<&'static str>) -> FreeGame<'a, usize> {
+        FreeGame::lift_f(Game::Pick(options, Rc::new(|i| i)))
+    }
+
+    fn name<'a>() -> FreeGame<'a, String> {
+        FreeGame::lift_f(Game::Name(Rc::new(|name| name)))
+    }
+
+    //the player names their dog, and then keeps throwing sticks until they go home. Returns the number of throws.
+    fn game<'a>() -> FreeGame<'a, u32> {
+        free_do! {
+            dog <= name();
+            loop_m(0, move |throws: u32| {
+                let dog = dog.clone();
+                free_do! {
+                    choice <= pick(vec!["throw stick", "go home"]);
+                    if choice == 0 {
+                        say(format!("{dog} fetches the stick."));
+                        yield ControlFlow::Continue(throws + 1)
+                    } else {
+                        yield ControlFlow::Break(throws)
+                    }
+                }
+            })
+        }
+    }
+
+    //plays the game with the given inputs, and returns what was said.
+    fn supplier(
+        inputs: Vec<Input>,
+        said: &mut Vec<String>,
+    ) -> impl FnMut(&Game<'_, FreeGame<'_, u32>>) -> Option<Input> + '_ {
+        let mut inputs = inputs.into_iter();
+        move |layer| match layer {
+            Game::Say(text, _) => {
+                said.push(text.clone());
+                None
+            }
+            _ => inputs.next(),
+        }
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let inputs = vec![
+            Input::Named(String::from("Rex")),
+            Input::Picked(0),
+            Input::Picked(0),
+            Input::Picked(1),
+        ];
+        let mut journal = Vec::new();
+        let mut said = Vec::new();
+        let throws = record(game(), supplier(inputs.clone(), &mut said), |input| {
+            journal.push(input.clone());
+        });
+        assert_eq!(throws.ok(), Some(2));
+        assert_eq!(journal, inputs);
+        assert_eq!(said, vec!["Rex fetches the stick."; 2]);
+
+        assert_eq!(replay(game(), journal).unwrap().into_pure(), Some(2));
+    }
+
+    #[test]
+    fn test_save_game() {
+        //play a bit, and quit. The journal is written line by line, as a save file would be.
+        let mut journal = Vec::new();
+        let mut said = Vec::new();
+        let inputs = vec![Input::Named(String::from("Bello")), Input::Picked(0)];
+        let sink = |input: &Input| match input {
+            Input::Named(name) => writeln!(journal, "name {name}").unwrap(),
+            Input::Picked(i) => writeln!(journal, "pick {i}").unwrap(),
+        };
+        let quit = record(game(), supplier(inputs, &mut said), sink);
+        assert!(matches!(quit, Err(Interrupted::NoInput(_))));
+        let journal = String::from_utf8(journal).unwrap();
+        assert_eq!(journal, "name Bello\npick 0\n");
+        let journal: Vec<_> = journal
+            .lines()
+            .map(|line| match line.split_once(' ') {
+                Some(("name", name)) => Input::Named(String::from(name)),
+                Some((_, i)) => Input::Picked(i.parse().unwrap()),
+                None => unreachable!(),
+            })
+            .collect();
+
+        //load the game, and continue playing.
+        let loaded = replay(game(), journal.clone()).unwrap();
+        assert!(
+            matches!(loaded.clone(), FreeGame::Free(layer) if matches!(*layer, Game::Pick(..)))
+        );
+        let mut said = Vec::new();
+        let more = vec![Input::Picked(0), Input::Picked(1)];
+        let mut journal = journal;
+        let throws = record(loaded, supplier(more, &mut said), |input| {
+            journal.push(input.clone());
+        });
+        assert_eq!(throws.ok(), Some(2));
+        assert_eq!(said, vec!["Bello fetches the stick."]);
+        assert_eq!(journal.len(), 4);
+
+        //an input that does not fit stops the program, and is not recorded.
+        let mut journal = Vec::new();
+        let wrong = vec![Input::Picked(0)];
+        let stopped = record(game(), supplier(wrong, &mut said), |input| {
+            journal.push(input.clone());
+        });
+        assert!(
+            matches!(stopped, Err(Interrupted::Mismatch(FreeGame::Free(layer))) if matches!(*layer, Game::Name(_)))
+        );
+        assert!(journal.is_empty());
+    }
+
+    #[test]
+    fn test_replay_errors() {
+        let wrong = vec![Input::Picked(0)];
+        assert_eq!(replay(game(), wrong).err(), Some(ReplayError::Mismatch(0)));
+        let out_of_range = vec![Input::Named(String::from("Rex")), Input::Picked(2)];
+        assert_eq!(
+            replay(game(), out_of_range).err(),
+            Some(ReplayError::Mismatch(1))
+        );
+        let too_long = vec![
+            Input::Named(String::from("Rex")),
+            Input::Picked(1),
+            Input::Picked(1),
+        ];
+        assert_eq!(
+            replay(game(), too_long).err(),
+            Some(ReplayError::Finished(2))
+        );
+    }
+}
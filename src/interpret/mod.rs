@@ -0,0 +1,31 @@
+//! Generic tools for writing and running interpreters.
+//!
+//! An interpreter of a Free Monad usually is a loop, that calls [`resume()`][crate::MonadFree::resume] and handles the returned
+//! [`Functor`][higher::Functor] layer, until the program is `Pure`. The modules in here wrap such loops, and work for every type
+//! that implements [`MonadFree`]. The handling of a single layer is left to a function supplied by the caller,
+//! usually called `handler`, which takes the layer and returns the rest of the program.
+
+use crate::MonadFree;
+
+pub mod compare;
+pub mod debugger;
+pub mod explore;
+pub mod fuel;
+pub mod fuzz;
+pub mod intercept;
+pub mod journal;
+
+/// Runs `program` to completion, passing each layer to `handler`. This is the plain interpreter loop.
+pub fn run<'a, M, H>(program: M, mut handler: H) -> M::Value
+where
+    M: MonadFree<'a>,
+    H: FnMut(M::Functor) -> M,
+{
+    let mut program = program;
+    loop {
+        match program.resume() {
+            Ok(a) => return a,
+            Err(layer) => program = handler(layer),
+        }
+    }
+}
@@ -28,12 +28,24 @@
 //! # How to use the macro?
 //!
 //! For details, please see the documentation of the [free] macro.
-//! In short, the syntax is either  
-//! `free!(FreeMonadTypeName<'a,A>, FunctorItsBasedOn<FreeMonadTypeName<'a,A>>)`,  
-//! or, if the lifetime of the Free Monad depends on the lifetime of the function passed to the Functor's fmap function,  
-//! `free!(<'a>, FreeMonadTypeName<'a,A>, FunctorItsBasedOn<'a,FreeMonadTypeName<'a,A>>)`,  
+//! In short, the syntax is either\
+//! `free!(FreeMonadTypeName<'a,A>, FunctorItsBasedOn<FreeMonadTypeName<'a,A>>)`,\
+//! or, if the lifetime of the Free Monad depends on the lifetime of the function passed to the Functor's fmap function,\
+//! `free!(<'a>, FreeMonadTypeName<'a,A>, FunctorItsBasedOn<'a,FreeMonadTypeName<'a,A>>)`,\
 //! where `'a` is the affected lifetime.
 //!
+//! All types generated by the macro implement the [`MonadFree`] trait, so code that should work with any Free Monad can be written generically.
+//!
+//! # Utilities
+//! Apart from the macro, the crate contains the [`free_do!`] do-notation macro, and a few modules that build on the [`MonadFree`] trait:
+//! - [`control`] contains control-flow combinators like [`when`][control::when], [`forever`][control::forever] and [`loop_m`][control::loop_m].
+//! - [`collections`] runs Free Monads over collections, with combinators like [`sequence`][collections::sequence] and [`traverse`][collections::traverse].
+//! - [`effects`] contains ready-made effects (like State, Reader, Writer, Error, Console, Random, Clock, Concurrency, Key-Value storage, Nondeterminism and probability Distributions) with interpreters.
+//! - [`interpret`] contains generic helpers for interpreters, like running a program with a limited budget of steps, observing it with interceptors, recording its inputs in a journal, exploring every path through it, fuzzing it with random inputs, comparing it to another program, or stepping through it in a debugger.
+//! - [`render`] draws Free Monads as Graphviz DOT graphs or indented outlines, which helps when debugging.
+//! - `testing` (behind the `testing` feature) checks the Functor, Applicative and Monad laws for Free Monads on generated data.
+//! - [`prng`] is a small seedable random number generator, so everything that needs randomness is reproducible.
+//!
 //! # Examples
 //! The project's repository contains a folder named "examples", which at the moment contains a tiny text adventure that shows how such a game
 //! could be implemented with Free Monads. The example highlights both, features and (current) limitations of Free Monads in Rust.
@@ -42,10 +54,10 @@
 //!
 //! # Why a Macro?
 //! Until [non-lifetime binders](https://github.com/rust-lang/rust/issues/108185) become stable, this seems to be the easiest way.
-//! In generic code, the type signature would be  
-//! `enum Free<A,F> where F : Functor<Free<A,F>>`.  
-//! If one now wants to implement the [`Functor`][higher::Functor] trait for this, it is not really possible to express the  
-//! `Target<T> = Free<A,F::Target<Free<A,F::Target<...>>>>`  
+//! In generic code, the type signature would be\
+//! `enum Free<A,F> where F : Functor<Free<A,F>>`.\
+//! If one now wants to implement the [`Functor`][higher::Functor] trait for this, it is not really possible to express the\
+//! `Target<T> = Free<A,F::Target<Free<A,F::Target<...>>>>`\
 //! generic associated type.
 //!
 //! See the [blog post about this crate](https://www.grois.info/posts/2023-03/2023-03-11-adventures-with-free-monads-and-higher.xhtml)
@@ -54,8 +66,9 @@
 //! # A word of warning:
 //! This crate should be considered a proof-of-concept. Its memory complexity is horrendous, and the performance of the Free Monad's [`Apply`][higher::Apply]
 //! implementation can only be described as abysmal due to its reliance on deep copies.
-//! In addition, the desugaring of do-notation currently (with higher-0.2) only works well with return types that are [`Copy`].
+//! In addition, the desugaring of do-notation in higher's `run!` macro currently (with higher-0.2) only works well with return types that are [`Copy`].
 //! If those types are big, that might be a further performance bottleneck.
+//! This crate therefore comes with its own do-notation macro, [`free_do!`], which supports explicit clones and pattern destructuring.
 //! There is work ongoing to [add explicit clone support to higher](https://github.com/bodil/higher/issues/6) though, so this might no longer be an issue with
 //! later higher versions.
 
@@ -63,10 +76,66 @@
 //that this is re-exported is an implementation detail. Users should import directly from higher imho.
 pub extern crate higher;
 
+#[doc(hidden)]
+//lets the `impl Iterator` returned by generated functions borrow from lifetimes that don't appear in its bounds.
+pub trait Captures<'a> {}
+impl<T: ?Sized> Captures<'_> for T {}
+
+/// The common interface of all Free [`Monad`][higher::Monad] types generated by the [`free!`] macro.
+///
+/// The types generated by the macro are unrelated enums, so code that should work for any eDSL cannot just match on their variants.
+/// This trait exposes the operations needed to take a Free Monad apart and to put it back together, allowing to write drivers,
+/// test harnesses and combinators that are generic over the underlying [`Functor`][higher::Functor].
+///
+/// For instance, a generic interpreter loop, that feeds each layer of the Free Monad into a step function, can be written like this:
+/// ```
+/// # #[macro_use] extern crate higher_free_macro;
+/// # use higher_free_macro::higher::*;
+/// use higher_free_macro::MonadFree;
+///
+/// fn run<'a, M : MonadFree<'a>>(mut program : M, mut step : impl FnMut(M::Functor) -> M) -> M::Value {
+///     loop {
+///         match program.resume() {
+///             Ok(value) => return value,
+///             Err(layer) => program = step(layer),
+///         }
+///     }
+/// }
+///
+/// free!(FreeOption<A>, Option<FreeOption<A>>);
+/// fn main() {
+///     let program = FreeOption::lift_f(Some(3)).bind(|x| FreeOption::lift_f(Some(x * 2)));
+///     assert_eq!(run(program, |layer| layer.unwrap()), 6);
+/// }
+/// ```
+pub trait MonadFree<'a>: Sized {
+    /// The type of the values held in `Pure` nodes.
+    type Value;
+    /// One layer of the Free Monad, namely the base [`Functor`][higher::Functor] holding the remainder of the Free Monad.
+    type Functor: higher::Functor<'a, Self>;
+
+    /// Creates a `Pure` node.
+    fn pure(value: Self::Value) -> Self;
+    /// Creates a `Free` node from a single layer.
+    fn wrap(layer: Self::Functor) -> Self;
+    /// Converts the base [`Functor`][higher::Functor] into the Free Monad. Same as the `lift_f()` function generated by the [`free!`] macro.
+    fn lift_f(functor: <Self::Functor as higher::Functor<'a, Self>>::Target<Self::Value>) -> Self;
+    /// Splits off the outermost layer. Same as the `resume()` function generated by the [`free!`] macro.
+    ///
+    /// # Errors
+    /// Not an error in the usual sense: `Err` holds the outermost layer if `self` is a `Free` node.
+    fn resume(self) -> Result<Self::Value, Self::Functor>;
+    /// Like [`resume()`][MonadFree::resume], but borrows instead of consuming `self`.
+    ///
+    /// # Errors
+    /// Just like [`resume()`][MonadFree::resume], `Err` holds the outermost layer if `self` is a `Free` node.
+    fn peel(&self) -> Result<&Self::Value, &Self::Functor>;
+}
+
 /// The macro that generates a Free [`Monad`][higher::Monad] type for a given [`Functor`][higher::Functor].
 ///
-/// To declare a Free [`Monad`][higher::Monad] over a [`Functor`][higher::Functor] named `Funky<A>`, the syntax would be  
-/// `free!(FreeFunky<A>, Funky<FreeFunky<A>>)`.  
+/// To declare a Free [`Monad`][higher::Monad] over a [`Functor`][higher::Functor] named `Funky<A>`, the syntax would be\
+/// `free!(FreeFunky<A>, Funky<FreeFunky<A>>)`.\
 /// This declares an enum named `FreeFunky<A>`, and implements all traits needed for it to be a [`Monad`][higher::Monad].
 ///
 /// # Restrictions
@@ -168,17 +237,17 @@ pub extern crate higher;
 /// ```
 ///
 /// # Generated Functions
-/// In addition to the trait implementations for [`Bind`][higher::Bind], [`Functor`][higher::Functor], [`Apply`][higher::Apply] and [`Pure`][higher::Pure],
-/// the macro also generates associated functions for the Free Monad type. These functions are:  
-/// `fn lift_f(functor : F) -> Self`  
-/// `fn retract(self)-> F where F : Bind + Pure`  
+/// In addition to the trait implementations for [`Bind`][higher::Bind], [`Functor`][higher::Functor], [`Apply`][higher::Apply], [`Pure`][higher::Pure]
+/// and this crate's [`MonadFree`], the macro also generates associated functions for the Free Monad type. These functions are:\
+/// `fn lift_f(functor : F) -> Self`\
+/// `fn retract(self)-> F where F : Bind + Pure`\
 /// where `F` is the [`Functor`][higher::Functor] the Free Monad is based on, specialized for the `Pure` type.
-/// A concrete example will make this more clear. Let's take our `FreeOption<A>` example from above. In this case, the signatures are  
-/// `fn lift_f(functor : Option<A>) -> FreeOption<A>` and  
+/// A concrete example will make this more clear. Let's take our `FreeOption<A>` example from above. In this case, the signatures are\
+/// `fn lift_f(functor : Option<A>) -> FreeOption<A>` and\
 /// `fn retract(self : FreeOption<A>) -> Option<A>`
 ///
 /// `lift_f()` converts a base Functor into the corresponding Free Monad, meaning that the Functor gets wrapped in `Free`, and the values it holds get
-/// mapped into `Pure`. The (simplified for readability) formula is:  
+/// mapped into `Pure`. The (simplified for readability) formula is:\
 /// `Self::Free(functor.fmap(|a| Self::Pure(a)))`
 ///
 /// `retract()` is the left-inverse of `lift_f()`. `|x| retract(lift_f(x))` is (ignoring type coercion) equivalent to [`identity`][std::convert::identity]:
@@ -206,6 +275,68 @@ pub extern crate higher;
 ///     assert_eq!(and_back, Some(12345u32));
 /// }
 /// ```
+///
+/// # Inspection Functions
+/// Interpreters usually need to look at one layer of the Free Monad at a time. To avoid having to match on the `Pure` and `Free` variants
+/// and dereferencing the `Box` in the latter by hand, the macro generates a couple of helpers:\
+/// `fn resume(self) -> Result<A, F>`\
+/// `fn peel(&self) -> Result<&A, &F>`\
+/// `fn is_pure(&self) -> bool` and `fn is_free(&self) -> bool`\
+/// `fn as_pure(&self) -> Option<&A>` and `fn as_free(&self) -> Option<&F>`\
+/// `fn into_pure(self) -> Option<A>`\
+/// `fn map_layer(self, f : impl FnOnce(F) -> F) -> Self`\
+/// where `F` is the [`Functor`][higher::Functor] the Free Monad is based on, specialized for the Free Monad itself
+/// (so, for `FreeOption<A>` it is `Option<FreeOption<A>>`).
+///
+/// With those, the above example becomes a lot less noisy:
+/// ```
+/// # #[macro_use] extern crate higher_free_macro;
+/// # use higher_free_macro::higher::*;
+/// free!(FreeOption<A>, Option<FreeOption<A>>);
+/// fn main() {
+///     let free_monad = FreeOption::lift_f(Some(12345u32));
+///     assert!(free_monad.is_free());
+///     let inner = free_monad.resume().unwrap_err().unwrap();
+///     assert_eq!(inner.as_pure(), Some(&12345u32));
+/// }
+/// ```
+///
+/// # Foldable
+/// If the [`Functor`][higher::Functor] the Free Monad is based on is [`Foldable`][higher::Foldable], the Free Monad is a tree with values
+/// at its leaves, and therefore [`Foldable`][higher::Foldable] too. The macro implements [`Foldable`][higher::Foldable] for this case, and
+/// additionally generates two functions to iterate lazily over the leaves from left to right:\
+/// `fn leaves(&self) -> impl Iterator<Item = &A>`\
+/// `fn into_leaves(self) -> impl Iterator<Item = A>`
+/// ```
+/// # #[macro_use] extern crate higher_free_macro;
+/// # use higher_free_macro::higher::*;
+/// free!(FreeVec<A>, Vec<FreeVec<A>>);
+/// fn main() {
+///     let tree = FreeVec::lift_f(vec![1, 2, 3]).bind(|x| FreeVec::lift_f(vec![x, x * 10]));
+///     assert_eq!(tree.leaves().sum::<u32>(), 66);
+///     assert_eq!(tree.foldl(|acc, x| acc.max(x), 0), 30);
+/// }
+/// ```
+///
+/// # Loops
+/// Writing a loop as a function that calls itself through `bind` works, but every iteration adds another layer of nested binds.
+/// For long running loops, like the main loop of a game, the macro generates\
+/// `fn tail_rec_m<S>(seed : S, step : impl Fn(S) -> FreeMonad<ControlFlow<A, S>>) -> Self`\
+/// in the style of PureScript's `MonadRec`. The next iteration is only built once the current one has finished, and steps that
+/// do not contain any [`Functor`][higher::Functor] layer are run in a plain loop. So, as long as the [`Functor`][higher::Functor] stores its
+/// continuation in a closure, an interpreter can run such a loop in constant stack space. See [`loop_m`][crate::control::loop_m], which this delegates to.
+/// ```
+/// # #[macro_use] extern crate higher_free_macro;
+/// # use higher_free_macro::higher::*;
+/// use std::ops::ControlFlow;
+/// free!(FreeOption<A>, Option<FreeOption<A>>);
+/// fn main() {
+///     let countdown = FreeOption::tail_rec_m(1_000_000u32, |n| {
+///         FreeOption::Pure(if n == 0 { ControlFlow::Break("liftoff") } else { ControlFlow::Continue(n - 1) })
+///     });
+///     assert_eq!(countdown.into_pure(), Some("liftoff"));
+/// }
+/// ```
 #[macro_export]
 macro_rules! free {
     ($v:vis $name:ident<$($other_lifetimes:lifetime,)* $generic:ident $(,$other_generics:ident)*>, $f:ty) => {
@@ -229,6 +360,116 @@ macro_rules! free {
                     $name::Free(m) => {m.bind(|a| a.retract())}
                 }
             }
+
+            /// Splits off the outermost layer: `Ok` holds the value of a `Pure` node, `Err` the (unboxed) [`Functor`][$crate::higher::Functor] of a `Free` node.
+            ///
+            /// # Errors
+            /// Returns the [`Functor`][$crate::higher::Functor] layer as `Err` if `self` is a `Free` node. This is not a failure.
+            #[allow(unused)]
+            $v fn resume(self) -> Result<$generic, $f> {
+                match self {
+                    $name::Pure(a) => Ok(a),
+                    $name::Free(m) => Err(*m),
+                }
+            }
+
+            /// Like `resume()`, but borrows instead of consuming `self`.
+            ///
+            /// # Errors
+            /// Returns a reference to the [`Functor`][$crate::higher::Functor] layer as `Err` if `self` is a `Free` node.
+            #[allow(unused)]
+            $v fn peel(&self) -> Result<&$generic, &$f> {
+                match self {
+                    $name::Pure(a) => Ok(a),
+                    $name::Free(m) => Err(&**m),
+                }
+            }
+
+            #[allow(unused)]
+            #[must_use]
+            $v fn is_pure(&self) -> bool {
+                matches!(self, $name::Pure(_))
+            }
+
+            #[allow(unused)]
+            #[must_use]
+            $v fn is_free(&self) -> bool {
+                matches!(self, $name::Free(_))
+            }
+
+            #[allow(unused)]
+            #[must_use]
+            $v fn as_pure(&self) -> Option<&$generic> {
+                self.peel().ok()
+            }
+
+            #[allow(unused)]
+            #[must_use]
+            $v fn as_free(&self) -> Option<&$f> {
+                self.peel().err()
+            }
+
+            #[allow(unused)]
+            #[must_use]
+            $v fn into_pure(self) -> Option<$generic> {
+                self.resume().ok()
+            }
+
+            /// Applies `f` to the outermost [`Functor`][$crate::higher::Functor] layer. `Pure` nodes are returned unchanged.
+            #[allow(unused)]
+            #[must_use]
+            $v fn map_layer<FreeMacroReservedType>(self, f: FreeMacroReservedType) -> Self where FreeMacroReservedType: FnOnce($f) -> $f {
+                match self {
+                    $name::Pure(a) => $name::Pure(a),
+                    $name::Free(m) => $name::Free(Box::new(f(*m))),
+                }
+            }
+
+            /// Iterates over the values in all `Pure` nodes, from left to right. Only available if the base [`Functor`][$crate::higher::Functor] is [`Foldable`][$crate::higher::Foldable].
+            /// The tree is walked lazily, with an explicit stack of the nodes still to visit.
+            #[allow(unused)]
+            $v fn leaves<'free_macro_reserved_lifetime>(&'free_macro_reserved_lifetime self) -> impl Iterator<Item = &'free_macro_reserved_lifetime $generic> $(+ $crate::Captures<$other_lifetimes>)* + $crate::Captures<'free_macro_reserved_lifetime> where $f : $crate::higher::Foldable<'free_macro_reserved_lifetime, Self> {
+                let mut stack = vec![self];
+                std::iter::from_fn(move || {
+                    while let Some(node) = stack.pop() {
+                        match node {
+                            $name::Pure(a) => return Some(a),
+                            $name::Free(m) => {
+                                let children = $crate::higher::Foldable::foldl_ref(&**m, |mut v : Vec<&'free_macro_reserved_lifetime Self>, x| { v.push(x); v }, Vec::new());
+                                stack.extend(children.into_iter().rev());
+                            }
+                        }
+                    }
+                    None
+                })
+            }
+
+            /// Like `leaves()`, but consumes `self` and yields the values themselves.
+            #[allow(unused)]
+            $v fn into_leaves<'free_macro_reserved_lifetime>(self) -> impl Iterator<Item = $generic> $(+ $crate::Captures<$other_lifetimes>)* + $crate::Captures<'free_macro_reserved_lifetime> where $f : $crate::higher::Foldable<'free_macro_reserved_lifetime, Self> {
+                let mut stack = vec![self];
+                std::iter::from_fn(move || {
+                    while let Some(node) = stack.pop() {
+                        match node {
+                            $name::Pure(a) => return Some(a),
+                            $name::Free(m) => {
+                                let children = $crate::higher::Foldable::foldl(*m, |mut v : Vec<Self>, x| { v.push(x); v }, Vec::new());
+                                stack.extend(children.into_iter().rev());
+                            }
+                        }
+                    }
+                    None
+                })
+            }
+
+            /// Runs `step` in a loop, starting with `seed`, until it yields [`ControlFlow::Break`][std::ops::ControlFlow::Break]. See [`loop_m`][$crate::control::loop_m].
+            #[allow(unused)]
+            $v fn tail_rec_m<'free_macro_reserved_lifetime, FreeMacroReservedType, FreeMacroReservedType2>(seed : FreeMacroReservedType, step : FreeMacroReservedType2) -> Self
+            where FreeMacroReservedType2 : Fn(FreeMacroReservedType) -> $name<$($other_lifetimes,)* std::ops::ControlFlow<$generic, FreeMacroReservedType> $(,$other_generics)*> + 'free_macro_reserved_lifetime,
+                $name<$($other_lifetimes,)* std::ops::ControlFlow<$generic, FreeMacroReservedType> $(,$other_generics)*> : $crate::MonadFree<'free_macro_reserved_lifetime, Value = std::ops::ControlFlow<$generic, FreeMacroReservedType>>
+            {
+                $crate::control::loop_m(seed, step)
+            }
         }
 
         impl<'free_macro_reserved_lifetime, $($other_lifetimes,)* $generic $(,$other_generics)*> $crate::higher::Functor<'free_macro_reserved_lifetime,$generic> for $name<$($other_lifetimes,)* $generic $(,$other_generics)*> {
@@ -250,6 +491,26 @@ macro_rules! free {
             }
         }
 
+        impl<'free_macro_reserved_lifetime, $($other_lifetimes,)* $generic $(,$other_generics)*> $crate::MonadFree<'free_macro_reserved_lifetime> for $name<$($other_lifetimes,)* $generic $(,$other_generics)*> where $f : $crate::higher::Functor<'free_macro_reserved_lifetime, $name<$($other_lifetimes,)* $generic $(,$other_generics)*>> {
+            type Value = $generic;
+            type Functor = $f;
+            fn pure(value : $generic) -> Self {
+                Self::Pure(value)
+            }
+            fn wrap(layer : $f) -> Self {
+                Self::Free(Box::new(layer))
+            }
+            fn lift_f(functor : <$f as $crate::higher::Functor<'free_macro_reserved_lifetime, Self>>::Target<$generic>) -> Self {
+                $name::lift_f(functor)
+            }
+            fn resume(self) -> Result<$generic, $f> {
+                $name::resume(self)
+            }
+            fn peel(&self) -> Result<&$generic, &$f> {
+                $name::peel(self)
+            }
+        }
+
         impl<'free_macro_reserved_lifetime, $($other_lifetimes,)* $generic $(,$other_generics)*> $crate::higher::Apply<'free_macro_reserved_lifetime, $generic> for $name<$($other_lifetimes,)* $generic $(,$other_generics)*> where $generic: 'free_macro_reserved_lifetime + Clone, Self : Clone {
             type Target<FreeMacroReservedType> = $name<$($other_lifetimes,)* FreeMacroReservedType $(,$other_generics)*> where FreeMacroReservedType:'free_macro_reserved_lifetime;
             fn apply<FreeMacroReservedType>(
@@ -279,6 +540,76 @@ macro_rules! free {
                 __bind_impl(self, &f)
             }
         }
+
+        impl<'free_macro_reserved_lifetime, $($other_lifetimes,)* $generic $(,$other_generics)*> $crate::higher::Foldable<'free_macro_reserved_lifetime, $generic> for $name<$($other_lifetimes,)* $generic $(,$other_generics)*> where $f : $crate::higher::Foldable<'free_macro_reserved_lifetime, $name<$($other_lifetimes,)* $generic $(,$other_generics)*>> {
+            fn foldr<FreeMacroReservedType, FreeMacroReservedType2>(self, f: FreeMacroReservedType2, init: FreeMacroReservedType) -> FreeMacroReservedType
+            where
+                FreeMacroReservedType2: Fn($generic, FreeMacroReservedType) -> FreeMacroReservedType + 'free_macro_reserved_lifetime,
+            {
+                fn __foldr_impl<'free_macro_reserved_lifetime, $($other_lifetimes,)* $generic $(,$other_generics)*, FreeMacroReservedType, FreeMacroReservedType2>(s : $name<$($other_lifetimes,)* $generic $(,$other_generics)*>, f : std::rc::Rc<FreeMacroReservedType2>, init: FreeMacroReservedType) -> FreeMacroReservedType where $f : $crate::higher::Foldable<'free_macro_reserved_lifetime, $name<$($other_lifetimes,)* $generic $(,$other_generics)*>>, FreeMacroReservedType2: Fn($generic, FreeMacroReservedType) -> FreeMacroReservedType + 'free_macro_reserved_lifetime{
+                    match s {
+                        $name::Pure(a) => f(a, init),
+                        $name::Free(fa) => $crate::higher::Foldable::foldr(*fa, move |x : $name<$($other_lifetimes,)* $generic $(,$other_generics)*>, acc| __foldr_impl(x, f.clone(), acc), init),
+                    }
+                }
+                __foldr_impl(self, std::rc::Rc::new(f), init)
+            }
+
+            fn foldr_ref<FreeMacroReservedType, FreeMacroReservedType2>(&'free_macro_reserved_lifetime self, f: FreeMacroReservedType2, init: FreeMacroReservedType) -> FreeMacroReservedType
+            where
+                $generic: 'free_macro_reserved_lifetime,
+                FreeMacroReservedType2: Fn(&'free_macro_reserved_lifetime $generic, FreeMacroReservedType) -> FreeMacroReservedType + 'free_macro_reserved_lifetime,
+            {
+                fn __foldr_ref_impl<'free_macro_reserved_lifetime, $($other_lifetimes,)* $generic $(,$other_generics)*, FreeMacroReservedType, FreeMacroReservedType2>(s : &'free_macro_reserved_lifetime $name<$($other_lifetimes,)* $generic $(,$other_generics)*>, f : std::rc::Rc<FreeMacroReservedType2>, init: FreeMacroReservedType) -> FreeMacroReservedType where $generic: 'free_macro_reserved_lifetime, $f : $crate::higher::Foldable<'free_macro_reserved_lifetime, $name<$($other_lifetimes,)* $generic $(,$other_generics)*>>, FreeMacroReservedType2: Fn(&'free_macro_reserved_lifetime $generic, FreeMacroReservedType) -> FreeMacroReservedType + 'free_macro_reserved_lifetime{
+                    match s {
+                        $name::Pure(a) => f(a, init),
+                        $name::Free(fa) => $crate::higher::Foldable::foldr_ref(&**fa, move |x : &'free_macro_reserved_lifetime $name<$($other_lifetimes,)* $generic $(,$other_generics)*>, acc| __foldr_ref_impl(x, f.clone(), acc), init),
+                    }
+                }
+                __foldr_ref_impl(self, std::rc::Rc::new(f), init)
+            }
+
+            fn foldl<FreeMacroReservedType, FreeMacroReservedType2>(self, f: FreeMacroReservedType2, init: FreeMacroReservedType) -> FreeMacroReservedType
+            where
+                FreeMacroReservedType2: Fn(FreeMacroReservedType, $generic) -> FreeMacroReservedType + 'free_macro_reserved_lifetime,
+            {
+                fn __foldl_impl<'free_macro_reserved_lifetime, $($other_lifetimes,)* $generic $(,$other_generics)*, FreeMacroReservedType, FreeMacroReservedType2>(s : $name<$($other_lifetimes,)* $generic $(,$other_generics)*>, f : std::rc::Rc<FreeMacroReservedType2>, init: FreeMacroReservedType) -> FreeMacroReservedType where $f : $crate::higher::Foldable<'free_macro_reserved_lifetime, $name<$($other_lifetimes,)* $generic $(,$other_generics)*>>, FreeMacroReservedType2: Fn(FreeMacroReservedType, $generic) -> FreeMacroReservedType + 'free_macro_reserved_lifetime{
+                    match s {
+                        $name::Pure(a) => f(init, a),
+                        $name::Free(fa) => $crate::higher::Foldable::foldl(*fa, move |acc, x : $name<$($other_lifetimes,)* $generic $(,$other_generics)*>| __foldl_impl(x, f.clone(), acc), init),
+                    }
+                }
+                __foldl_impl(self, std::rc::Rc::new(f), init)
+            }
+
+            fn foldl_ref<FreeMacroReservedType, FreeMacroReservedType2>(&'free_macro_reserved_lifetime self, f: FreeMacroReservedType2, init: FreeMacroReservedType) -> FreeMacroReservedType
+            where
+                $generic: 'free_macro_reserved_lifetime,
+                FreeMacroReservedType2: Fn(FreeMacroReservedType, &'free_macro_reserved_lifetime $generic) -> FreeMacroReservedType + 'free_macro_reserved_lifetime,
+            {
+                fn __foldl_ref_impl<'free_macro_reserved_lifetime, $($other_lifetimes,)* $generic $(,$other_generics)*, FreeMacroReservedType, FreeMacroReservedType2>(s : &'free_macro_reserved_lifetime $name<$($other_lifetimes,)* $generic $(,$other_generics)*>, f : std::rc::Rc<FreeMacroReservedType2>, init: FreeMacroReservedType) -> FreeMacroReservedType where $generic: 'free_macro_reserved_lifetime, $f : $crate::higher::Foldable<'free_macro_reserved_lifetime, $name<$($other_lifetimes,)* $generic $(,$other_generics)*>>, FreeMacroReservedType2: Fn(FreeMacroReservedType, &'free_macro_reserved_lifetime $generic) -> FreeMacroReservedType + 'free_macro_reserved_lifetime{
+                    match s {
+                        $name::Pure(a) => f(init, a),
+                        $name::Free(fa) => $crate::higher::Foldable::foldl_ref(&**fa, move |acc, x : &'free_macro_reserved_lifetime $name<$($other_lifetimes,)* $generic $(,$other_generics)*>| __foldl_ref_impl(x, f.clone(), acc), init),
+                    }
+                }
+                __foldl_ref_impl(self, std::rc::Rc::new(f), init)
+            }
+
+            fn fold_map<FreeMacroReservedType2, FreeMacroReservedType>(self, f: FreeMacroReservedType2) -> FreeMacroReservedType
+            where
+                FreeMacroReservedType2: Fn($generic) -> FreeMacroReservedType + 'free_macro_reserved_lifetime,
+                FreeMacroReservedType: $crate::higher::Monoid,
+            {
+                fn __fold_map_impl<'free_macro_reserved_lifetime, $($other_lifetimes,)* $generic $(,$other_generics)*, FreeMacroReservedType, FreeMacroReservedType2>(s : $name<$($other_lifetimes,)* $generic $(,$other_generics)*>, f : std::rc::Rc<FreeMacroReservedType2>) -> FreeMacroReservedType where $f : $crate::higher::Foldable<'free_macro_reserved_lifetime, $name<$($other_lifetimes,)* $generic $(,$other_generics)*>>, FreeMacroReservedType2: Fn($generic) -> FreeMacroReservedType + 'free_macro_reserved_lifetime, FreeMacroReservedType: $crate::higher::Monoid{
+                    match s {
+                        $name::Pure(a) => f(a),
+                        $name::Free(fa) => $crate::higher::Foldable::fold_map(*fa, move |x : $name<$($other_lifetimes,)* $generic $(,$other_generics)*>| __fold_map_impl(x, f.clone())),
+                    }
+                }
+                __fold_map_impl(self, std::rc::Rc::new(f))
+            }
+        }
     };
     (<$a:lifetime>, $v:vis $name:ident<$($other_lifetimes:lifetime,)+ $generic:ident $(,$other_generics:ident)*>, $f:ty) =>{
         #[derive(Clone)]
@@ -301,6 +632,116 @@ macro_rules! free {
                     $name::Free(m) => {m.bind(Self::retract)}
                 }
             }
+
+            /// Splits off the outermost layer: `Ok` holds the value of a `Pure` node, `Err` the (unboxed) [`Functor`][$crate::higher::Functor] of a `Free` node.
+            ///
+            /// # Errors
+            /// Returns the [`Functor`][$crate::higher::Functor] layer as `Err` if `self` is a `Free` node. This is not a failure.
+            #[allow(unused)]
+            $v fn resume(self) -> Result<$generic, $f> {
+                match self {
+                    $name::Pure(a) => Ok(a),
+                    $name::Free(m) => Err(*m),
+                }
+            }
+
+            /// Like `resume()`, but borrows instead of consuming `self`.
+            ///
+            /// # Errors
+            /// Returns a reference to the [`Functor`][$crate::higher::Functor] layer as `Err` if `self` is a `Free` node.
+            #[allow(unused)]
+            $v fn peel(&self) -> Result<&$generic, &$f> {
+                match self {
+                    $name::Pure(a) => Ok(a),
+                    $name::Free(m) => Err(&**m),
+                }
+            }
+
+            #[allow(unused)]
+            #[must_use]
+            $v fn is_pure(&self) -> bool {
+                matches!(self, $name::Pure(_))
+            }
+
+            #[allow(unused)]
+            #[must_use]
+            $v fn is_free(&self) -> bool {
+                matches!(self, $name::Free(_))
+            }
+
+            #[allow(unused)]
+            #[must_use]
+            $v fn as_pure(&self) -> Option<&$generic> {
+                self.peel().ok()
+            }
+
+            #[allow(unused)]
+            #[must_use]
+            $v fn as_free(&self) -> Option<&$f> {
+                self.peel().err()
+            }
+
+            #[allow(unused)]
+            #[must_use]
+            $v fn into_pure(self) -> Option<$generic> {
+                self.resume().ok()
+            }
+
+            /// Applies `f` to the outermost [`Functor`][$crate::higher::Functor] layer. `Pure` nodes are returned unchanged.
+            #[allow(unused)]
+            #[must_use]
+            $v fn map_layer<FreeMacroReservedType>(self, f: FreeMacroReservedType) -> Self where FreeMacroReservedType: FnOnce($f) -> $f {
+                match self {
+                    $name::Pure(a) => $name::Pure(a),
+                    $name::Free(m) => $name::Free(Box::new(f(*m))),
+                }
+            }
+
+            /// Iterates over the values in all `Pure` nodes, from left to right. Only available if the base [`Functor`][$crate::higher::Functor] is [`Foldable`][$crate::higher::Foldable].
+            /// The tree is walked lazily, with an explicit stack of the nodes still to visit.
+            #[allow(unused)]
+            $v fn leaves<'free_macro_reserved_lifetime>(&'free_macro_reserved_lifetime self) -> impl Iterator<Item = &'free_macro_reserved_lifetime $generic> $(+ $crate::Captures<$other_lifetimes>)* + $crate::Captures<'free_macro_reserved_lifetime> where $f : $crate::higher::Foldable<'free_macro_reserved_lifetime, Self> {
+                let mut stack = vec![self];
+                std::iter::from_fn(move || {
+                    while let Some(node) = stack.pop() {
+                        match node {
+                            $name::Pure(a) => return Some(a),
+                            $name::Free(m) => {
+                                let children = $crate::higher::Foldable::foldl_ref(&**m, |mut v : Vec<&'free_macro_reserved_lifetime Self>, x| { v.push(x); v }, Vec::new());
+                                stack.extend(children.into_iter().rev());
+                            }
+                        }
+                    }
+                    None
+                })
+            }
+
+            /// Like `leaves()`, but consumes `self` and yields the values themselves.
+            #[allow(unused)]
+            $v fn into_leaves<'free_macro_reserved_lifetime>(self) -> impl Iterator<Item = $generic> $(+ $crate::Captures<$other_lifetimes>)* + $crate::Captures<'free_macro_reserved_lifetime> where $f : $crate::higher::Foldable<'free_macro_reserved_lifetime, Self> {
+                let mut stack = vec![self];
+                std::iter::from_fn(move || {
+                    while let Some(node) = stack.pop() {
+                        match node {
+                            $name::Pure(a) => return Some(a),
+                            $name::Free(m) => {
+                                let children = $crate::higher::Foldable::foldl(*m, |mut v : Vec<Self>, x| { v.push(x); v }, Vec::new());
+                                stack.extend(children.into_iter().rev());
+                            }
+                        }
+                    }
+                    None
+                })
+            }
+
+            /// Runs `step` in a loop, starting with `seed`, until it yields [`ControlFlow::Break`][std::ops::ControlFlow::Break]. See [`loop_m`][$crate::control::loop_m].
+            #[allow(unused)]
+            $v fn tail_rec_m<FreeMacroReservedType : $a, FreeMacroReservedType2>(seed : FreeMacroReservedType, step : FreeMacroReservedType2) -> Self
+            where FreeMacroReservedType2 : Fn(FreeMacroReservedType) -> $name<$($other_lifetimes,)* std::ops::ControlFlow<$generic, FreeMacroReservedType> $(,$other_generics)*> + $a,
+                $name<$($other_lifetimes,)* std::ops::ControlFlow<$generic, FreeMacroReservedType> $(,$other_generics)*> : $crate::MonadFree<$a, Value = std::ops::ControlFlow<$generic, FreeMacroReservedType>>
+            {
+                $crate::control::loop_m(seed, step)
+            }
         }
 
         impl<$($other_lifetimes : $a,)* $generic $(,$other_generics)*> $crate::higher::Functor<$a,$generic> for $name<$($other_lifetimes,)* $generic $(,$other_generics)*> where $generic : $a $(,$other_generics : $a)* {
@@ -326,6 +767,26 @@ macro_rules! free {
             }
         }
 
+        impl<$($other_lifetimes : $a,)* $generic $(,$other_generics)*> $crate::MonadFree<$a> for $name<$($other_lifetimes,)* $generic $(,$other_generics)*> where $generic : $a $(,$other_generics : $a)*, $f : $crate::higher::Functor<$a, $name<$($other_lifetimes,)* $generic $(,$other_generics)*>> {
+            type Value = $generic;
+            type Functor = $f;
+            fn pure(value : $generic) -> Self {
+                Self::Pure(value)
+            }
+            fn wrap(layer : $f) -> Self {
+                Self::Free(Box::new(layer))
+            }
+            fn lift_f(functor : <$f as $crate::higher::Functor<$a, Self>>::Target<$generic>) -> Self {
+                $name::lift_f(functor)
+            }
+            fn resume(self) -> Result<$generic, $f> {
+                $name::resume(self)
+            }
+            fn peel(&self) -> Result<&$generic, &$f> {
+                $name::peel(self)
+            }
+        }
+
         impl<$($other_lifetimes : $a,)* $generic $(,$other_generics)*> $crate::higher::Apply<$a, $generic> for $name<$($other_lifetimes,)* $generic $(,$other_generics)*> where $generic: $a + Clone $(,$other_generics : $a + Clone)*, Self : Clone{
             type Target<FreeMacroReservedType> = $name<$($other_lifetimes,)* FreeMacroReservedType $(,$other_generics)*> where FreeMacroReservedType:$a;
             fn apply<FreeMacroReservedType>(
@@ -356,15 +817,96 @@ macro_rules! free {
                 __bind_impl(self, r)
             }
         }
+
+        impl<'free_macro_reserved_lifetime, $($other_lifetimes,)* $generic $(,$other_generics)*> $crate::higher::Foldable<'free_macro_reserved_lifetime, $generic> for $name<$($other_lifetimes,)* $generic $(,$other_generics)*> where $f : $crate::higher::Foldable<'free_macro_reserved_lifetime, $name<$($other_lifetimes,)* $generic $(,$other_generics)*>> {
+            fn foldr<FreeMacroReservedType, FreeMacroReservedType2>(self, f: FreeMacroReservedType2, init: FreeMacroReservedType) -> FreeMacroReservedType
+            where
+                FreeMacroReservedType2: Fn($generic, FreeMacroReservedType) -> FreeMacroReservedType + 'free_macro_reserved_lifetime,
+            {
+                fn __foldr_impl<'free_macro_reserved_lifetime, $($other_lifetimes,)* $generic $(,$other_generics)*, FreeMacroReservedType, FreeMacroReservedType2>(s : $name<$($other_lifetimes,)* $generic $(,$other_generics)*>, f : std::rc::Rc<FreeMacroReservedType2>, init: FreeMacroReservedType) -> FreeMacroReservedType where $f : $crate::higher::Foldable<'free_macro_reserved_lifetime, $name<$($other_lifetimes,)* $generic $(,$other_generics)*>>, FreeMacroReservedType2: Fn($generic, FreeMacroReservedType) -> FreeMacroReservedType + 'free_macro_reserved_lifetime{
+                    match s {
+                        $name::Pure(a) => f(a, init),
+                        $name::Free(fa) => $crate::higher::Foldable::foldr(*fa, move |x : $name<$($other_lifetimes,)* $generic $(,$other_generics)*>, acc| __foldr_impl(x, f.clone(), acc), init),
+                    }
+                }
+                __foldr_impl(self, std::rc::Rc::new(f), init)
+            }
+
+            fn foldr_ref<FreeMacroReservedType, FreeMacroReservedType2>(&'free_macro_reserved_lifetime self, f: FreeMacroReservedType2, init: FreeMacroReservedType) -> FreeMacroReservedType
+            where
+                $generic: 'free_macro_reserved_lifetime,
+                FreeMacroReservedType2: Fn(&'free_macro_reserved_lifetime $generic, FreeMacroReservedType) -> FreeMacroReservedType + 'free_macro_reserved_lifetime,
+            {
+                fn __foldr_ref_impl<'free_macro_reserved_lifetime, $($other_lifetimes,)* $generic $(,$other_generics)*, FreeMacroReservedType, FreeMacroReservedType2>(s : &'free_macro_reserved_lifetime $name<$($other_lifetimes,)* $generic $(,$other_generics)*>, f : std::rc::Rc<FreeMacroReservedType2>, init: FreeMacroReservedType) -> FreeMacroReservedType where $generic: 'free_macro_reserved_lifetime, $f : $crate::higher::Foldable<'free_macro_reserved_lifetime, $name<$($other_lifetimes,)* $generic $(,$other_generics)*>>, FreeMacroReservedType2: Fn(&'free_macro_reserved_lifetime $generic, FreeMacroReservedType) -> FreeMacroReservedType + 'free_macro_reserved_lifetime{
+                    match s {
+                        $name::Pure(a) => f(a, init),
+                        $name::Free(fa) => $crate::higher::Foldable::foldr_ref(&**fa, move |x : &'free_macro_reserved_lifetime $name<$($other_lifetimes,)* $generic $(,$other_generics)*>, acc| __foldr_ref_impl(x, f.clone(), acc), init),
+                    }
+                }
+                __foldr_ref_impl(self, std::rc::Rc::new(f), init)
+            }
+
+            fn foldl<FreeMacroReservedType, FreeMacroReservedType2>(self, f: FreeMacroReservedType2, init: FreeMacroReservedType) -> FreeMacroReservedType
+            where
+                FreeMacroReservedType2: Fn(FreeMacroReservedType, $generic) -> FreeMacroReservedType + 'free_macro_reserved_lifetime,
+            {
+                fn __foldl_impl<'free_macro_reserved_lifetime, $($other_lifetimes,)* $generic $(,$other_generics)*, FreeMacroReservedType, FreeMacroReservedType2>(s : $name<$($other_lifetimes,)* $generic $(,$other_generics)*>, f : std::rc::Rc<FreeMacroReservedType2>, init: FreeMacroReservedType) -> FreeMacroReservedType where $f : $crate::higher::Foldable<'free_macro_reserved_lifetime, $name<$($other_lifetimes,)* $generic $(,$other_generics)*>>, FreeMacroReservedType2: Fn(FreeMacroReservedType, $generic) -> FreeMacroReservedType + 'free_macro_reserved_lifetime{
+                    match s {
+                        $name::Pure(a) => f(init, a),
+                        $name::Free(fa) => $crate::higher::Foldable::foldl(*fa, move |acc, x : $name<$($other_lifetimes,)* $generic $(,$other_generics)*>| __foldl_impl(x, f.clone(), acc), init),
+                    }
+                }
+                __foldl_impl(self, std::rc::Rc::new(f), init)
+            }
+
+            fn foldl_ref<FreeMacroReservedType, FreeMacroReservedType2>(&'free_macro_reserved_lifetime self, f: FreeMacroReservedType2, init: FreeMacroReservedType) -> FreeMacroReservedType
+            where
+                $generic: 'free_macro_reserved_lifetime,
+                FreeMacroReservedType2: Fn(FreeMacroReservedType, &'free_macro_reserved_lifetime $generic) -> FreeMacroReservedType + 'free_macro_reserved_lifetime,
+            {
+                fn __foldl_ref_impl<'free_macro_reserved_lifetime, $($other_lifetimes,)* $generic $(,$other_generics)*, FreeMacroReservedType, FreeMacroReservedType2>(s : &'free_macro_reserved_lifetime $name<$($other_lifetimes,)* $generic $(,$other_generics)*>, f : std::rc::Rc<FreeMacroReservedType2>, init: FreeMacroReservedType) -> FreeMacroReservedType where $generic: 'free_macro_reserved_lifetime, $f : $crate::higher::Foldable<'free_macro_reserved_lifetime, $name<$($other_lifetimes,)* $generic $(,$other_generics)*>>, FreeMacroReservedType2: Fn(FreeMacroReservedType, &'free_macro_reserved_lifetime $generic) -> FreeMacroReservedType + 'free_macro_reserved_lifetime{
+                    match s {
+                        $name::Pure(a) => f(init, a),
+                        $name::Free(fa) => $crate::higher::Foldable::foldl_ref(&**fa, move |acc, x : &'free_macro_reserved_lifetime $name<$($other_lifetimes,)* $generic $(,$other_generics)*>| __foldl_ref_impl(x, f.clone(), acc), init),
+                    }
+                }
+                __foldl_ref_impl(self, std::rc::Rc::new(f), init)
+            }
+
+            fn fold_map<FreeMacroReservedType2, FreeMacroReservedType>(self, f: FreeMacroReservedType2) -> FreeMacroReservedType
+            where
+                FreeMacroReservedType2: Fn($generic) -> FreeMacroReservedType + 'free_macro_reserved_lifetime,
+                FreeMacroReservedType: $crate::higher::Monoid,
+            {
+                fn __fold_map_impl<'free_macro_reserved_lifetime, $($other_lifetimes,)* $generic $(,$other_generics)*, FreeMacroReservedType, FreeMacroReservedType2>(s : $name<$($other_lifetimes,)* $generic $(,$other_generics)*>, f : std::rc::Rc<FreeMacroReservedType2>) -> FreeMacroReservedType where $f : $crate::higher::Foldable<'free_macro_reserved_lifetime, $name<$($other_lifetimes,)* $generic $(,$other_generics)*>>, FreeMacroReservedType2: Fn($generic) -> FreeMacroReservedType + 'free_macro_reserved_lifetime, FreeMacroReservedType: $crate::higher::Monoid{
+                    match s {
+                        $name::Pure(a) => f(a),
+                        $name::Free(fa) => $crate::higher::Foldable::fold_map(*fa, move |x : $name<$($other_lifetimes,)* $generic $(,$other_generics)*>| __fold_map_impl(x, f.clone())),
+                    }
+                }
+                __fold_map_impl(self, std::rc::Rc::new(f))
+            }
+        }
     };
 }
 
+//modules that use the free!() macro need to come after it.
+pub mod collections;
+pub mod control;
+mod do_notation;
+pub mod effects;
+pub mod interpret;
+pub mod prng;
+pub mod render;
+#[cfg(test)]
+mod test_support;
+#[cfg(feature = "testing")]
+pub mod testing;
+
 #[cfg(test)]
 mod free_monad_tests {
     use higher::{apply::ApplyFn, Apply, Bind, Functor, Pure};
 
-    use super::free;
-
     free!(FreeVec<A>, Vec<FreeVec<A>>);
 
     #[test]
@@ -388,6 +930,31 @@ mod free_monad_tests {
         assert_eq!(v, vec![1, 2, 3]);
     }
 
+    #[test]
+    fn test_inspection_no_lifetime() {
+        let free = FreeVec::lift_f(vec![1, 2]);
+        assert!(free.is_free());
+        assert!(!free.is_pure());
+        assert!(free.as_pure().is_none());
+        assert_eq!(free.as_free().map(Vec::len), Some(2));
+        assert!(matches!(
+            free.peel().map_err(Vec::as_slice),
+            Err([FreeVec::Pure(1), FreeVec::Pure(2)])
+        ));
+        let free = free.map_layer(|mut v| {
+            v.reverse();
+            v
+        });
+        let layer = free.resume().unwrap_err();
+        let values: Vec<_> = layer.into_iter().filter_map(FreeVec::into_pure).collect();
+        assert_eq!(values, vec![2, 1]);
+
+        let pure = FreeVec::Pure(7).map_layer(|_| unreachable!());
+        assert!(pure.is_pure());
+        assert_eq!(pure.as_pure(), Some(&7));
+        assert_eq!(pure.resume().ok(), Some(7));
+    }
+
     #[test]
     fn test_pure_no_lifetime() {
         let f = FreeVec::pure(3);
@@ -573,7 +1140,7 @@ mod free_monad_tests {
         let h = |x: u32| Conti::<u32, u32>(Rc::new(move |a| x + a), Rc::new(move |a| x * a + 12));
 
         let v1 = (test3.clone().bind(g)).bind(h);
-        let v2 = test3.bind(|a| (g(a).bind(h)));
+        let v2 = test3.bind(|a| g(a).bind(h));
         assert_eq!((v1.0)(37), (v2.0)(37));
         assert_eq!((v1.1)(41), (v2.1)(41));
 
@@ -614,6 +1181,32 @@ mod free_monad_tests {
         assert_eq!((r.1)(4), 9);
     }
 
+    #[test]
+    fn test_inspection_lifetime() {
+        let f = FreeConti::lift_f(Conti(
+            Rc::new((|x| x * 2) as fn(u32) -> u32),
+            Rc::new((|x| x + 5) as fn(u32) -> u32),
+        ));
+        assert!(f.is_free());
+        assert!(f.into_pure().is_none());
+        let f = FreeConti::lift_f(Conti(
+            Rc::new((|x| x * 2) as fn(u32) -> u32),
+            Rc::new((|x| x + 5) as fn(u32) -> u32),
+        ));
+        let f = f.map_layer(|c| Conti(c.1, c.0));
+        match f.resume() {
+            Ok(_) => unreachable!(),
+            Err(layer) => {
+                assert_eq!((layer.0)(4).into_pure(), Some(9));
+                assert_eq!((layer.1)(4).as_pure(), Some(&8));
+            }
+        }
+        let p: FreeConti<_, ()> = FreeConti::Pure(3u32);
+        assert!(p.is_pure());
+        assert!(p.as_free().is_none());
+        assert!(matches!(p.peel(), Ok(3)));
+    }
+
     #[test]
     fn test_fmap_lifetime() {
         let functor = Conti(
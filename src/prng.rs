@@ -0,0 +1,105 @@
+//! A tiny deterministic pseudo random number generator.
+//!
+//! This is *not* a cryptographically secure random number generator, and it is not meant to be a replacement for the `rand` crate.
+//! It exists so that everything in this crate that needs randomness (like the law checks in the `testing` module) is
+//! reproducible from a single seed, without pulling in any dependencies.
+//!
+//! The algorithm is [SplitMix64](https://prng.di.unimi.it/splitmix64.c).
+
+/// The `SplitMix64` pseudo random number generator. Same seed, same sequence, on every platform.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    /// Creates a new generator from the given seed.
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+
+    /// Returns the next pseudo random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a pseudo random number in the range `0..bound`, without modulo bias.
+    ///
+    /// # Panics
+    /// Panics if `bound` is zero.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        assert!(bound > 0, "SplitMix64::below() called with an empty range");
+        //rejection sampling: throw away the incomplete bucket at the top.
+        let zone = u64::MAX - (u64::MAX - bound + 1) % bound;
+        loop {
+            let r = self.next_u64();
+            if r <= zone {
+                return r % bound;
+            }
+        }
+    }
+
+    /// Returns a pseudo random index into a collection of length `len`.
+    ///
+    /// # Panics
+    /// Panics if `len` is zero.
+    pub fn index(&mut self, len: usize) -> usize {
+        //usize is at most 64 bits wide on all supported platforms, so neither conversion can fail.
+        usize::try_from(self.below(len as u64)).unwrap()
+    }
+
+    /// Returns a pseudo random floating point number in the range `0.0..1.0`.
+    pub fn next_f64(&mut self) -> f64 {
+        //53 bits of mantissa. The shifted value always fits, so the cast is exact.
+        #[allow(clippy::cast_precision_loss)]
+        let mantissa = (self.next_u64() >> 11) as f64;
+        mantissa / 9_007_199_254_740_992.0 //2^53
+    }
+
+    /// Returns `true` with the given probability.
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.next_f64() < probability
+    }
+}
+
+#[cfg(test)]
+mod prng_tests {
+    use super::SplitMix64;
+
+    #[test]
+    fn test_reference_values() {
+        //first outputs of the reference implementation for seed 1234567.
+        let mut rng = SplitMix64::new(1_234_567);
+        assert_eq!(rng.next_u64(), 6_457_827_717_110_365_317);
+        assert_eq!(rng.next_u64(), 3_203_168_211_198_807_973);
+        assert_eq!(rng.next_u64(), 9_817_491_932_198_370_423);
+    }
+
+    #[test]
+    fn test_ranges() {
+        let mut rng = SplitMix64::new(42);
+        for _ in 0..1000 {
+            assert!(rng.below(7) < 7);
+            assert!(rng.index(3) < 3);
+            let f = rng.next_f64();
+            assert!((0.0..1.0).contains(&f));
+        }
+        assert_eq!(rng.below(1), 0);
+        assert!(!rng.chance(0.0));
+        assert!(rng.chance(1.0));
+    }
+
+    #[test]
+    fn test_determinism() {
+        let mut a = SplitMix64::new(7);
+        let mut b = a.clone();
+        let xs: Vec<_> = (0..10).map(|_| a.below(100)).collect();
+        let ys: Vec<_> = (0..10).map(|_| b.below(100)).collect();
+        assert_eq!(xs, ys);
+    }
+}
@@ -0,0 +1,294 @@
+//! Renders Free Monads as [Graphviz](https://graphviz.org/) DOT graphs or as indented text outlines.
+//!
+//! This is mostly a debugging aid. The renderers walk the Free Monad by reference, and ask each [`Functor`][higher::Functor] layer
+//! to describe itself via the [`DescribeLayer`] trait. `Pure` nodes are rendered using their [`Debug`] output.
+//!
+//! Layers that hold their continuation in a closure (as is typical for eDSLs) cannot be entered without actually
+//! feeding them an input. The renderers stop there and draw a placeholder instead. They also stop at a configurable depth,
+//! because Free Monads over data-holding Functors can get rather large.
+//!
+//! ```
+//! # #[macro_use] extern crate higher_free_macro;
+//! # use higher_free_macro::higher::*;
+//! use higher_free_macro::render::{to_dot, to_outline};
+//!
+//! free!(FreeVec<A>, Vec<FreeVec<A>>);
+//! fn main() {
+//!     let tree = FreeVec::lift_f(vec![1, 2]).bind(|x| if x == 1 { FreeVec::Pure(x) } else { FreeVec::lift_f(vec![3, 4]) });
+//!     assert_eq!(to_outline(&tree, None), "Free: 2 branches\n  Pure: 1\n  Free: 2 branches\n    Pure: 3\n    Pure: 4\n");
+//!     assert!(to_dot(&tree, Some(1)).contains("depth limit"));
+//! }
+//! ```
+
+use std::fmt::{Debug, Write};
+
+use crate::MonadFree;
+
+/// Describes a single [`Functor`][higher::Functor] layer of a Free Monad `M` for the renderers in this module.
+///
+/// The trait is implemented on the layer type itself (so, for a `FreeSausageRoll`, on `SausageRoll<FreeSausageRoll>`).
+/// Implementations for [`Vec`] and [`Option`] are provided by this crate.
+pub trait DescribeLayer<M> {
+    /// A short human-readable description of this layer, for instance the name of the eDSL instruction.
+    fn describe(&self) -> String;
+
+    /// The sub-programs that are directly reachable from this layer, from left to right.
+    fn children(&self) -> Vec<&M>;
+
+    /// Whether this layer holds continuations that cannot be entered without an input (usually closures).
+    /// Such continuations are rendered as placeholders.
+    fn has_opaque_continuation(&self) -> bool {
+        false
+    }
+}
+
+impl<M> DescribeLayer<M> for Vec<M> {
+    fn describe(&self) -> String {
+        format!("{} branches", self.len())
+    }
+
+    fn children(&self) -> Vec<&M> {
+        self.iter().collect()
+    }
+}
+
+impl<M> DescribeLayer<M> for Option<M> {
+    fn describe(&self) -> String {
+        String::from(if self.is_some() { "Some" } else { "None" })
+    }
+
+    fn children(&self) -> Vec<&M> {
+        self.iter().collect()
+    }
+}
+
+/// What the renderers found at a given position of the tree.
+enum Node<'r, 'a, M: MonadFree<'a>> {
+    Pure(&'r M::Value),
+    Free(&'r M::Functor),
+    Opaque,
+    DepthLimit,
+}
+
+fn classify<'r, 'a, M>(program: &'r M, depth: usize, max_depth: Option<usize>) -> Node<'r, 'a, M>
+where
+    M: MonadFree<'a>,
+{
+    match program.peel() {
+        Ok(value) => Node::Pure(value),
+        Err(_) if matches!(max_depth, Some(max) if depth >= max) => Node::DepthLimit,
+        Err(layer) => Node::Free(layer),
+    }
+}
+
+/// Renders the Free Monad as an indented outline, one node per line, children indented by two spaces.
+///
+/// `max_depth` limits how many `Free` layers are entered. `None` means no limit.
+#[must_use]
+pub fn to_outline<'a, M>(program: &M, max_depth: Option<usize>) -> String
+where
+    M: MonadFree<'a>,
+    M::Value: Debug,
+    M::Functor: DescribeLayer<M>,
+{
+    let mut result = String::new();
+    //explicit stack instead of recursion, Free Monads can get deep.
+    let mut stack = vec![(0usize, Some(program))];
+    while let Some((depth, program)) = stack.pop() {
+        let indent = "  ".repeat(depth);
+        let node = program.map_or(Node::Opaque, |p| classify(p, depth, max_depth));
+        match node {
+            Node::Pure(value) => {
+                let _ = writeln!(result, "{indent}Pure: {value:?}");
+            }
+            Node::Free(layer) => {
+                let _ = writeln!(result, "{indent}Free: {}", layer.describe());
+                if layer.has_opaque_continuation() {
+                    stack.push((depth + 1, None));
+                }
+                stack.extend(
+                    layer
+                        .children()
+                        .into_iter()
+                        .rev()
+                        .map(|c| (depth + 1, Some(c))),
+                );
+            }
+            Node::Opaque => {
+                let _ = writeln!(result, "{indent}<continuation>");
+            }
+            Node::DepthLimit => {
+                let _ = writeln!(result, "{indent}... (depth limit)");
+            }
+        }
+    }
+    result
+}
+
+/// Renders the Free Monad as a Graphviz DOT `digraph`.
+///
+/// `Free` nodes are drawn as ellipses, `Pure` nodes as boxes, and placeholders for continuations or the depth limit
+/// as plain text. `max_depth` limits how many `Free` layers are entered. `None` means no limit.
+#[must_use]
+pub fn to_dot<'a, M>(program: &M, max_depth: Option<usize>) -> String
+where
+    M: MonadFree<'a>,
+    M::Value: Debug,
+    M::Functor: DescribeLayer<M>,
+{
+    let mut nodes = String::new();
+    let mut edges = String::new();
+    let mut next_id = 1usize;
+    let mut stack = vec![(0usize, 0usize, Some(program))];
+    while let Some((id, depth, program)) = stack.pop() {
+        let node = program.map_or(Node::Opaque, |p| classify(p, depth, max_depth));
+        let (shape, label) = match node {
+            Node::Pure(value) => ("box", format!("{value:?}")),
+            Node::Free(layer) => {
+                let mut children: Vec<_> = layer.children().into_iter().map(Some).collect();
+                if layer.has_opaque_continuation() {
+                    children.push(None);
+                }
+                let first_child_id = next_id;
+                next_id += children.len();
+                for (child_id, child) in (first_child_id..next_id).zip(children).rev() {
+                    stack.push((child_id, depth + 1, child));
+                }
+                for child_id in first_child_id..next_id {
+                    let _ = writeln!(edges, "    n{id} -> n{child_id};");
+                }
+                ("ellipse", layer.describe())
+            }
+            Node::Opaque => ("plaintext", String::from("<continuation>")),
+            Node::DepthLimit => ("plaintext", String::from("... (depth limit)")),
+        };
+        let _ = writeln!(
+            nodes,
+            "    n{id} [shape={shape}, label=\"{}\"];",
+            escape(&label)
+        );
+    }
+    format!("digraph program {{\n{nodes}{edges}}}\n")
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod render_tests {
+    use super::{to_dot, to_outline, DescribeLayer};
+    use crate::effects::console::{choose_from, print_line, FreeConsole};
+    use crate::free_do;
+    use higher::{Bind, Functor};
+    use std::rc::Rc;
+
+    free!(FreeVec<A>, Vec<FreeVec<A>>);
+
+    fn tree() -> FreeVec<&'static str> {
+        FreeVec::lift_f(vec!["a", "b"]).bind(|x| {
+            if x == "a" {
+                FreeVec::Pure("say \"a\"")
+            } else {
+                FreeVec::lift_f(vec!["c", "d"])
+            }
+        })
+    }
+
+    #[test]
+    fn test_outline_vec() {
+        assert_eq!(
+            to_outline(&tree(), None),
+            "Free: 2 branches\n  Pure: \"say \\\"a\\\"\"\n  Free: 2 branches\n    Pure: \"c\"\n    Pure: \"d\"\n"
+        );
+        assert_eq!(
+            to_outline(&tree(), Some(1)),
+            "Free: 2 branches\n  Pure: \"say \\\"a\\\"\"\n  ... (depth limit)\n"
+        );
+    }
+
+    #[test]
+    fn test_dot_vec() {
+        let expected = r#"digraph program {
+    n0 [shape=ellipse, label="2 branches"];
+    n1 [shape=box, label="\"say \\\"a\\\"\""];
+    n2 [shape=ellipse, label="2 branches"];
+    n3 [shape=box, label="\"c\""];
+    n4 [shape=box, label="\"d\""];
+    n0 -> n1;
+    n0 -> n2;
+    n2 -> n3;
+    n2 -> n4;
+}
+"#;
+        assert_eq!(to_dot(&tree(), None), expected);
+    }
+
+    #[derive(Clone)]
+    enum Command<'a, A> {
+        Say(String, A),
+        Ask(Rc<dyn Fn(bool) -> A + 'a>),
+    }
+
+    impl<'a, A: 'a> Functor<'a, A> for Command<'a, A> {
+        type Target<T> = Command<'a, T>;
+
+        fn fmap<B, F>(self, f: F) -> Self::Target<B>
+        where
+            F: Fn(A) -> B + 'a,
+        {
+            match self {
+                Command::Say(s, next) => Command::Say(s, f(next)),
+                Command::Ask(next) => Command::Ask(Rc::new(move |x| f(next(x)))),
+            }
+        }
+    }
+
+    free!(<'a>, FreeCommand<'a, A>, Command<'a, FreeCommand<'a, A>>);
+
+    impl<'a, A> DescribeLayer<FreeCommand<'a, A>> for Command<'a, FreeCommand<'a, A>> {
+        fn describe(&self) -> String {
+            match self {
+                Command::Say(s, _) => format!("Say {s}"),
+                Command::Ask(_) => String::from("Ask"),
+            }
+        }
+
+        fn children(&self) -> Vec<&FreeCommand<'a, A>> {
+            match self {
+                Command::Say(_, next) => vec![next],
+                Command::Ask(_) => Vec::new(),
+            }
+        }
+
+        fn has_opaque_continuation(&self) -> bool {
+            matches!(self, Command::Ask(_))
+        }
+    }
+
+    #[test]
+    fn test_outline_continuation() {
+        let program = FreeCommand::lift_f(Command::Say(String::from("hi"), ()))
+            .bind(|()| FreeCommand::lift_f(Command::Ask(Rc::new(|b| b))));
+        assert_eq!(
+            to_outline(&program, None),
+            "Free: Say hi\n  Free: Ask\n    <continuation>\n"
+        );
+        assert!(to_dot(&program, None).contains("n2 [shape=plaintext, label=\"<continuation>\"];"));
+    }
+
+    #[test]
+    fn test_outline_console() {
+        let program: FreeConsole<usize> = free_do! {
+            print_line("Tea?");
+            choose_from(vec!["yes", "no"])
+        };
+        assert_eq!(
+            to_outline(&program, None),
+            "Free: print \"Tea?\"\n  Free: choose from [\"yes\", \"no\"]\n    <continuation>\n"
+        );
+    }
+}
@@ -0,0 +1,54 @@
+//! Fixtures shared by the unit tests: A small effect that reads and writes numbers, and a runner for it.
+
+use higher::Functor;
+use std::rc::Rc;
+
+#[derive(Clone)]
+pub enum Io<'a, A> {
+    Read(Rc<dyn Fn(u32) -> A + 'a>),
+    Write(u32, A),
+}
+
+impl<'a, A: 'a> Functor<'a, A> for Io<'a, A> {
+    type Target<T> = Io<'a, T>;
+
+    fn fmap<B, F>(self, f: F) -> Self::Target<B>
+    where
+        F: Fn(A) -> B + 'a,
+    {
+        match self {
+            Io::Read(next) => Io::Read(Rc::new(move |x| f(next(x)))),
+            Io::Write(x, next) => Io::Write(x, f(next)),
+        }
+    }
+}
+
+free!(<'a>, pub FreeIo<'a, A>, Io<'a, FreeIo<'a, A>>);
+
+pub fn read<'a>() -> FreeIo<'a, u32> {
+    FreeIo::lift_f(Io::Read(Rc::new(|x| x)))
+}
+
+pub fn write<'a>(x: u32) -> FreeIo<'a, ()> {
+    FreeIo::lift_f(Io::Write(x, ()))
+}
+
+//Feeds 1, 2, 3,... to the program, collects written values, and gives up after `max_steps` layers.
+pub fn run<'a, A: 'a>(mut program: FreeIo<'a, A>, max_steps: usize) -> (Vec<u32>, Option<A>) {
+    let mut written = Vec::new();
+    let mut input = 0;
+    for _ in 0..max_steps {
+        match program.resume() {
+            Ok(a) => return (written, Some(a)),
+            Err(Io::Read(next)) => {
+                input += 1;
+                program = next(input);
+            }
+            Err(Io::Write(x, next)) => {
+                written.push(x);
+                program = next;
+            }
+        }
+    }
+    (written, None)
+}
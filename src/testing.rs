@@ -0,0 +1,374 @@
+//! Property based checks of the Functor, Applicative and Monad laws for Free Monads. Only available with the `testing` feature.
+//!
+//! The Free Monad itself is lawful by construction, but only if the [`Functor`] it is based on is lawful too.
+//! Since hand-written Functors are a typical source of bugs, this module allows to check the laws for a given `free!()` type
+//! against randomly generated programs.
+//!
+//! To use it, implement [`FreeLaws`] for a marker type. The implementation tells the checks how to generate values,
+//! programs and functions, and how to observe a program. Observing is needed because Free Monads over Functors with
+//! continuation functions cannot be compared directly. A typical observation is to run the program with a fixed interpreter.
+//! The [`free_laws!`][crate::free_laws] macro then generates one `#[test]` per law.
+//!
+//! All randomness comes from the [`SplitMix64`] generator in this crate, seeded by the caller, so failures are reproducible.
+//!
+//! ```
+//! # #[macro_use] extern crate higher_free_macro;
+//! # use higher_free_macro::higher::*;
+//! use higher_free_macro::higher::apply::ApplyFn;
+//! use higher_free_macro::prng::SplitMix64;
+//! use higher_free_macro::testing::{check_laws, FreeLaws};
+//! use std::rc::Rc;
+//!
+//! free!(FreeVec<A>, Vec<FreeVec<A>>);
+//!
+//! struct VecLaws;
+//! impl<'a> FreeLaws<'a> for VecLaws {
+//!     type Value = u32;
+//!     type Program = FreeVec<u32>;
+//!     type FnProgram = FreeVec<ApplyFn<'a, u32, u32>>;
+//!     type Observation = Vec<u32>;
+//!
+//!     fn value(rng: &mut SplitMix64) -> u32 {
+//!         rng.next_u64() as u32 % 100
+//!     }
+//!     fn program(rng: &mut SplitMix64) -> FreeVec<u32> {
+//!         let values = (0..rng.below(4)).map(|_| Self::value(rng)).collect();
+//!         FreeVec::lift_f(values)
+//!     }
+//!     fn function(rng: &mut SplitMix64) -> Rc<dyn Fn(u32) -> u32 + 'a> {
+//!         let k = Self::value(rng);
+//!         Rc::new(move |x| x.wrapping_mul(3).wrapping_add(k))
+//!     }
+//!     fn kleisli(rng: &mut SplitMix64) -> Rc<dyn Fn(u32) -> FreeVec<u32> + 'a> {
+//!         let k = Self::value(rng);
+//!         Rc::new(move |x| if x % 2 == 0 { FreeVec::Pure(x + k) } else { FreeVec::lift_f(vec![x, k]) })
+//!     }
+//!     fn observe(program: FreeVec<u32>) -> Vec<u32> {
+//!         //the leaves alone would not be enough, the shape of the tree matters too.
+//!         fn walk(p: FreeVec<u32>, out: &mut Vec<u32>) {
+//!             match p {
+//!                 FreeVec::Pure(x) => out.push(x),
+//!                 FreeVec::Free(v) => {
+//!                     out.push(u32::MAX - v.len() as u32);
+//!                     v.into_iter().for_each(|c| walk(c, out));
+//!                 }
+//!             }
+//!         }
+//!         let mut out = Vec::new();
+//!         walk(program, &mut out);
+//!         out
+//!     }
+//! }
+//!
+//! fn main() {
+//!     check_laws::<VecLaws>(0xDEC0DE, 50).unwrap();
+//! }
+//! ```
+
+use std::fmt::{Debug, Display};
+use std::rc::Rc;
+
+use higher::apply::ApplyFn;
+use higher::{Apply, Bind, Functor, Pure};
+
+pub use crate::prng::SplitMix64;
+
+/// Describes how to generate test data for, and how to observe, a Free Monad type whose laws should be checked.
+///
+/// To keep the number of types manageable, all laws are checked with a single value type, [`Self::Value`][FreeLaws::Value].
+/// All generated functions map `Value` to `Value` (or to a program yielding `Value`).
+pub trait FreeLaws<'a> {
+    /// The type of the values in the programs.
+    type Value: Clone + 'a;
+    /// The Free Monad under test, yielding [`Self::Value`][FreeLaws::Value].
+    type Program: Clone
+        + 'a
+        + Pure<Self::Value>
+        + Functor<'a, Self::Value, Target<Self::Value> = Self::Program>
+        + Bind<'a, Self::Value, Target<Self::Value> = Self::Program>
+        + Bind<'a, Self::Value, Target<ApplyFn<'a, Self::Value, Self::Value>> = Self::FnProgram>
+        + Apply<'a, Self::Value, Target<Self::Value> = Self::Program>
+        + Apply<'a, Self::Value, Target<ApplyFn<'a, Self::Value, Self::Value>> = Self::FnProgram>;
+    /// The same Free Monad, but yielding functions. For a `FreeVec<A>` this would be `FreeVec<ApplyFn<'a, A, A>>`.
+    type FnProgram: Pure<ApplyFn<'a, Self::Value, Self::Value>>
+        + Bind<'a, ApplyFn<'a, Self::Value, Self::Value>, Target<Self::Value> = Self::Program>;
+    /// What the checks compare. Programs are equal if their observations are equal.
+    type Observation: PartialEq + Debug;
+
+    /// Generates a value.
+    fn value(rng: &mut SplitMix64) -> Self::Value;
+    /// Generates a program. Should generate a mix of `Pure` and `Free` nodes.
+    fn program(rng: &mut SplitMix64) -> Self::Program;
+    /// Generates a function from values to values.
+    fn function(rng: &mut SplitMix64) -> Rc<dyn Fn(Self::Value) -> Self::Value + 'a>;
+    /// Generates a function from values to programs, as used by `bind()`.
+    fn kleisli(rng: &mut SplitMix64) -> Rc<dyn Fn(Self::Value) -> Self::Program + 'a>;
+    /// Observes a program, for instance by interpreting it.
+    fn observe(program: Self::Program) -> Self::Observation;
+}
+
+/// A failed law check. Contains everything needed to reproduce it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LawViolation {
+    /// The name of the violated law.
+    pub law: &'static str,
+    /// The seed the check was started with.
+    pub seed: u64,
+    /// The index of the failing case.
+    pub case: usize,
+    /// Debug output of the observation of the left hand side of the law.
+    pub left: String,
+    /// Debug output of the observation of the right hand side of the law.
+    pub right: String,
+}
+
+impl Display for LawViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} violated in case {} (seed {:#x}):\n  left: {}\n right: {}",
+            self.law, self.case, self.seed, self.left, self.right
+        )
+    }
+}
+
+impl std::error::Error for LawViolation {}
+
+/// The signature of all law checks in this module: seed and number of cases in, first violation out.
+pub type LawCheck = fn(u64, usize) -> Result<(), LawViolation>;
+
+fn check<'a, L, F>(law: &'static str, seed: u64, cases: usize, f: F) -> Result<(), LawViolation>
+where
+    L: FreeLaws<'a>,
+    F: Fn(&mut SplitMix64) -> (L::Program, L::Program),
+{
+    let mut rng = SplitMix64::new(seed);
+    for case in 0..cases {
+        let (left, right) = f(&mut rng);
+        let (left, right) = (L::observe(left), L::observe(right));
+        if left != right {
+            return Err(LawViolation {
+                law,
+                seed,
+                case,
+                left: format!("{left:?}"),
+                right: format!("{right:?}"),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// `m.fmap(id) == m`
+///
+/// # Errors
+/// Returns the first case that violates the law.
+pub fn functor_identity<'a, L: FreeLaws<'a>>(seed: u64, cases: usize) -> Result<(), LawViolation> {
+    check::<L, _>("functor identity", seed, cases, |rng| {
+        let m = L::program(rng);
+        (m.clone().fmap(|x| x), m)
+    })
+}
+
+/// `m.fmap(f).fmap(g) == m.fmap(|x| g(f(x)))`
+///
+/// # Errors
+/// Returns the first case that violates the law.
+pub fn functor_composition<'a, L: FreeLaws<'a>>(
+    seed: u64,
+    cases: usize,
+) -> Result<(), LawViolation> {
+    check::<L, _>("functor composition", seed, cases, |rng| {
+        let m = L::program(rng);
+        let (f, g) = (L::function(rng), L::function(rng));
+        let (f2, g2) = (f.clone(), g.clone());
+        (
+            m.clone().fmap(move |x| f(x)).fmap(move |x| g(x)),
+            m.fmap(move |x| g2(f2(x))),
+        )
+    })
+}
+
+/// `m.apply(pure(id)) == m`
+///
+/// # Errors
+/// Returns the first case that violates the law.
+pub fn applicative_identity<'a, L: FreeLaws<'a>>(
+    seed: u64,
+    cases: usize,
+) -> Result<(), LawViolation> {
+    check::<L, _>("applicative identity", seed, cases, |rng| {
+        let m = L::program(rng);
+        let id = L::FnProgram::pure(ApplyFn::from(|x| x));
+        (m.clone().apply(id), m)
+    })
+}
+
+/// `pure(a).apply(pure(f)) == pure(f(a))`
+///
+/// # Errors
+/// Returns the first case that violates the law.
+pub fn applicative_homomorphism<'a, L: FreeLaws<'a>>(
+    seed: u64,
+    cases: usize,
+) -> Result<(), LawViolation> {
+    check::<L, _>("applicative homomorphism", seed, cases, |rng| {
+        let a = L::value(rng);
+        let f = L::function(rng);
+        let right = L::Program::pure(f(a.clone()));
+        let f = L::FnProgram::pure(ApplyFn::from(move |x| f(x)));
+        (L::Program::pure(a).apply(f), right)
+    })
+}
+
+/// `m.apply(fs) == fs.bind(|f| m.fmap(f))`, meaning that `apply()` is consistent with `bind()`.
+///
+/// The functions in `fs` are generated from a generated program. Depending on the case, they either ignore their argument and
+/// return a value that depends on the position in `fs`, or they ignore the position and apply a generated function to the argument.
+///
+/// # Errors
+/// Returns the first case that violates the law.
+pub fn apply_consistent_with_bind<'a, L: FreeLaws<'a>>(
+    seed: u64,
+    cases: usize,
+) -> Result<(), LawViolation> {
+    check::<L, _>("apply consistent with bind", seed, cases, |rng| {
+        let m = L::program(rng);
+        let positions = L::program(rng);
+        let f = L::function(rng);
+        let ignore_argument = rng.chance(0.5);
+        let to_functions = move |p: L::Program| -> L::FnProgram {
+            let f = f.clone();
+            p.bind::<ApplyFn<'a, L::Value, L::Value>, _>(move |x| {
+                let f = f.clone();
+                L::FnProgram::pure(if ignore_argument {
+                    ApplyFn::from(move |_| f(x.clone()))
+                } else {
+                    ApplyFn::from(move |y| f(y))
+                })
+            })
+        };
+        let left = m.clone().apply(to_functions(positions.clone()));
+        let right = to_functions(positions)
+            .bind::<L::Value, _>(move |h| m.clone().fmap(move |x| h.apply(x)));
+        (left, right)
+    })
+}
+
+/// `pure(a).bind(k) == k(a)`
+///
+/// # Errors
+/// Returns the first case that violates the law.
+pub fn monad_left_identity<'a, L: FreeLaws<'a>>(
+    seed: u64,
+    cases: usize,
+) -> Result<(), LawViolation> {
+    check::<L, _>("monad left identity", seed, cases, |rng| {
+        let a = L::value(rng);
+        let k = L::kleisli(rng);
+        let right = k(a.clone());
+        (
+            L::Program::pure(a).bind::<L::Value, _>(move |x| k(x)),
+            right,
+        )
+    })
+}
+
+/// `m.bind(pure) == m`
+///
+/// # Errors
+/// Returns the first case that violates the law.
+pub fn monad_right_identity<'a, L: FreeLaws<'a>>(
+    seed: u64,
+    cases: usize,
+) -> Result<(), LawViolation> {
+    check::<L, _>("monad right identity", seed, cases, |rng| {
+        let m = L::program(rng);
+        (m.clone().bind::<L::Value, _>(L::Program::pure), m)
+    })
+}
+
+/// `m.bind(k).bind(h) == m.bind(|x| k(x).bind(h))`
+///
+/// # Errors
+/// Returns the first case that violates the law.
+pub fn monad_associativity<'a, L: FreeLaws<'a>>(
+    seed: u64,
+    cases: usize,
+) -> Result<(), LawViolation> {
+    check::<L, _>("monad associativity", seed, cases, |rng| {
+        let m = L::program(rng);
+        let (k, h) = (L::kleisli(rng), L::kleisli(rng));
+        let (k2, h2) = (k.clone(), h.clone());
+        let left = m
+            .clone()
+            .bind::<L::Value, _>(move |x| k(x))
+            .bind::<L::Value, _>(move |x| h(x));
+        let right = m.bind::<L::Value, _>(move |x| {
+            let h = h2.clone();
+            k2(x).bind::<L::Value, _>(move |y| h(y))
+        });
+        (left, right)
+    })
+}
+
+/// All law checks in this module, together with their names.
+#[must_use]
+pub fn all_laws<'a, L: FreeLaws<'a>>() -> [(&'static str, LawCheck); 8] {
+    [
+        ("functor_identity", functor_identity::<L>),
+        ("functor_composition", functor_composition::<L>),
+        ("applicative_identity", applicative_identity::<L>),
+        ("applicative_homomorphism", applicative_homomorphism::<L>),
+        (
+            "apply_consistent_with_bind",
+            apply_consistent_with_bind::<L>,
+        ),
+        ("monad_left_identity", monad_left_identity::<L>),
+        ("monad_right_identity", monad_right_identity::<L>),
+        ("monad_associativity", monad_associativity::<L>),
+    ]
+}
+
+/// Runs all law checks, each with `cases` generated cases.
+///
+/// # Errors
+/// Returns the first violation found.
+pub fn check_laws<'a, L: FreeLaws<'a>>(seed: u64, cases: usize) -> Result<(), LawViolation> {
+    all_laws::<L>()
+        .iter()
+        .try_for_each(|(_, law)| law(seed, cases))
+}
+
+/// Generates a module with one `#[test]` per law in the [`testing`][crate::testing] module. Only available with the `testing` feature.
+///
+/// The syntax is `free_laws!(mod module_name, LawsType)`, or `free_laws!(mod module_name, LawsType, seed, cases)` to override
+/// the default seed and number of generated cases per law. `LawsType` has to implement [`FreeLaws`][crate::testing::FreeLaws].
+/// The generated module imports everything from its parent module.
+///
+/// ```ignore
+/// free_laws!(mod free_vec_laws, VecLaws);
+/// ```
+#[macro_export]
+macro_rules! free_laws {
+    ($v:vis mod $module:ident, $laws:ty) => {
+        $crate::free_laws!($v mod $module, $laws, 0x5EED_F4EE, 100);
+    };
+    ($v:vis mod $module:ident, $laws:ty, $seed:expr, $cases:expr) => {
+        $v mod $module {
+            #[allow(unused_imports)]
+            use super::*;
+            $crate::free_laws!(@tests $laws, $seed, $cases, functor_identity, functor_composition, applicative_identity, applicative_homomorphism, apply_consistent_with_bind, monad_left_identity, monad_right_identity, monad_associativity);
+        }
+    };
+    (@tests $laws:ty, $seed:expr, $cases:expr, $($law:ident),*) => {
+        $(
+            #[test]
+            fn $law() {
+                if let Err(violation) = $crate::testing::$law::<$laws>($seed, $cases) {
+                    panic!("{}", violation);
+                }
+            }
+        )*
+    };
+}
@@ -0,0 +1,125 @@
+#![deny(clippy::pedantic)]
+#![deny(clippy::all)]
+//! Tests the `Foldable` implementation for Free Monads over `Foldable` Functors, and the leaf iterators that come with it.
+
+use higher::{Bind, Foldable, Functor};
+use higher_free_macro::free;
+use std::marker::PhantomData;
+
+free!(FreeVec<A>, Vec<FreeVec<A>>);
+
+fn tree() -> FreeVec<u32> {
+    FreeVec::lift_f(vec![1, 2, 3]).bind(|x| {
+        if x == 2 {
+            FreeVec::lift_f(vec![20, 21])
+        } else {
+            FreeVec::Pure(x)
+        }
+    })
+}
+
+#[test]
+fn test_fold_vec() {
+    assert_eq!(tree().foldl(|acc, x| acc + x, 0), 45);
+    assert_eq!(tree().foldr(|x, acc| acc * 100 + x, 0), 3_212_001);
+    assert_eq!(tree().foldl(|acc, x| acc * 100 + x, 0), 1_202_103);
+    assert_eq!(tree().fold_map(|x| vec![x]), vec![1, 20, 21, 3]);
+    let t = tree();
+    assert_eq!(t.foldr_ref(|x, acc| acc.max(*x), 0), 21);
+    assert_eq!(t.foldl_ref(|acc, x| acc.min(*x), u32::MAX), 1);
+}
+
+#[test]
+fn test_leaves_vec() {
+    let t = tree();
+    assert_eq!(t.leaves().copied().collect::<Vec<_>>(), vec![1, 20, 21, 3]);
+    assert_eq!(t.leaves().find(|x| **x > 10), Some(&20));
+    assert_eq!(t.into_leaves().map(u64::from).sum::<u64>(), 45);
+    assert_eq!(FreeVec::Pure(5).into_leaves().collect::<Vec<_>>(), vec![5]);
+    assert_eq!(FreeVec::<u8>::lift_f(vec![]).leaves().count(), 0);
+}
+
+#[test]
+fn test_leaves_lazy() {
+    //the leaves are walked with an explicit stack, so even a very deep tree does not overflow the call stack.
+    let mut deep = FreeVec::Pure(7);
+    for _ in 0..100_000 {
+        deep = FreeVec::Free(Box::new(vec![deep, FreeVec::Pure(1)]));
+    }
+    let mut leaves = deep.into_leaves();
+    assert_eq!(leaves.next(), Some(7));
+    assert_eq!(leaves.take(3).collect::<Vec<_>>(), vec![1, 1, 1]);
+}
+
+//A user-supplied foldable Functor, using the lifetime syntax of the macro.
+#[derive(Clone)]
+struct Pair<'a, A>(A, A, PhantomData<&'a ()>);
+
+impl<'a, A: 'a> Functor<'a, A> for Pair<'a, A> {
+    type Target<T> = Pair<'a, T>;
+
+    fn fmap<B, F>(self, f: F) -> Self::Target<B>
+    where
+        F: Fn(A) -> B + 'a,
+    {
+        Pair(f(self.0), f(self.1), PhantomData)
+    }
+}
+
+impl<'b, A> Foldable<'b, A> for Pair<'_, A> {
+    fn foldr<B, F>(self, f: F, init: B) -> B
+    where
+        F: Fn(A, B) -> B + 'b,
+    {
+        f(self.0, f(self.1, init))
+    }
+
+    fn foldr_ref<B, F>(&'b self, f: F, init: B) -> B
+    where
+        A: 'b,
+        F: Fn(&'b A, B) -> B + 'b,
+    {
+        f(&self.0, f(&self.1, init))
+    }
+
+    fn foldl<B, F>(self, f: F, init: B) -> B
+    where
+        F: Fn(B, A) -> B + 'b,
+    {
+        f(f(init, self.0), self.1)
+    }
+
+    fn foldl_ref<B, F>(&'b self, f: F, init: B) -> B
+    where
+        A: 'b,
+        F: Fn(B, &'b A) -> B + 'b,
+    {
+        f(f(init, &self.0), &self.1)
+    }
+
+    fn fold_map<F, M>(self, f: F) -> M
+    where
+        F: Fn(A) -> M + 'b,
+        M: higher::Monoid,
+    {
+        let l = f(self.0);
+        l.mappend(f(self.1))
+    }
+}
+
+free!(<'a>, FreePair<'a,A>, Pair<'a,FreePair<'a,A>>);
+
+#[test]
+fn test_fold_custom_functor() {
+    let free_pair = FreePair::lift_f(Pair(String::from("a"), String::from("b"), PhantomData))
+        .bind(|s| FreePair::lift_f(Pair(s.clone() + "1", s + "2", PhantomData)));
+    assert_eq!(
+        free_pair.leaves().cloned().collect::<Vec<_>>(),
+        vec!["a1", "a2", "b1", "b2"]
+    );
+    assert_eq!(
+        free_pair.clone().foldr(|s, acc| acc + &s, String::new()),
+        "b2b1a2a1"
+    );
+    assert_eq!(free_pair.fold_map(|s| s), "a1a2b1b2");
+}
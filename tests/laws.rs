@@ -0,0 +1,226 @@
+#![deny(clippy::pedantic)]
+#![deny(clippy::all)]
+//! Checks the Functor, Applicative and Monad laws for a couple of Free Monads, using the `testing` feature.
+
+use higher::{apply::ApplyFn, Functor};
+use higher_free_macro::prng::SplitMix64;
+use higher_free_macro::testing::{functor_identity, monad_associativity, FreeLaws};
+use higher_free_macro::{free, free_laws};
+use std::rc::Rc;
+
+free!(FreeVec<A>, Vec<FreeVec<A>>);
+
+//shape and leaves of the tree.
+fn observe_vec(program: FreeVec<u32>, out: &mut Vec<Option<u32>>) {
+    match program.resume() {
+        Ok(x) => out.push(Some(x)),
+        Err(v) => {
+            out.push(None);
+            for c in v {
+                observe_vec(c, out);
+            }
+            out.push(None);
+        }
+    }
+}
+
+fn small(rng: &mut SplitMix64) -> u32 {
+    u32::try_from(rng.below(100)).unwrap()
+}
+
+struct VecLaws;
+
+impl<'a> FreeLaws<'a> for VecLaws {
+    type Value = u32;
+    type Program = FreeVec<u32>;
+    type FnProgram = FreeVec<ApplyFn<'a, u32, u32>>;
+    type Observation = Vec<Option<u32>>;
+
+    fn value(rng: &mut SplitMix64) -> u32 {
+        small(rng)
+    }
+
+    fn program(rng: &mut SplitMix64) -> FreeVec<u32> {
+        if rng.chance(0.2) {
+            FreeVec::Pure(small(rng))
+        } else {
+            let children = (0..rng.below(3))
+                .map(|_| {
+                    if rng.chance(0.3) {
+                        Self::program(rng)
+                    } else {
+                        FreeVec::Pure(small(rng))
+                    }
+                })
+                .collect();
+            FreeVec::Free(Box::new(children))
+        }
+    }
+
+    fn function(rng: &mut SplitMix64) -> Rc<dyn Fn(u32) -> u32 + 'a> {
+        let k = small(rng);
+        Rc::new(move |x| x.wrapping_mul(7).wrapping_add(k))
+    }
+
+    fn kleisli(rng: &mut SplitMix64) -> Rc<dyn Fn(u32) -> FreeVec<u32> + 'a> {
+        let k = small(rng);
+        Rc::new(move |x| {
+            if x % 3 == 0 {
+                FreeVec::Pure(x + k)
+            } else {
+                FreeVec::lift_f(vec![x, k, x + k])
+            }
+        })
+    }
+
+    fn observe(program: FreeVec<u32>) -> Vec<Option<u32>> {
+        let mut out = Vec::new();
+        observe_vec(program, &mut out);
+        out
+    }
+}
+
+free_laws!(mod free_vec_laws, VecLaws);
+
+//A Functor with a continuation. Observed by feeding it a fixed sequence of inputs.
+#[derive(Clone)]
+enum Ask<'a, A> {
+    Number(Rc<dyn Fn(u32) -> A + 'a>),
+    Tell(u32, A),
+}
+
+impl<'a, A: 'a> Functor<'a, A> for Ask<'a, A> {
+    type Target<T> = Ask<'a, T>;
+
+    fn fmap<B, F>(self, f: F) -> Self::Target<B>
+    where
+        F: Fn(A) -> B + 'a,
+    {
+        match self {
+            Ask::Number(next) => Ask::Number(Rc::new(move |x| f(next(x)))),
+            Ask::Tell(n, next) => Ask::Tell(n, f(next)),
+        }
+    }
+}
+
+free!(<'a>, FreeAsk<'a, A>, Ask<'a, FreeAsk<'a, A>>);
+
+struct AskLaws;
+
+impl<'a> FreeLaws<'a> for AskLaws {
+    type Value = u32;
+    type Program = FreeAsk<'a, u32>;
+    type FnProgram = FreeAsk<'a, ApplyFn<'a, u32, u32>>;
+    type Observation = (Vec<u32>, u32);
+
+    fn value(rng: &mut SplitMix64) -> u32 {
+        small(rng)
+    }
+
+    fn program(rng: &mut SplitMix64) -> FreeAsk<'a, u32> {
+        match rng.below(3) {
+            0 => FreeAsk::Pure(small(rng)),
+            1 => FreeAsk::lift_f(Ask::Tell(small(rng), small(rng))),
+            _ => {
+                let k = small(rng);
+                FreeAsk::lift_f(Ask::Number(Rc::new(move |x| x * k)))
+            }
+        }
+    }
+
+    fn function(rng: &mut SplitMix64) -> Rc<dyn Fn(u32) -> u32 + 'a> {
+        let k = small(rng);
+        Rc::new(move |x| x + k)
+    }
+
+    fn kleisli(rng: &mut SplitMix64) -> Rc<dyn Fn(u32) -> FreeAsk<'a, u32> + 'a> {
+        let k = small(rng);
+        Rc::new(move |x| {
+            if x % 2 == 0 {
+                FreeAsk::lift_f(Ask::Tell(x, x + k))
+            } else {
+                FreeAsk::lift_f(Ask::Number(Rc::new(move |y| x + y + k)))
+            }
+        })
+    }
+
+    fn observe(mut program: FreeAsk<'a, u32>) -> (Vec<u32>, u32) {
+        let mut told = Vec::new();
+        let mut input = 1;
+        loop {
+            match program.resume() {
+                Ok(x) => return (told, x),
+                Err(Ask::Tell(n, next)) => {
+                    told.push(n);
+                    program = next;
+                }
+                Err(Ask::Number(next)) => {
+                    input += 1;
+                    program = next(input);
+                }
+            }
+        }
+    }
+}
+
+free_laws!(mod free_ask_laws, AskLaws, 0xA5C, 200);
+
+//A broken Functor, that reverses the order of its elements when mapped over.
+#[derive(Clone)]
+struct Backwards<A>(Vec<A>);
+
+impl<'a, A> Functor<'a, A> for Backwards<A> {
+    type Target<T> = Backwards<T>;
+
+    fn fmap<B, F>(self, f: F) -> Self::Target<B>
+    where
+        F: Fn(A) -> B + 'a,
+    {
+        Backwards(self.0.into_iter().rev().map(f).collect())
+    }
+}
+
+free!(FreeBackwards<A>, Backwards<FreeBackwards<A>>);
+
+struct BackwardsLaws;
+
+impl<'a> FreeLaws<'a> for BackwardsLaws {
+    type Value = u32;
+    type Program = FreeBackwards<u32>;
+    type FnProgram = FreeBackwards<ApplyFn<'a, u32, u32>>;
+    type Observation = Vec<u32>;
+
+    fn value(rng: &mut SplitMix64) -> u32 {
+        small(rng)
+    }
+
+    fn program(rng: &mut SplitMix64) -> FreeBackwards<u32> {
+        FreeBackwards::Free(Box::new(Backwards(
+            (0..3).map(|_| FreeBackwards::Pure(small(rng))).collect(),
+        )))
+    }
+
+    fn function(_: &mut SplitMix64) -> Rc<dyn Fn(u32) -> u32 + 'a> {
+        Rc::new(|x| x)
+    }
+
+    fn kleisli(_: &mut SplitMix64) -> Rc<dyn Fn(u32) -> FreeBackwards<u32> + 'a> {
+        Rc::new(FreeBackwards::Pure)
+    }
+
+    fn observe(program: FreeBackwards<u32>) -> Vec<u32> {
+        match program.resume() {
+            Ok(x) => vec![x],
+            Err(Backwards(v)) => v.into_iter().flat_map(Self::observe).collect(),
+        }
+    }
+}
+
+#[test]
+fn test_broken_functor_is_detected() {
+    let violation = functor_identity::<BackwardsLaws>(1, 10).unwrap_err();
+    assert_eq!(violation.law, "functor identity");
+    assert_eq!(violation.case, 0);
+    assert!(violation.to_string().contains("seed 0x1"));
+    assert!(monad_associativity::<BackwardsLaws>(1, 10).is_err());
+}
@@ -0,0 +1,102 @@
+#![deny(clippy::pedantic)]
+#![deny(clippy::all)]
+//! Tests that the `MonadFree` trait is implemented for the different flavours of `free!()` invocations, and that
+//! generic code can be written against it.
+
+use higher::{Bind, Functor};
+use higher_free_macro::{free, MonadFree};
+use std::rc::Rc;
+
+free!(FreeVec<A>, Vec<FreeVec<A>>);
+
+#[derive(Functor, Clone)]
+struct WithLifetimes<'a, 'b, A> {
+    s1: &'a str,
+    s2: &'b str,
+    next: A,
+}
+
+free!(
+    FreeWithLifetimes<'a, 'b, A>,
+    WithLifetimes<'a, 'b, FreeWithLifetimes<'a, 'b, A>>
+);
+
+#[derive(Clone)]
+struct Conti<'a, A, B>(Rc<dyn Fn(B) -> A + 'a>);
+
+impl<'a, A: 'a, B: 'a> Functor<'a, A> for Conti<'a, A, B> {
+    type Target<T> = Conti<'a, T, B>;
+
+    fn fmap<C, F>(self, f: F) -> Self::Target<C>
+    where
+        F: Fn(A) -> C + 'a,
+    {
+        Conti(Rc::new(move |x| f((self.0)(x))))
+    }
+}
+
+free!(<'a>, FreeConti<'a,A,B>, Conti<'a,FreeConti<'a,A,B>,B>);
+
+/// Drives a program to completion, passing each layer to `step`.
+fn run<'a, M: MonadFree<'a>>(mut program: M, mut step: impl FnMut(M::Functor) -> M) -> M::Value {
+    loop {
+        match program.resume() {
+            Ok(value) => return value,
+            Err(layer) => program = step(layer),
+        }
+    }
+}
+
+/// Takes the outermost layer apart and puts it back together.
+fn rebuild<'a, M: MonadFree<'a>>(program: M) -> M {
+    match program.resume() {
+        Ok(value) => M::pure(value),
+        Err(layer) => M::wrap(layer),
+    }
+}
+
+#[test]
+fn test_monad_free_vec() {
+    let program = <FreeVec<u32> as MonadFree>::lift_f(vec![1, 2, 3]);
+    let program = rebuild(program);
+    assert_eq!(program.clone().retract(), vec![1, 2, 3]);
+    //always take the last branch.
+    assert_eq!(run(program, |mut v| v.pop().unwrap()), 3);
+    assert_eq!(
+        run(<FreeVec<_> as MonadFree>::pure(5), |_| unreachable!()),
+        5
+    );
+}
+
+#[test]
+fn test_monad_free_with_lifetimes() {
+    let s1 = String::from("First");
+    let program = <FreeWithLifetimes<_> as MonadFree>::lift_f(WithLifetimes {
+        s1: &s1,
+        s2: "Second",
+        next: 2,
+    });
+    let program = rebuild(program).bind(|x| {
+        FreeWithLifetimes::lift_f(WithLifetimes {
+            s1: "Third",
+            s2: "Fourth",
+            next: x * 3,
+        })
+    });
+    let mut seen = Vec::new();
+    let result = run(program, |layer| {
+        seen.push((layer.s1, layer.s2));
+        layer.next
+    });
+    assert_eq!(result, 6);
+    assert_eq!(seen, vec![("First", "Second"), ("Third", "Fourth")]);
+}
+
+#[test]
+fn test_monad_free_lifetime() {
+    let offset = 7;
+    let program = <FreeConti<_, _> as MonadFree>::lift_f(Conti(Rc::new(move |x: i32| x + offset)));
+    let program = rebuild(program).fmap(|x| x * 2);
+    assert!(rebuild(FreeConti::<_, i32>::Pure(1)).is_pure());
+    assert_eq!(run(program, |c| (c.0)(3)), 20);
+}
@@ -0,0 +1,110 @@
+#![deny(clippy::pedantic)]
+#![deny(clippy::all)]
+//! Tests the generated `tail_rec_m` function, and that interpreters can run the resulting loops in constant stack space.
+
+use higher::Functor;
+use higher_free_macro::{free, free_do};
+use std::ops::ControlFlow;
+use std::rc::Rc;
+
+#[derive(Clone)]
+enum Counter<'a, 'b, A, N> {
+    Tick(&'b str, Rc<dyn Fn(N) -> A + 'a>),
+    Report(N, A),
+}
+
+impl<'a, 'b, A: 'a, N: 'a> Functor<'a, A> for Counter<'a, 'b, A, N> {
+    type Target<T> = Counter<'a, 'b, T, N>;
+
+    fn fmap<C, F>(self, f: F) -> Self::Target<C>
+    where
+        F: Fn(A) -> C + 'a,
+    {
+        match self {
+            Counter::Tick(label, next) => Counter::Tick(label, Rc::new(move |x| f(next(x)))),
+            Counter::Report(n, next) => Counter::Report(n, f(next)),
+        }
+    }
+}
+
+free!(<'a>, FreeCounter<'a, 'b, A, N>, Counter<'a, 'b, FreeCounter<'a, 'b, A, N>, N>);
+
+fn tick<'a, 'b: 'a>(label: &'b str) -> FreeCounter<'a, 'b, u64, u64> {
+    FreeCounter::lift_f(Counter::Tick(label, Rc::new(|x| x)))
+}
+
+fn report<'a, 'b: 'a>(n: u64) -> FreeCounter<'a, 'b, (), u64> {
+    FreeCounter::lift_f(Counter::Report(n, ()))
+}
+
+//Adds up the ticks until they exceed `limit`, reporting every millionth step.
+fn count_to<'a, 'b: 'a>(limit: u64) -> FreeCounter<'a, 'b, u64, u64> {
+    FreeCounter::tail_rec_m((0u64, 0u64), move |(steps, sum)| {
+        free_do! {
+            x <= tick("tick");
+            let (steps, sum) = (steps + 1, sum + x);
+            if steps % 1_000_000 == 0 {
+                report(steps)
+            }
+            yield if sum > limit { ControlFlow::Break(steps) } else { ControlFlow::Continue((steps, sum)) }
+        }
+    })
+}
+
+fn interpret<'a, 'b: 'a, A: 'a>(mut program: FreeCounter<'a, 'b, A, u64>) -> (A, Vec<u64>) {
+    let mut reports = Vec::new();
+    loop {
+        match program.resume() {
+            Ok(a) => return (a, reports),
+            Err(Counter::Tick(_, next)) => program = next(1),
+            Err(Counter::Report(n, next)) => {
+                reports.push(n);
+                program = next;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_tail_rec_m_constant_stack() {
+    //A tiny stack. A loop that nests deeper with every iteration would overflow it long before the end.
+    let result = std::thread::Builder::new()
+        .stack_size(256 * 1024)
+        .spawn(|| interpret(count_to(2_999_999)))
+        .unwrap()
+        .join()
+        .unwrap();
+    assert_eq!(result, (3_000_000, vec![1_000_000, 2_000_000, 3_000_000]));
+}
+
+free!(FreeVec<A>, Vec<FreeVec<A>>);
+
+#[test]
+fn test_tail_rec_m_pure_steps() {
+    let program = FreeVec::tail_rec_m(0u32, |n| {
+        FreeVec::Pure(if n < 5_000_000 {
+            ControlFlow::Continue(n + 1)
+        } else {
+            ControlFlow::Break(n)
+        })
+    });
+    assert_eq!(program.into_pure(), Some(5_000_000));
+}
+
+#[test]
+fn test_tail_rec_m_branching() {
+    let program = FreeVec::tail_rec_m(1u32, |n| {
+        if n > 4 {
+            FreeVec::Pure(ControlFlow::Break(n))
+        } else {
+            FreeVec::lift_f(vec![
+                ControlFlow::Continue(n * 2),
+                ControlFlow::Continue(n * 3),
+            ])
+        }
+    });
+    assert_eq!(
+        program.into_leaves().collect::<Vec<_>>(),
+        vec![8, 12, 6, 6, 9]
+    );
+}